@@ -8,6 +8,15 @@ struct World {
     ambient_light: f32,
 };
 
+// How much light reaches a fully occluded voxel corner.
+const MIN_AMBIENT_OCCLUSION: f32 = 0.4;
+// The highest block light level, the block light is passed along divided by it.
+const MAX_LIGHT_LEVEL: f32 = 15.0;
+// How much darker each light level is than the one above it.
+const BLOCK_LIGHT_FALLOFF: f32 = 0.8;
+// The color of the block light.
+const BLOCK_LIGHT_COLOR: vec3<f32> = vec3<f32>(1.0, 0.9, 0.7);
+
 @group(0) @binding(0)
 var<uniform> camera: Camera;
 
@@ -36,11 +45,13 @@ fn lighting_fragment(
     let normal = textureSample(normal_texture, input_sampler, tex_coords);
     let depth = textureSample(depth_texture, input_sampler, tex_coords);
 
-    var color = albedo;
-    let brightness = calculate_brightness(normal.xyz);
-    color *= brightness;
+    // The sun only reaches as far as the sky light, the voxels are lit by whichever is brighter,
+    // the sun or the block light.
+    let sun_light = calculate_brightness(normal.xyz) * calculate_sky_light(geometry.a);
+    let light = max(vec3<f32>(sun_light), calculate_block_light(albedo.a));
+    let color = albedo.rgb * light * mix(MIN_AMBIENT_OCCLUSION, 1.0, normal.a);
 
-    return color;
+    return vec4<f32>(color, 1.0);
 }
 
 fn calculate_brightness(normal: vec3<f32>) -> f32 {
@@ -51,4 +62,15 @@ fn calculate_brightness(normal: vec3<f32>) -> f32 {
         world.ambient_light
     );
     return brightness;
+}
+
+fn calculate_block_light(block_light: f32) -> vec3<f32> {
+    // Every light level is a bit darker than the one above it, so the light fades out smoothly.
+    let level = block_light * MAX_LIGHT_LEVEL;
+    return BLOCK_LIGHT_COLOR * pow(BLOCK_LIGHT_FALLOFF, MAX_LIGHT_LEVEL - level) * step(0.5, level);
+}
+
+fn calculate_sky_light(sky_light: f32) -> f32 {
+    // The sky light fades out the same way as the block light, but a voxel the sky does not reach is not completely black.
+    return pow(BLOCK_LIGHT_FALLOFF, (1.0 - sky_light) * MAX_LIGHT_LEVEL);
 }
\ No newline at end of file
@@ -0,0 +1,53 @@
+struct Camera {
+    view_proj: mat4x4f,
+    position: vec4f,
+};
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+struct InstanceInput {
+    @location(0) min: vec3f,
+    @location(1) max: vec3f,
+    @location(2) color: vec4f,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4f,
+    @location(0) color: vec4f,
+};
+
+// The corners of a unit cube, two for every one of the 12 edges.
+const CORNERS = array<vec3f, 24>(
+    vec3f(0.0, 0.0, 0.0), vec3f(1.0, 0.0, 0.0),
+    vec3f(1.0, 0.0, 0.0), vec3f(1.0, 0.0, 1.0),
+    vec3f(1.0, 0.0, 1.0), vec3f(0.0, 0.0, 1.0),
+    vec3f(0.0, 0.0, 1.0), vec3f(0.0, 0.0, 0.0),
+    vec3f(0.0, 1.0, 0.0), vec3f(1.0, 1.0, 0.0),
+    vec3f(1.0, 1.0, 0.0), vec3f(1.0, 1.0, 1.0),
+    vec3f(1.0, 1.0, 1.0), vec3f(0.0, 1.0, 1.0),
+    vec3f(0.0, 1.0, 1.0), vec3f(0.0, 1.0, 0.0),
+    vec3f(0.0, 0.0, 0.0), vec3f(0.0, 1.0, 0.0),
+    vec3f(1.0, 0.0, 0.0), vec3f(1.0, 1.0, 0.0),
+    vec3f(1.0, 0.0, 1.0), vec3f(1.0, 1.0, 1.0),
+    vec3f(0.0, 0.0, 1.0), vec3f(0.0, 1.0, 1.0),
+);
+
+@vertex
+fn outline_vertex(
+    @builtin(vertex_index) vertex_index: u32,
+    instance: InstanceInput,
+) -> VertexOutput {
+    var corners = CORNERS;
+    let position = mix(instance.min, instance.max, corners[vertex_index]);
+
+    return VertexOutput(
+        camera.view_proj * vec4f(position, 1.0),
+        instance.color,
+    );
+}
+
+@fragment
+fn outline_fragment(in: VertexOutput) -> @location(0) vec4f {
+    return in.color;
+}
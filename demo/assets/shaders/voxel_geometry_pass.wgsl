@@ -16,14 +16,33 @@ var voxel_textures: texture_2d_array<f32>;
 @group(1) @binding(1)
 var voxel_sampler: sampler;
 
+// The packed vertex, see `rendering::vertex::Vertex`.
 struct VertexInput {
-    @location(0) position: vec3f,
-    @location(1) tex_coords: vec2f,
-    @location(2) normal: vec3f,
-    @location(3) texture_index: vec3u,
+    @location(0) data: vec2u,
 };
 
-const VERTEX_INPUT_COUNT: u32 = 4;
+const VERTEX_INPUT_COUNT: u32 = 1;
+// The amount of steps per voxel that the positions and the texture coordinates are packed in.
+const POSITION_STEPS: f32 = 2.0;
+// The highest ambient occlusion level.
+const MAX_AMBIENT_OCCLUSION: f32 = 3.0;
+// The normals of the face directions, followed by the normals of the two sides of the cross diagonals.
+const NORMALS: array<vec3f, 10> = array<vec3f, 10>(
+    vec3f(0.0, -1.0, 0.0),
+    vec3f(0.0, 1.0, 0.0),
+    vec3f(-1.0, 0.0, 0.0),
+    vec3f(1.0, 0.0, 0.0),
+    vec3f(0.0, 0.0, 1.0),
+    vec3f(0.0, 0.0, -1.0),
+    vec3f(-0.70710677, 0.0, 0.70710677),
+    vec3f(0.70710677, 0.0, -0.70710677),
+    vec3f(-0.70710677, 0.0, -0.70710677),
+    vec3f(0.70710677, 0.0, 0.70710677),
+);
+// The highest light level.
+const MAX_LIGHT_LEVEL: f32 = 15.0;
+// Texels with a lower alpha than this are discarded.
+const CUTOUT_THRESHOLD: f32 = 0.5;
 
 struct InstanceInput {
     @location(VERTEX_INPUT_COUNT) model_matrix0: vec4f,
@@ -36,8 +55,11 @@ struct VertexOutput {
     @builtin(position) clip_position: vec4f,
     @location(0) tex_coords: vec2f,
     @location(1) normal: vec3f,
-    @location(2) texture_index: vec3u,
+    @location(2) @interpolate(flat) texture_layer: u32,
     @location(3) world_position: vec3f,
+    @location(4) ambient_occlusion: f32,
+    @location(5) block_light: f32,
+    @location(6) sky_light: f32,
 };
 
 @vertex
@@ -51,15 +73,19 @@ fn voxel_vertex(
         instance.model_matrix2,
         instance.model_matrix3
     );
-    let world_pos = model_matrix * vec4<f32>(vertex.position, 1.0);
+    let position = unpack_position(vertex.data.x);
+    let world_pos = model_matrix * vec4<f32>(position, 1.0);
 
     var out: VertexOutput;
 
     out.clip_position = camera.view_proj * world_pos;
-    out.tex_coords = vertex.tex_coords;
-    out.normal = normalize(vertex.normal);
-    out.texture_index = vertex.texture_index;
+    out.tex_coords = unpack_position(vertex.data.y).xy;
+    out.normal = unpack_normal((vertex.data.x >> 24u) & 0xFu);
+    out.texture_layer = vertex.data.y >> 24u;
     out.world_position = world_pos.xyz;
+    out.ambient_occlusion = f32((vertex.data.x >> 28u) & 0x3u) / MAX_AMBIENT_OCCLUSION;
+    out.block_light = f32((vertex.data.y >> 16u) & 0xFu) / MAX_LIGHT_LEVEL;
+    out.sky_light = f32((vertex.data.y >> 20u) & 0xFu) / MAX_LIGHT_LEVEL;
 
     return out;
 }
@@ -78,23 +104,37 @@ fn voxel_fragment(in: VertexOutput) -> FragmentOutput {
         voxel_textures, 
         voxel_sampler, 
         -in.tex_coords, 
-        get_texture_index(in.normal, in.texture_index)
+        in.texture_layer
     );
 
-    out.albedo = vec4<f32>(texture_color.rgb, 1.0);
+    // Cutout voxels have fully transparent texels that should not be rendered.
+    if texture_color.a < CUTOUT_THRESHOLD {
+        discard;
+    }
+
+    // The block light is stored in the alpha of the albedo for the lighting pass.
+    out.albedo = vec4<f32>(texture_color.rgb, in.block_light);
     // out.albedo = vec4<f32>(1.0);
-    out.geometry = vec4<f32>(in.world_position, 1.0);
-    out.normals = vec4<f32>(in.normal, 1.0);
+    // The sky light is stored in the alpha of the geometry for the lighting pass.
+    out.geometry = vec4<f32>(in.world_position, in.sky_light);
+    // The ambient occlusion is stored in the alpha of the normals for the lighting pass.
+    out.normals = vec4<f32>(in.normal, in.ambient_occlusion);
 
     return out;
 }
 
-fn get_texture_index(normal: vec3f, index: vec3u) -> u32 {
-    if normal.y > 0.0 {
-        return index.x;
-    } else if normal.y < 0.0 {
-        return index.z;
-    } else {
-        return index.y;
-    }
-}
\ No newline at end of file
+// Unpacks a position or texture coordinates that are packed in 8 bits per axis.
+fn unpack_position(data: u32) -> vec3f {
+    return vec3f(
+        f32(data & 0xFFu),
+        f32((data >> 8u) & 0xFFu),
+        f32((data >> 16u) & 0xFFu)
+    ) / POSITION_STEPS;
+}
+
+// Returns the normal with the specified index.
+fn unpack_normal(index: u32) -> vec3f {
+    // Constant arrays can not be indexed dynamically, so the normals are copied into a variable.
+    var normals = NORMALS;
+    return normals[index];
+}
@@ -0,0 +1,152 @@
+struct Camera {
+    view_proj: mat4x4f,
+    position: vec4f,
+};
+
+struct World {
+    sun_direction: vec3f,
+    ambient_light: f32,
+};
+
+// How much light reaches a fully occluded voxel corner.
+const MIN_AMBIENT_OCCLUSION: f32 = 0.4;
+// The highest block light level, the block light is passed along divided by it.
+const MAX_LIGHT_LEVEL: f32 = 15.0;
+// How much darker each light level is than the one above it.
+const BLOCK_LIGHT_FALLOFF: f32 = 0.8;
+// The color of the block light.
+const BLOCK_LIGHT_COLOR: vec3<f32> = vec3<f32>(1.0, 0.9, 0.7);
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+@group(1) @binding(0)
+var voxel_textures: texture_2d_array<f32>;
+@group(1) @binding(1)
+var voxel_sampler: sampler;
+
+@group(2) @binding(0)
+var<uniform> world: World;
+
+// The packed vertex, see `rendering::vertex::Vertex`.
+struct VertexInput {
+    @location(0) data: vec2u,
+};
+
+const VERTEX_INPUT_COUNT: u32 = 1;
+// The amount of steps per voxel that the positions and the texture coordinates are packed in.
+const POSITION_STEPS: f32 = 2.0;
+// The highest ambient occlusion level.
+const MAX_AMBIENT_OCCLUSION: f32 = 3.0;
+// The normals of the face directions, followed by the normals of the two sides of the cross diagonals.
+const NORMALS: array<vec3f, 10> = array<vec3f, 10>(
+    vec3f(0.0, -1.0, 0.0),
+    vec3f(0.0, 1.0, 0.0),
+    vec3f(-1.0, 0.0, 0.0),
+    vec3f(1.0, 0.0, 0.0),
+    vec3f(0.0, 0.0, 1.0),
+    vec3f(0.0, 0.0, -1.0),
+    vec3f(-0.70710677, 0.0, 0.70710677),
+    vec3f(0.70710677, 0.0, -0.70710677),
+    vec3f(-0.70710677, 0.0, -0.70710677),
+    vec3f(0.70710677, 0.0, 0.70710677),
+);
+
+struct InstanceInput {
+    @location(VERTEX_INPUT_COUNT) model_matrix0: vec4f,
+    @location(VERTEX_INPUT_COUNT + 1) model_matrix1: vec4f,
+    @location(VERTEX_INPUT_COUNT + 2) model_matrix2: vec4f,
+    @location(VERTEX_INPUT_COUNT + 3) model_matrix3: vec4f,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4f,
+    @location(0) tex_coords: vec2f,
+    @location(1) normal: vec3f,
+    @location(2) @interpolate(flat) texture_layer: u32,
+    @location(3) ambient_occlusion: f32,
+    @location(4) block_light: f32,
+    @location(5) sky_light: f32,
+};
+
+@vertex
+fn voxel_vertex(
+    vertex: VertexInput,
+    instance: InstanceInput
+) -> VertexOutput {
+    let model_matrix = mat4x4<f32>(
+        instance.model_matrix0,
+        instance.model_matrix1,
+        instance.model_matrix2,
+        instance.model_matrix3
+    );
+    let position = unpack_position(vertex.data.x);
+    let world_pos = model_matrix * vec4<f32>(position, 1.0);
+
+    var out: VertexOutput;
+
+    out.clip_position = camera.view_proj * world_pos;
+    out.tex_coords = unpack_position(vertex.data.y).xy;
+    out.normal = unpack_normal((vertex.data.x >> 24u) & 0xFu);
+    out.texture_layer = vertex.data.y >> 24u;
+    out.ambient_occlusion = f32((vertex.data.x >> 28u) & 0x3u) / MAX_AMBIENT_OCCLUSION;
+    out.block_light = f32((vertex.data.y >> 16u) & 0xFu) / MAX_LIGHT_LEVEL;
+    out.sky_light = f32((vertex.data.y >> 20u) & 0xFu) / MAX_LIGHT_LEVEL;
+
+    return out;
+}
+
+// Translucent voxels are not in the GBuffer, so they are lit here the same way as the lighting pass does.
+@fragment
+fn voxel_fragment(in: VertexOutput) -> @location(0) vec4f {
+    let texture_color = textureSample(
+        voxel_textures,
+        voxel_sampler,
+        -in.tex_coords,
+        in.texture_layer
+    );
+
+    let sun_light = calculate_brightness(in.normal) * calculate_sky_light(in.sky_light);
+    let light = max(vec3<f32>(sun_light), calculate_block_light(in.block_light));
+    let ambient_occlusion = mix(MIN_AMBIENT_OCCLUSION, 1.0, in.ambient_occlusion);
+
+    return vec4<f32>(texture_color.rgb * light * ambient_occlusion, texture_color.a);
+}
+
+
+fn calculate_brightness(normal: vec3<f32>) -> f32 {
+    let brightness = max(
+        // Negate the sun direction to get the direction
+        // from the fragment to the sun
+        dot(normal, -world.sun_direction),
+        world.ambient_light
+    );
+    return brightness;
+}
+
+fn calculate_block_light(block_light: f32) -> vec3<f32> {
+    // Every light level is a bit darker than the one above it, so the light fades out smoothly.
+    let level = block_light * MAX_LIGHT_LEVEL;
+    return BLOCK_LIGHT_COLOR * pow(BLOCK_LIGHT_FALLOFF, MAX_LIGHT_LEVEL - level) * step(0.5, level);
+}
+
+fn calculate_sky_light(sky_light: f32) -> f32 {
+    // The sky light fades out the same way as the block light, but a voxel the sky does not reach is not completely black.
+    return pow(BLOCK_LIGHT_FALLOFF, (1.0 - sky_light) * MAX_LIGHT_LEVEL);
+}
+
+// Unpacks a position or texture coordinates that are packed in 8 bits per axis.
+fn unpack_position(data: u32) -> vec3f {
+    return vec3f(
+        f32(data & 0xFFu),
+        f32((data >> 8u) & 0xFFu),
+        f32((data >> 16u) & 0xFFu)
+    ) / POSITION_STEPS;
+}
+
+// Returns the normal with the specified index.
+fn unpack_normal(index: u32) -> vec3f {
+    // Constant arrays can not be indexed dynamically, so the normals are copied into a variable.
+    var normals = NORMALS;
+    return normals[index];
+}
@@ -1,6 +1,6 @@
 use bevy_ecs::component::Component;
 use nalgebra::{vector, Matrix4, Perspective3, Point3, Unit, Vector3};
-use voxel_engine::ecs::resources::camera::CameraUniform;
+use voxel_engine::{common::aabb::Aabb, ecs::resources::camera::CameraUniform};
 
 /// A tag for the current camera controller.
 #[derive(Component)]
@@ -65,3 +65,82 @@ impl CameraController {
         Unit::new_normalize(vector![yaw_cos * pitch_cos, pitch_sin, yaw_sin * pitch_cos])
     }
 }
+
+/// Makes a camera controller walk on the terrain with gravity instead of flying through it.
+#[derive(Component, Debug)]
+pub struct CharacterController {
+    /// The current velocity in blocks per second.
+    pub velocity: Vector3<f32>,
+    /// Whether the character is standing on something.
+    pub on_ground: bool,
+    /// Whether the character is crouching.
+    pub crouching: bool,
+    /// The width of the bounding box on the x and z axes.
+    pub width: f32,
+    /// The height of the bounding box while standing.
+    pub height: f32,
+    /// The height of the bounding box while crouching.
+    pub crouch_height: f32,
+    /// How far the eyes are below the top of the bounding box.
+    pub eye_offset: f32,
+    /// The movement speed while walking.
+    pub walk_speed: f32,
+    /// The movement speed while sprinting.
+    pub sprint_speed: f32,
+    /// The movement speed while crouching.
+    pub crouch_speed: f32,
+    /// The upwards speed of a jump.
+    pub jump_speed: f32,
+    /// The downwards acceleration.
+    pub gravity: f32,
+    /// The largest falling speed.
+    pub max_fall_speed: f32,
+    /// How high of a ledge the character walks up without jumping.
+    pub step_height: f32,
+}
+
+impl Default for CharacterController {
+    fn default() -> Self {
+        Self {
+            velocity: Vector3::zeros(),
+            on_ground: false,
+            crouching: false,
+            width: 0.6,
+            height: 1.8,
+            crouch_height: 1.5,
+            eye_offset: 0.18,
+            walk_speed: 4.3,
+            sprint_speed: 5.6,
+            crouch_speed: 1.3,
+            jump_speed: 8.4,
+            gravity: 28.0,
+            max_fall_speed: 60.0,
+            step_height: 0.6,
+        }
+    }
+}
+
+impl CharacterController {
+    /// Returns the current height of the bounding box.
+    pub fn get_current_height(&self) -> f32 {
+        if self.crouching {
+            self.crouch_height
+        } else {
+            self.height
+        }
+    }
+
+    /// Returns the height of the eyes above the feet.
+    pub fn get_eye_height(&self) -> f32 {
+        self.get_current_height() - self.eye_offset
+    }
+
+    /// Returns the bounding box of a character whose feet are at the specified position.
+    pub fn get_aabb(&self, feet_position: Point3<f32>, height: f32) -> Aabb {
+        let half_width = self.width / 2.0;
+        Aabb::new(
+            feet_position - vector![half_width, 0.0, half_width],
+            feet_position + vector![half_width, height, half_width],
+        )
+    }
+}
@@ -1,14 +1,15 @@
 use bevy_ecs::{
     event::EventReader,
-    query::{Changed, With},
+    query::{Changed, With, Without},
     schedule::IntoSystemConfigs as _,
-    system::{Query, Res},
+    system::{Query, Res, ResMut},
 };
-use component::{CameraController, CurrentCameraController};
+pub use component::{CameraController, CharacterController, CurrentCameraController};
 use nalgebra::{point, vector, Matrix3, Vector3};
 use voxel_engine::{
     application::Application,
     ecs::{
+        components::ChunkLoader,
         events::window_events::{MouseMotion, MouseScrollDelta, WindowEvent, WinitWindowEvent},
         packages::{
             input_provider::{self, InputProvider, KeyCode, MouseButton},
@@ -18,14 +19,15 @@ use voxel_engine::{
             InitializationStage, Package,
         },
         resources::Camera,
-        schedules::{Render, SentWindowEvent},
+        schedules::{Render, SentWindowEvent, Update},
         systems,
     },
 };
 
-use crate::config::Config;
+use crate::{config::Config, world::CurrentWorld};
 
 mod component;
+mod walking;
 
 /// Package for `CameraController`.
 pub struct CameraControllerPackage;
@@ -40,13 +42,31 @@ impl Package for CameraControllerPackage {
             }
         };
 
+        let position = match app.get_resource::<CurrentWorld>() {
+            Some(current_world) => current_world.metadata.camera_position.into(),
+            None => point![0.0, 1.0, 4.0],
+        };
+        let aspect_ratio = window.get_aspect_ratio();
         let camera_controller = CameraController {
-            position: point![0.0, 1.0, 4.0],
-            aspect_ratio: window.get_aspect_ratio(),
+            position,
+            aspect_ratio,
             ..Default::default()
         };
 
-        app.spawn((camera_controller, CurrentCameraController));
+        app.spawn((
+            camera_controller,
+            CurrentCameraController,
+            ChunkLoader::default(),
+        ));
+        // The walking camera controller is idle until the toggle key switches to it.
+        app.spawn((
+            CameraController {
+                position,
+                aspect_ratio,
+                ..Default::default()
+            },
+            CharacterController::default(),
+        ));
         app.add_systems(
             SentWindowEvent,
             (
@@ -55,6 +75,15 @@ impl Package for CameraControllerPackage {
                 mouse_motion_listener_system.after(input_provider::mouse_moved_listener_system),
             ),
         );
+        app.add_systems(
+            Update,
+            (
+                walking::toggle_walking_system,
+                walking::walking_system,
+                update_chunk_loader_system,
+            )
+                .chain(),
+        );
         app.add_systems(
             Render,
             (update_camera_system.before(systems::render_system),),
@@ -68,10 +97,10 @@ impl Package for CameraControllerPackage {
     }
 }
 
-/// Listens for mouse motion events and updates the camera controller accordingly.
+/// Listens for mouse motion events and updates the current camera controller accordingly.
 fn mouse_motion_listener_system(
     mut events: EventReader<MouseMotion>,
-    mut camera_controllers: Query<&mut CameraController>,
+    mut camera_controllers: Query<&mut CameraController, With<CurrentCameraController>>,
     input_provider: Res<InputProvider>,
     config: Res<Config>,
 ) {
@@ -121,9 +150,12 @@ fn window_event_listener_system(
     }
 }
 
-/// Updates the camera controller by moving it based on keyboard input.
+/// Updates the flying camera controller by moving it based on keyboard input.
 fn update_system(
-    mut camera_controllers: Query<&mut CameraController>,
+    mut camera_controllers: Query<
+        &mut CameraController,
+        (With<CurrentCameraController>, Without<CharacterController>),
+    >,
     time: Res<Time>,
     input_provider: Res<InputProvider>,
 ) {
@@ -170,13 +202,31 @@ fn update_system(
     }
 }
 
+/// Moves the chunk loaders of the camera controllers along with them and applies the configured render distances.
+fn update_chunk_loader_system(
+    mut query: Query<(&CameraController, &mut ChunkLoader)>,
+    config: Res<Config>,
+) {
+    for (controller, mut loader) in query.iter_mut() {
+        if loader.position != controller.position {
+            loader.position = controller.position;
+        }
+        if loader.horizontal_render_distance != config.horizontal_render_distance
+            || loader.vertical_render_distance != config.vertical_render_distance
+        {
+            loader.horizontal_render_distance = config.horizontal_render_distance;
+            loader.vertical_render_distance = config.vertical_render_distance;
+        }
+    }
+}
+
 /// Updates the camera with the camera controller that is marked with `CurrentCameraController`.
 pub fn update_camera_system(
     query: Query<&CameraController, (With<CurrentCameraController>, Changed<CameraController>)>,
     render_context: Res<RenderContext>,
-    camera: Option<Res<Camera>>,
+    camera: Option<ResMut<Camera>>,
 ) {
-    if let Some(camera) = camera {
+    if let Some(mut camera) = camera {
         if let Ok(controller) = query.get_single() {
             camera.update_camera(&render_context.queue, controller.construct_uniform());
         }
@@ -0,0 +1,257 @@
+use bevy_ecs::{
+    entity::Entity,
+    query::With,
+    system::{Commands, Local, Query, Res},
+};
+use nalgebra::{point, vector, Point3, Vector3};
+use voxel_engine::{
+    common::{aabb::Aabb, chunk, collision::CollisionResult},
+    ecs::{
+        components::{Chunk, ChunkLoader, ChunkState},
+        packages::{
+            chunk::ChunkMap,
+            input_provider::{InputProvider, KeyCode},
+            time::Time,
+            voxel_registry::VoxelRegistry,
+        },
+    },
+};
+
+use super::component::{CameraController, CharacterController, CurrentCameraController};
+
+/// The key that switches between flying and walking.
+const TOGGLE_KEY: KeyCode = KeyCode::KeyF;
+/// The longest time step of a single physics step, longer frames are split into multiple steps.
+const MAX_STEP_SECONDS: f32 = 1.0 / 60.0;
+/// The longest frame that gets simulated, so a hitch does not launch the character.
+const MAX_FRAME_SECONDS: f32 = 0.25;
+
+/// Moves the `CurrentCameraController` tag and the `ChunkLoader` between the flying and the
+/// walking camera controller when the toggle key is pressed.
+pub(super) fn toggle_walking_system(
+    mut commands: Commands,
+    mut controllers: Query<(
+        Entity,
+        &mut CameraController,
+        Option<&mut CharacterController>,
+        Option<&CurrentCameraController>,
+    )>,
+    input_provider: Res<InputProvider>,
+    mut was_pressed: Local<bool>,
+) {
+    let is_pressed = input_provider.is_pressed(TOGGLE_KEY);
+    let just_pressed = is_pressed && !*was_pressed;
+    *was_pressed = is_pressed;
+    if !just_pressed {
+        return;
+    }
+
+    let (current, position, yaw, pitch, is_walking) =
+        match controllers.iter().find(|(.., current)| current.is_some()) {
+            Some((entity, controller, character, _)) => (
+                entity,
+                controller.position,
+                controller.yaw,
+                controller.pitch,
+                character.is_some(),
+            ),
+            None => return,
+        };
+    let next = controllers
+        .iter_mut()
+        .find(|(entity, _, character, _)| *entity != current && character.is_some() != is_walking);
+    let (next, mut controller, character, _) = match next {
+        Some(next) => next,
+        None => return,
+    };
+
+    controller.position = position;
+    controller.yaw = yaw;
+    controller.pitch = pitch;
+    if let Some(mut character) = character {
+        character.velocity = Vector3::zeros();
+        character.on_ground = false;
+        character.crouching = false;
+    }
+
+    commands
+        .entity(current)
+        .remove::<(CurrentCameraController, ChunkLoader)>();
+    commands
+        .entity(next)
+        .insert((CurrentCameraController, ChunkLoader::default()));
+}
+
+/// Moves the current walking camera controller with gravity and collides it with the terrain.
+pub(super) fn walking_system(
+    mut controllers: Query<
+        (&mut CameraController, &mut CharacterController),
+        With<CurrentCameraController>,
+    >,
+    chunks: Query<&Chunk>,
+    chunk_states: Query<&ChunkState>,
+    chunk_map: Res<ChunkMap>,
+    voxel_registry: Res<VoxelRegistry>,
+    input_provider: Res<InputProvider>,
+    time: Res<Time>,
+) {
+    let (mut controller, mut character) = match controllers.get_single_mut() {
+        Ok(controller) => controller,
+        Err(_) => return,
+    };
+
+    let direction = controller.get_direction();
+    let forward = vector![direction.x, 0.0, direction.z]
+        .try_normalize(f32::EPSILON)
+        .unwrap_or_else(Vector3::zeros);
+    let right = forward.cross(&Vector3::y_axis());
+    let mut wish_direction = Vector3::zeros();
+    if input_provider.is_pressed(KeyCode::KeyW) {
+        wish_direction += forward;
+    }
+    if input_provider.is_pressed(KeyCode::KeyS) {
+        wish_direction -= forward;
+    }
+    if input_provider.is_pressed(KeyCode::KeyD) {
+        wish_direction += right;
+    }
+    if input_provider.is_pressed(KeyCode::KeyA) {
+        wish_direction -= right;
+    }
+    let wish_direction = wish_direction
+        .try_normalize(f32::EPSILON)
+        .unwrap_or_else(Vector3::zeros);
+
+    let mut feet_position = controller.position - vector![0.0, character.get_eye_height(), 0.0];
+    let move_aabb = |aabb: &Aabb, movement: Vector3<f32>| {
+        chunk_map.move_aabb(&chunks, &voxel_registry.voxels, aabb, movement)
+    };
+
+    // Crouching shrinks the box from the top, standing up needs free space above.
+    let wants_to_crouch = input_provider.is_pressed(KeyCode::ControlLeft);
+    if wants_to_crouch != character.crouching {
+        let rise = character.height - character.crouch_height;
+        let crouching_aabb = character.get_aabb(feet_position, character.crouch_height);
+        if wants_to_crouch
+            || move_aabb(&crouching_aabb, vector![0.0, rise, 0.0])
+                .movement
+                .y
+                >= rise
+        {
+            character.crouching = wants_to_crouch;
+        }
+    }
+
+    let speed = if character.crouching {
+        character.crouch_speed
+    } else if input_provider.is_pressed(KeyCode::ShiftLeft) {
+        character.sprint_speed
+    } else {
+        character.walk_speed
+    };
+    character.velocity.x = wish_direction.x * speed;
+    character.velocity.z = wish_direction.z * speed;
+    if character.on_ground && input_provider.is_pressed(KeyCode::Space) {
+        character.velocity.y = character.jump_speed;
+        character.on_ground = false;
+    }
+
+    let mut remaining = time.get_delta_time().get_seconds().min(MAX_FRAME_SECONDS);
+    while remaining > 0.0 {
+        let step = remaining.min(MAX_STEP_SECONDS);
+        remaining -= step;
+
+        character.velocity.y =
+            (character.velocity.y - character.gravity * step).max(-character.max_fall_speed);
+        let movement = character.velocity * step;
+        let aabb = character.get_aabb(feet_position, character.get_current_height());
+
+        // Chunks that are not generated yet are empty, so the character waits instead of falling through them.
+        if !is_area_generated(&aabb, movement, &chunk_map, &chunk_states) {
+            character.velocity = Vector3::zeros();
+            break;
+        }
+
+        let mut result = move_aabb(&aabb, movement);
+        if character.on_ground && (result.contact_normal.x != 0.0 || result.contact_normal.z != 0.0)
+        {
+            if let Some(stepped) =
+                step_up(&aabb, movement, &result, character.step_height, move_aabb)
+            {
+                result = stepped;
+            }
+        }
+
+        if result.contact_normal.y != 0.0 {
+            character.velocity.y = 0.0;
+        }
+        character.on_ground = result.is_on_ground();
+        let center = result.aabb.get_center();
+        feet_position = point![center.x, result.aabb.min.y, center.z];
+    }
+
+    let eye_position = feet_position + vector![0.0, character.get_eye_height(), 0.0];
+    if controller.position != eye_position {
+        controller.position = eye_position;
+    }
+}
+
+/// Tries to walk up a ledge by moving up, then sideways and then back down.
+///
+/// ## Returns
+/// The stepped result if it got further horizontally and ended up on the ground.
+fn step_up(
+    aabb: &Aabb,
+    movement: Vector3<f32>,
+    direct: &CollisionResult,
+    step_height: f32,
+    move_aabb: impl Fn(&Aabb, Vector3<f32>) -> CollisionResult,
+) -> Option<CollisionResult> {
+    let up = move_aabb(aabb, vector![0.0, step_height, 0.0]);
+    let side = move_aabb(&up.aabb, vector![movement.x, 0.0, movement.z]);
+    let down = move_aabb(
+        &side.aabb,
+        vector![0.0, -up.movement.y + movement.y.min(0.0), 0.0],
+    );
+
+    let horizontal_distance = |movement: Vector3<f32>| movement.xz().norm_squared();
+    if !down.is_on_ground()
+        || horizontal_distance(side.movement) <= horizontal_distance(direct.movement)
+    {
+        return None;
+    }
+
+    Some(CollisionResult {
+        aabb: down.aabb,
+        movement: down.aabb.min - aabb.min,
+        contact_normal: vector![
+            side.contact_normal.x,
+            down.contact_normal.y,
+            side.contact_normal.z
+        ],
+    })
+}
+
+/// Returns true if every chunk the box touches while moving is loaded and generated.
+fn is_area_generated(
+    aabb: &Aabb,
+    movement: Vector3<f32>,
+    chunk_map: &ChunkMap,
+    chunk_states: &Query<&ChunkState>,
+) -> bool {
+    let to_chunk_index =
+        |point: Point3<f32>| chunk::world_to_chunk_index(point.coords.map(|c| c.floor() as i32));
+    let min = to_chunk_index(aabb.min.inf(&(aabb.min + movement)));
+    let max = to_chunk_index(aabb.max.sup(&(aabb.max + movement)));
+
+    (min.x..=max.x).all(|x| {
+        (min.y..=max.y).all(|y| {
+            (min.z..=max.z).all(|z| {
+                chunk_map
+                    .get_entity(vector![x, y, z])
+                    .and_then(|entity| chunk_states.get(entity).ok())
+                    .is_some_and(|state| *state != ChunkState::Queued)
+            })
+        })
+    })
+}
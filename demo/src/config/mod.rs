@@ -91,6 +91,22 @@ fn config_debug_gui(
                         "How much the camera speed changes when scrolling the mouse wheel.",
                     );
                 }
+                if ui.slider(
+                    "Horizontal render distance",
+                    1,
+                    32,
+                    &mut config.horizontal_render_distance,
+                ) {
+                    save_config(&config);
+                }
+                if ui.slider(
+                    "Vertical render distance",
+                    1,
+                    16,
+                    &mut config.vertical_render_distance,
+                ) {
+                    save_config(&config);
+                }
             });
             state.open = open;
         }
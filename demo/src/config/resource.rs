@@ -3,11 +3,18 @@ use serde::{Deserialize, Serialize};
 
 /// The global config resource.
 #[derive(Resource, Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Config {
     /// The mouse sensitivity.
     pub sensitivity: f32,
     /// How fast should the camera speed change while scrolling.
     pub camera_speed_change_step: f32,
+    /// How many chunks are loaded around the camera on the x and z axes.
+    pub horizontal_render_distance: u32,
+    /// How many chunks are loaded above and below the camera.
+    pub vertical_render_distance: u32,
+    /// The name of the world that gets opened or created on start up.
+    pub world_name: String,
 }
 
 impl Default for Config {
@@ -15,6 +22,9 @@ impl Default for Config {
         Self {
             sensitivity: 0.005,
             camera_speed_change_step: 10.0,
+            horizontal_render_distance: 8,
+            vertical_render_distance: 4,
+            world_name: "default".to_owned(),
         }
     }
 }
@@ -1,9 +1,9 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use super::common::{FractalType, NoiseType, RotationType3D};
 
 /// Describes the parameters to use for cave generation.
-#[derive(Clone, Copy, Debug, Deserialize)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct CaveGenerationOptions {
     /// The seed to use for the noise generation.
     pub seed: i32,
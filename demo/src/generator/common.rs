@@ -1,9 +1,9 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 /// The noise type to use for the noise generation.
 ///
-/// This is different from the `fastnoise_lite::NoiseType` because this can be deserialized.
-#[derive(Copy, Clone, Debug, PartialEq, Deserialize)]
+/// This is different from the `fastnoise_lite::NoiseType` because this can be serialized.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum NoiseType {
     OpenSimplex2,
     OpenSimplex2S,
@@ -28,8 +28,8 @@ impl From<NoiseType> for fastnoise_lite::NoiseType {
 
 /// The rotation type to use for the noise generation.
 ///
-/// This is different from the `fastnoise_lite::RotationType3D` because this can be deserialized.
-#[derive(Copy, Clone, Debug, PartialEq, Deserialize)]
+/// This is different from the `fastnoise_lite::RotationType3D` because this can be serialized.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum RotationType3D {
     None,
     ImproveXYPlanes,
@@ -48,8 +48,8 @@ impl From<RotationType3D> for fastnoise_lite::RotationType3D {
 
 /// The fractal type to use for the noise generation.
 ///
-/// This is different from the `fastnoise_lite::FractalType` because this can be deserialized.
-#[derive(Copy, Clone, Debug, PartialEq, Deserialize)]
+/// This is different from the `fastnoise_lite::FractalType` because this can be serialized.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum FractalType {
     None,
     FBm,
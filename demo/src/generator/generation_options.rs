@@ -1,8 +1,17 @@
-use bevy_ecs::system::Resource;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+
+use super::{cave_options::CaveGenerationOptions, terrain_options::TerrainGenerationOptions};
+
+/// All the options of the `Generator`, these are stored in the world metadata.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GeneratorOptions {
+    pub generation: GenerationOptions,
+    pub terrain: TerrainGenerationOptions,
+    pub cave: CaveGenerationOptions,
+}
 
 /// The general world generation options.
-#[derive(Resource, Deserialize)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct GenerationOptions {
     /// The base dirt height.
     pub dirt_height: f32,
@@ -1,148 +1,65 @@
-use std::time::Instant;
-
 mod cave_options;
 mod common;
 mod generation_options;
 mod resource;
 mod terrain_options;
-pub use generation_options::GenerationOptions;
+pub use generation_options::{GenerationOptions, GeneratorOptions};
 
-use bevy_ecs::{
-    query::Added,
-    system::{Query, Res},
-};
-use nalgebra::{vector, Vector3};
-use rayon::iter::{IntoParallelIterator, ParallelIterator};
 pub use resource::Generator;
-use voxel_engine::{application::Application, common::{chunk, VoxelHandle}, ecs::{components::Chunk, packages::Package, schedules::Update}, utils::file_system};
-
-/// Package for `Generator`.
-pub struct GeneratorPackage;
-
-impl Package for GeneratorPackage {
-    fn initialize(&mut self, app: &mut Application) {
-        let generation_options =
-            match file_system::read_asset_config("generation", "generation_options") {
-                Ok(options) => options,
-                Err(e) => {
-                    log::error!("Failed to read generation options: {}", e);
-                    return;
-                }
-            };
-        let generation_options = match ron::de::from_str::<GenerationOptions>(&generation_options) {
-            Ok(options) => options,
-            Err(e) => {
-                log::error!("Failed to deserialize generation options: {}", e);
-                return;
-            }
-        };
-
-        let terrain_options =
-            match file_system::read_asset_config("generation", "terrain_gen_options") {
-                Ok(options) => options,
-                Err(e) => {
-                    log::error!("Failed to read terrain generation options: {}", e);
-                    return;
-                }
-            };
-        let terrain_options = match ron::de::from_str(&terrain_options) {
-            Ok(options) => options,
-            Err(e) => {
-                log::error!("Failed to deserialize terrain generation options: {}", e);
-                return;
-            }
-        };
+use voxel_engine::utils::file_system;
 
-        let cave_options = match file_system::read_asset_config("generation", "cave_gen_options") {
-            Ok(options) => options,
-            Err(e) => {
-                log::error!("Failed to read cave generation options: {}", e);
-                return;
-            }
-        };
-        let cave_options = match ron::de::from_str(&cave_options) {
+/// Reads the generator options from the asset configs, these are used for new worlds.
+pub fn load_generator_options() -> Option<GeneratorOptions> {
+    let generation_options =
+        match file_system::read_asset_config("generation", "generation_options") {
             Ok(options) => options,
             Err(e) => {
-                log::error!("Failed to deserialize cave generation options: {}", e);
-                return;
+                log::error!("Failed to read generation options: {}", e);
+                return None;
             }
         };
+    let generation_options = match ron::de::from_str::<GenerationOptions>(&generation_options) {
+        Ok(options) => options,
+        Err(e) => {
+            log::error!("Failed to deserialize generation options: {}", e);
+            return None;
+        }
+    };
 
-        app.insert_resource(generation_options);
-        app.insert_resource(Generator::new(terrain_options, cave_options));
-        app.add_systems(Update, generate_chunk_data_3d);
-    }
-}
-
-/// Generates chunk data for newly added chunks.
-pub fn generate_chunk_data_3d(
-    mut query: Query<&mut Chunk, Added<Chunk>>,
-    generator: Res<Generator>,
-    generation_options: Res<GenerationOptions>,
-) {
-    /// Converts the given local x, y and z coordinates of the chunk to world coordinates by using the chunk index to transform them.
-    fn get_world_pos(index: &Vector3<i32>, x: i32, y: i32, z: i32) -> Vector3<f32> {
-        (vector![x, y, z] + index * chunk::CHUNK_LENGTHI32).map(|c| c as f32)
-    }
-
-    if query.is_empty() {
-        return;
-    }
-
-    let start = Instant::now();
-
-    let generator = &generator;
-    let generation_options = &generation_options;
-    query.par_iter_mut().for_each(|mut chunk| {
-        let index = chunk.get_index();
-        let height_map = &(0..chunk::CHUNK_LENGTHI32)
-            .into_par_iter()
-            .flat_map_iter(|x| {
-                (0..chunk::CHUNK_LENGTHI32).map(move |z| {
-                    // Only need x and z components so leave y as 0
-                    let world_pos = get_world_pos(&index, x, 0, z);
-                    generator.get_terrain_height(world_pos.xz())
-                })
-            })
-            .collect::<Vec<_>>();
-
-        chunk.voxels = (0..chunk::CHUNK_LENGTHI32)
-            .into_par_iter()
-            .flat_map(|x| {
-                (0..chunk::CHUNK_LENGTHI32)
-                    .into_par_iter()
-                    .flat_map_iter(move |y| {
-                        (0..chunk::CHUNK_LENGTHI32).map(move |z| {
-                            let world_pos = get_world_pos(&index, x, y, z);
-                            let height = height_map[(x + z * chunk::CHUNK_LENGTHI32) as usize];
-                            let is_cave_voxel =
-                                generator.does_underground_contains_voxel(world_pos);
+    let terrain_options = match file_system::read_asset_config("generation", "terrain_gen_options")
+    {
+        Ok(options) => options,
+        Err(e) => {
+            log::error!("Failed to read terrain generation options: {}", e);
+            return None;
+        }
+    };
+    let terrain_options = match ron::de::from_str(&terrain_options) {
+        Ok(options) => options,
+        Err(e) => {
+            log::error!("Failed to deserialize terrain generation options: {}", e);
+            return None;
+        }
+    };
 
-                            if height >= world_pos.y && is_cave_voxel {
-                                let delta = height - world_pos.y;
-                                let grass_threshold = if delta >= generation_options.stone_threshold
-                                {
-                                    (rand::random::<f32>() * 2.0 - 1.0)
-                                        * generation_options.dirt_variation
-                                        + generation_options.dirt_height
-                                } else {
-                                    f32::INFINITY
-                                };
-                                if delta <= 1.0 {
-                                    Some(VoxelHandle { id: 0 })
-                                } else if delta <= grass_threshold {
-                                    Some(VoxelHandle { id: 1 })
-                                } else {
-                                    Some(VoxelHandle { id: 2 })
-                                }
-                            } else {
-                                None
-                            }
-                        })
-                    })
-            })
-            .collect();
-    });
+    let cave_options = match file_system::read_asset_config("generation", "cave_gen_options") {
+        Ok(options) => options,
+        Err(e) => {
+            log::error!("Failed to read cave generation options: {}", e);
+            return None;
+        }
+    };
+    let cave_options = match ron::de::from_str(&cave_options) {
+        Ok(options) => options,
+        Err(e) => {
+            log::error!("Failed to deserialize cave generation options: {}", e);
+            return None;
+        }
+    };
 
-    log::info!("Chunk generation took {} ms", start.elapsed().as_millis());
+    Some(GeneratorOptions {
+        generation: generation_options,
+        terrain: terrain_options,
+        cave: cave_options,
+    })
 }
@@ -1,11 +1,23 @@
-use bevy_ecs::system::Resource;
 use fastnoise_lite::FastNoiseLite;
+use nalgebra::{vector, Vector3};
+use voxel_engine::{
+    common::{
+        chunk,
+        world_generator::{self, WorldGenerator},
+        VoxelHandle,
+    },
+    ecs::components::Chunk,
+};
 
-use super::{cave_options::CaveGenerationOptions, terrain_options::TerrainGenerationOptions};
+use super::{
+    cave_options::CaveGenerationOptions, terrain_options::TerrainGenerationOptions,
+    GenerationOptions, GeneratorOptions,
+};
 
 /// Procedural world generator.
-#[derive(Resource)]
 pub struct Generator {
+    seed: u64,
+    generation_options: GenerationOptions,
     terrain_height_noise: FastNoiseLite,
     terrain_options: TerrainGenerationOptions,
     cave_noise: FastNoiseLite,
@@ -14,19 +26,28 @@ pub struct Generator {
 
 impl Generator {
     /// Creates a new generator.
-    pub fn new(
-        terrain_options: TerrainGenerationOptions,
-        cave_options: CaveGenerationOptions,
-    ) -> Self {
+    ///
+    /// ## Arguments
+    /// * `seed` - The seed of the world, the noise seeds are offset by it.
+    /// * `options` - The options of the generator.
+    pub fn new(seed: u64, options: GeneratorOptions) -> Self {
+        let GeneratorOptions {
+            generation: generation_options,
+            terrain: terrain_options,
+            cave: cave_options,
+        } = options;
+        // Folds the 64 bit world seed into the 32 bit noise seeds.
+        let seed_offset = (seed ^ (seed >> 32)) as i32;
         let terrain_height_noise = {
-            let mut noise = FastNoiseLite::with_seed(terrain_options.seed);
+            let mut noise =
+                FastNoiseLite::with_seed(terrain_options.seed.wrapping_add(seed_offset));
             noise.set_frequency(Some(terrain_options.frequency));
             noise.set_noise_type(Some(terrain_options.noise_type.into()));
             noise.set_fractal_type(Some(terrain_options.fractal_type.into()));
             noise
         };
         let cave_noise = {
-            let mut noise = FastNoiseLite::with_seed(cave_options.seed);
+            let mut noise = FastNoiseLite::with_seed(cave_options.seed.wrapping_add(seed_offset));
             noise.set_frequency(Some(cave_options.frequency));
             noise.set_noise_type(Some(cave_options.noise_type.into()));
             noise.set_fractal_type(Some(cave_options.fractal_type.into()));
@@ -34,6 +55,8 @@ impl Generator {
         };
 
         Self {
+            seed,
+            generation_options,
             terrain_height_noise,
             terrain_options,
             cave_noise,
@@ -55,3 +78,63 @@ impl Generator {
         self.cave_noise.get_noise_3d(pos[0], pos[1], pos[2]) >= self.cave_options.voxel_threshold
     }
 }
+
+impl WorldGenerator for Generator {
+    fn get_seed(&self) -> u64 {
+        self.seed
+    }
+
+    fn generate_chunk(&self, chunk: &mut Chunk) {
+        /// Converts the given local x, y and z coordinates of the chunk to world coordinates by using the chunk index to transform them.
+        fn get_world_pos(index: &Vector3<i32>, x: i32, y: i32, z: i32) -> Vector3<f32> {
+            (vector![x, y, z] + index * chunk::CHUNK_LENGTHI32).map(|c| c as f32)
+        }
+
+        // Every chunk is generated on its own thread, so the loops here are sequential.
+        let index = chunk.get_index();
+        let height_map = (0..chunk::CHUNK_LENGTHI32)
+            .flat_map(|z| {
+                (0..chunk::CHUNK_LENGTHI32).map(move |x| {
+                    // Only need x and z components so leave y as 0
+                    let world_pos = get_world_pos(&index, x, 0, z);
+                    self.get_terrain_height(world_pos.xz())
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut voxels = Vec::with_capacity(chunk::CHUNK_VOLUME);
+        for z in 0..chunk::CHUNK_LENGTHI32 {
+            for y in 0..chunk::CHUNK_LENGTHI32 {
+                for x in 0..chunk::CHUNK_LENGTHI32 {
+                    let voxel_pos = vector![x, y, z] + index * chunk::CHUNK_LENGTHI32;
+                    let world_pos = voxel_pos.map(|c| c as f32);
+                    let height = height_map[(x + z * chunk::CHUNK_LENGTHI32) as usize];
+
+                    let voxel = if height >= world_pos.y
+                        && self.does_underground_contains_voxel(world_pos)
+                    {
+                        let delta = height - world_pos.y;
+                        let grass_threshold = if delta >= self.generation_options.stone_threshold {
+                            (world_generator::random_at(self.seed, voxel_pos) * 2.0 - 1.0)
+                                * self.generation_options.dirt_variation
+                                + self.generation_options.dirt_height
+                        } else {
+                            f32::INFINITY
+                        };
+                        if delta <= 1.0 {
+                            Some(VoxelHandle { id: 0 })
+                        } else if delta <= grass_threshold {
+                            Some(VoxelHandle { id: 1 })
+                        } else {
+                            Some(VoxelHandle { id: 2 })
+                        }
+                    } else {
+                        None
+                    };
+                    voxels.push(voxel);
+                }
+            }
+        }
+        chunk.set_voxels(&voxels);
+    }
+}
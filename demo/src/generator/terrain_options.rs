@@ -1,9 +1,9 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use super::common::{FractalType, NoiseType};
 
 /// Describes the parameters to use for terrain generation.
-#[derive(Clone, Copy, Debug, Deserialize)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct TerrainGenerationOptions {
     /// The seed to use for the noise generation.
     pub seed: i32,
@@ -1,11 +1,14 @@
 use camera_controller::CameraControllerPackage;
 use config::ConfigPackage;
-use generator::GeneratorPackage;
+use voxel_editor::VoxelEditorPackage;
 use voxel_engine::application::Application;
+use world::WorldPackage;
 
 mod camera_controller;
 mod generator;
 mod config;
+mod voxel_editor;
+mod world;
 
 fn main() -> anyhow::Result<()> {
     Application::new()?
@@ -21,8 +24,12 @@ fn main() -> anyhow::Result<()> {
         .with_package(voxel_engine::ecs::packages::voxel_registry::VoxelRegistryPackage)
         .with_package(voxel_engine::ecs::packages::game_world::GameWorldPackage)
         .with_package(voxel_engine::ecs::packages::chunk::ChunkPackage)
+        .with_package(voxel_engine::ecs::packages::fluid::FluidPackage)
+        .with_package(voxel_engine::ecs::packages::light::LightPackage)
         .with_package(voxel_engine::ecs::packages::debug_gui::DebugCompositorPackage)
+        .with_package(voxel_engine::ecs::packages::outline::OutlinePackage)
         .with_package(CameraControllerPackage)
-        .with_package(GeneratorPackage)
+        .with_package(WorldPackage)
+        .with_package(VoxelEditorPackage)
         .run()
 }
@@ -0,0 +1,248 @@
+use bevy_ecs::{
+    event::EventReader,
+    query::With,
+    schedule::IntoSystemConfigs as _,
+    system::{NonSend, Query, Res, ResMut, Resource},
+};
+use voxel_engine::{
+    application::Application,
+    common::{aabb::Aabb, raycast::Raycast, VoxelHandle},
+    ecs::{
+        components::Chunk,
+        events::window_events::{ElementState, MouseButtonInput, MouseMotion},
+        packages::{
+            chunk::ChunkMap,
+            debug_gui::{self, DebugCompositor},
+            input_provider::{self, InputProvider, KeyCode, MouseButton},
+            outline::OutlineRenderer,
+            voxel_registry::VoxelRegistry,
+            Package,
+        },
+        schedules::{Render, Update},
+        systems,
+    },
+};
+
+pub use resource::VoxelEditor;
+
+use crate::camera_controller::{CameraController, CurrentCameraController};
+
+mod resource;
+
+/// How far in pixels the mouse can move while the right mouse button is held for it to still count as a click.
+const MAX_CLICK_DRAG_DISTANCE: f32 = 4.0;
+/// How much the target outline is grown so it does not fight with the voxel faces.
+const TARGET_OUTLINE_INFLATION: f32 = 0.005;
+/// The color of the target outline.
+const TARGET_OUTLINE_COLOR: [f32; 4] = [0.05, 0.05, 0.05, 0.8];
+/// The keys that select the registered voxels in the order of their ids.
+const SELECTION_KEYS: [KeyCode; 10] = [
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::Digit5,
+    KeyCode::Digit6,
+    KeyCode::Digit7,
+    KeyCode::Digit8,
+    KeyCode::Digit9,
+    KeyCode::Digit0,
+];
+
+/// Package for `VoxelEditor`.
+///
+/// Left click breaks the targeted voxel and right click places the selected voxel against the targeted face.
+pub struct VoxelEditorPackage;
+
+impl Package for VoxelEditorPackage {
+    fn initialize(&mut self, app: &mut Application) {
+        let selected_voxel = app
+            .get_resource::<VoxelRegistry>()
+            .and_then(|registry| registry.voxels.keys().min().copied())
+            .map(|id| VoxelHandle { id });
+
+        app.insert_resource(VoxelEditor {
+            selected_voxel,
+            ..Default::default()
+        });
+        app.insert_resource(VoxelEditorDebugGuiState::default());
+        app.add_systems(
+            Update,
+            (
+                voxel_selection_system,
+                (voxel_target_system, voxel_edit_system)
+                    .chain()
+                    .after(input_provider::mouse_moved_listener_system),
+            ),
+        );
+        app.add_systems(
+            Render,
+            (voxel_editor_debug_gui
+                .after(debug_gui::start_gui_frame)
+                .before(systems::render_system),),
+        );
+    }
+}
+
+/// Selects the voxel to place with the number keys.
+fn voxel_selection_system(
+    mut voxel_editor: ResMut<VoxelEditor>,
+    input_provider: Res<InputProvider>,
+    voxel_registry: Res<VoxelRegistry>,
+) {
+    let key_index = match SELECTION_KEYS
+        .iter()
+        .position(|key| input_provider.is_pressed(*key))
+    {
+        Some(key_index) => key_index,
+        None => return,
+    };
+
+    let mut ids = voxel_registry.voxels.keys().copied().collect::<Vec<_>>();
+    ids.sort_unstable();
+    if let Some(id) = ids.get(key_index) {
+        let selected_voxel = Some(VoxelHandle { id: *id });
+        if voxel_editor.selected_voxel != selected_voxel {
+            voxel_editor.selected_voxel = selected_voxel;
+        }
+    }
+}
+
+/// Finds the voxel the current camera controller is looking at and outlines it.
+fn voxel_target_system(
+    mut voxel_editor: ResMut<VoxelEditor>,
+    outline_renderer: Option<ResMut<OutlineRenderer>>,
+    chunks: Query<&Chunk>,
+    camera_controllers: Query<&CameraController, With<CurrentCameraController>>,
+    chunk_map: Res<ChunkMap>,
+) {
+    let target = camera_controllers.get_single().ok().and_then(|controller| {
+        let raycast = Raycast::new(
+            controller.position,
+            *controller.get_direction(),
+            voxel_editor.reach,
+        );
+        chunk_map.raycast(&chunks, &raycast)
+    });
+    if voxel_editor.target != target {
+        voxel_editor.target = target;
+    }
+
+    if let (Some(target), Some(mut outline_renderer)) = (target, outline_renderer) {
+        outline_renderer.draw_box(
+            &Aabb::from_voxel(target.position).inflate(TARGET_OUTLINE_INFLATION),
+            TARGET_OUTLINE_COLOR,
+        );
+    }
+}
+
+/// Breaks and places voxels at the target of the `VoxelEditor`.
+///
+/// The edited chunks get remeshed by the chunk package, together with their neighbours if the
+/// edit was on a chunk border.
+fn voxel_edit_system(
+    mut mouse_button_events: EventReader<MouseButtonInput>,
+    mut mouse_motion_events: EventReader<MouseMotion>,
+    mut voxel_editor: ResMut<VoxelEditor>,
+    mut chunks: Query<&mut Chunk>,
+    chunk_map: Res<ChunkMap>,
+    input_provider: Res<InputProvider>,
+    debug_compositor: Option<NonSend<DebugCompositor>>,
+) {
+    if input_provider.is_mouse_button_pressed(MouseButton::Right) {
+        for event in mouse_motion_events.read() {
+            voxel_editor.right_drag_distance += event.delta.norm();
+        }
+    } else {
+        mouse_motion_events.clear();
+    }
+
+    if debug_compositor.is_some_and(|debug_compositor| debug_compositor.wants_mouse()) {
+        mouse_button_events.clear();
+        return;
+    }
+
+    for event in mouse_button_events.read() {
+        let place = match (event.button, event.state) {
+            (MouseButton::Left, ElementState::Pressed) => false,
+            (MouseButton::Right, ElementState::Pressed) => {
+                voxel_editor.right_drag_distance = 0.0;
+                continue;
+            }
+            (MouseButton::Right, ElementState::Released)
+                if voxel_editor.right_drag_distance <= MAX_CLICK_DRAG_DISTANCE =>
+            {
+                true
+            }
+            _ => continue,
+        };
+
+        // The target is outdated after an edit, so only the first click of a frame does something.
+        let hit = match voxel_editor.target.take() {
+            Some(hit) => hit,
+            None => continue,
+        };
+
+        if place {
+            if voxel_editor.selected_voxel.is_some() {
+                chunk_map.set_voxel(
+                    &mut chunks,
+                    hit.get_adjacent_position(),
+                    voxel_editor.selected_voxel,
+                );
+            }
+        } else {
+            chunk_map.set_voxel(&mut chunks, hit.position, None);
+        }
+    }
+}
+
+/// Builds the ui for choosing the voxel to place.
+fn voxel_editor_debug_gui(
+    debug_compositor: Option<NonSend<DebugCompositor>>,
+    mut voxel_editor: ResMut<VoxelEditor>,
+    mut state: ResMut<VoxelEditorDebugGuiState>,
+    voxel_registry: Res<VoxelRegistry>,
+) {
+    if let Some(debug_compositor) = debug_compositor {
+        let ui = debug_compositor.get_frame_ui();
+
+        ui.main_menu_bar(|| {
+            ui.menu("Windows", || {
+                if ui.menu_item("Voxel Editor") {
+                    state.open = true;
+                }
+            })
+        });
+
+        if state.open {
+            let mut open = state.open;
+            ui.window("Voxel Editor").opened(&mut open).build(|| {
+                let mut voxels = voxel_registry.voxels.values().collect::<Vec<_>>();
+                voxels.sort_unstable_by_key(|voxel| voxel.id);
+
+                for (index, voxel) in voxels.iter().enumerate() {
+                    let selected =
+                        voxel_editor.selected_voxel == Some(VoxelHandle { id: voxel.id });
+                    let label = match SELECTION_KEYS.get(index) {
+                        Some(_) => format!("{}: {}", index + 1, voxel.name),
+                        None => voxel.name.clone(),
+                    };
+                    if ui.selectable_config(label).selected(selected).build() {
+                        voxel_editor.selected_voxel = Some(VoxelHandle { id: voxel.id });
+                    }
+                }
+
+                ui.separator();
+                ui.slider("Reach", 1.0, 64.0, &mut voxel_editor.reach);
+            });
+            state.open = open;
+        }
+    }
+}
+
+/// Singleton state for the voxel editor window.
+#[derive(Resource, Default)]
+struct VoxelEditorDebugGuiState {
+    open: bool,
+}
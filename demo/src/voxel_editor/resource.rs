@@ -0,0 +1,28 @@
+use bevy_ecs::system::Resource;
+use voxel_engine::common::{raycast::RaycastHit, VoxelHandle};
+
+/// The state of placing and breaking voxels.
+#[derive(Resource, Debug)]
+pub struct VoxelEditor {
+    /// How far away from the camera voxels can be edited.
+    pub reach: f32,
+    /// The voxel that gets placed.
+    pub selected_voxel: Option<VoxelHandle>,
+    /// The voxel the current camera controller is looking at.
+    pub target: Option<RaycastHit>,
+    /// How far the mouse has moved since the right mouse button was pressed.
+    ///
+    /// Dragging with the right mouse button rotates the camera, so only a click places a voxel.
+    pub(super) right_drag_distance: f32,
+}
+
+impl Default for VoxelEditor {
+    fn default() -> Self {
+        Self {
+            reach: 16.0,
+            selected_voxel: None,
+            target: None,
+            right_drag_distance: 0.0,
+        }
+    }
+}
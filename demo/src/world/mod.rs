@@ -0,0 +1,134 @@
+use std::{
+    collections::BTreeMap,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bevy_ecs::{
+    query::With,
+    system::{Query, ResMut, Resource},
+};
+use voxel_engine::{
+    application::Application,
+    common::world::{self, WorldError, WorldMetadata},
+    ecs::{
+        packages::{
+            voxel_registry::VoxelRegistry, world_generator::WorldGeneratorPackage,
+            world_storage::WorldStoragePackage, Package,
+        },
+        schedules::Exit,
+    },
+};
+
+use crate::{
+    camera_controller::{CameraController, CurrentCameraController},
+    config::Config,
+    generator::{self, Generator, GeneratorOptions},
+};
+
+/// How high above the terrain the camera is placed in a new world.
+const SPAWN_HEIGHT_ABOVE_TERRAIN: f32 = 2.0;
+
+/// The world that is currently being played.
+#[derive(Resource)]
+pub struct CurrentWorld {
+    pub metadata: WorldMetadata<GeneratorOptions>,
+}
+
+/// Package for `CurrentWorld`.
+///
+/// Opens the world named in the `Config` or creates it if it does not exist yet and sets up the
+/// generator and the chunk storage of the world.
+pub struct WorldPackage;
+
+impl Package for WorldPackage {
+    fn initialize(&mut self, app: &mut Application) {
+        let name = match app.get_resource::<Config>() {
+            Some(config) => config.world_name.clone(),
+            None => {
+                log::error!("Failed to get config");
+                return;
+            }
+        };
+        let voxel_ids = match app.get_resource::<VoxelRegistry>() {
+            Some(registry) => registry.get_voxel_ids(),
+            None => {
+                log::error!("Failed to get voxel registry");
+                return;
+            }
+        };
+
+        let mut metadata = match world::open_world::<GeneratorOptions>(&name) {
+            Ok(metadata) => {
+                log::info!("Opened world {name:?} with seed {}", metadata.seed);
+                metadata
+            }
+            Err(WorldError::NotFound(_)) => match create_world(name.clone(), voxel_ids.clone()) {
+                Some(metadata) => metadata,
+                None => return,
+            },
+            Err(e) => {
+                log::error!("Failed to open world {name:?}: {e}");
+                return;
+            }
+        };
+
+        // Newly registered voxels get recorded so that their ids are known the next time.
+        let has_new_voxels = metadata.register_voxel_ids(&voxel_ids);
+        // The region files keep the ids the voxels were saved with, they get translated when chunks are loaded and saved.
+        let voxel_id_map = metadata.create_voxel_id_map(&voxel_ids);
+        if has_new_voxels {
+            if let Err(e) = world::save_world_metadata(&metadata) {
+                log::error!("Failed to save world metadata: {e}");
+            }
+        }
+
+        let generator = Generator::new(metadata.seed, metadata.generator_options.clone());
+        app.add_package(WorldGeneratorPackage::new(generator));
+        app.add_package(
+            WorldStoragePackage::new(world::get_regions_dir(&name)).with_voxel_ids(voxel_id_map),
+        );
+
+        app.insert_resource(CurrentWorld { metadata });
+        app.add_systems(Exit, save_world_metadata_system);
+    }
+}
+
+/// Creates a new world with a random seed and the generator options from the assets.
+fn create_world(
+    name: String,
+    voxel_ids: BTreeMap<String, u32>,
+) -> Option<WorldMetadata<GeneratorOptions>> {
+    let options = generator::load_generator_options()?;
+    let seed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_nanos() as u64)
+        .unwrap_or_default();
+
+    let terrain_height = Generator::new(seed, options.clone()).get_terrain_height([0.0, 0.0]);
+    let mut metadata = WorldMetadata::new(name, seed, options, voxel_ids);
+    metadata.camera_position = [0.0, terrain_height + SPAWN_HEIGHT_ABOVE_TERRAIN, 0.0];
+
+    match world::create_world(&metadata) {
+        Ok(path) => {
+            log::info!("Created world {:?} at {}", metadata.name, path.display());
+            Some(metadata)
+        }
+        Err(e) => {
+            log::error!("Failed to create world {:?}: {e}", metadata.name);
+            None
+        }
+    }
+}
+
+/// Saves the metadata of the `CurrentWorld` with the last position of the camera.
+fn save_world_metadata_system(
+    mut current_world: ResMut<CurrentWorld>,
+    camera_controllers: Query<&CameraController, With<CurrentCameraController>>,
+) {
+    if let Ok(camera_controller) = camera_controllers.get_single() {
+        current_world.metadata.camera_position = camera_controller.position.into();
+    }
+    if let Err(e) = world::save_world_metadata(&current_world.metadata) {
+        log::error!("Failed to save world metadata: {e}");
+    }
+}
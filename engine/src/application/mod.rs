@@ -174,6 +174,9 @@ impl Application {
 
         self.world.run_schedule(EarlyUpdate);
         self.world.run_schedule(Update);
+
+        // Swaps the removed component buffers, so that they do not grow forever.
+        self.world.clear_trackers();
     }
 
     /// Runs this application.
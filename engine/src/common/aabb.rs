@@ -0,0 +1,67 @@
+use nalgebra::{Point3, Vector3};
+
+use super::chunk;
+
+/// An axis aligned bounding box in world space.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Aabb {
+    /// The corner with the smallest coordinates.
+    pub min: Point3<f32>,
+    /// The corner with the largest coordinates.
+    pub max: Point3<f32>,
+}
+
+impl Aabb {
+    /// Creates a new box from two opposite corners in any order.
+    pub fn new(a: Point3<f32>, b: Point3<f32>) -> Self {
+        Self {
+            min: a.inf(&b),
+            max: a.sup(&b),
+        }
+    }
+
+    /// Creates a box that covers a single voxel.
+    pub fn from_voxel(position: Vector3<i32>) -> Self {
+        Self::from_voxel_range(position, position)
+    }
+
+    /// Creates a box that covers all the voxels between two voxel positions, both inclusive.
+    pub fn from_voxel_range(a: Vector3<i32>, b: Vector3<i32>) -> Self {
+        let min = a.inf(&b).map(|c| c as f32);
+        let max = a.sup(&b).map(|c| (c + 1) as f32);
+        Self {
+            min: min.into(),
+            max: max.into(),
+        }
+    }
+
+    /// Creates a box that covers the chunk with the specified chunk index.
+    pub fn from_chunk_index(index: Vector3<i32>) -> Self {
+        let min = index * chunk::CHUNK_LENGTHI32;
+        Self::from_voxel_range(min, min.add_scalar(chunk::CHUNK_LENGTHI32 - 1))
+    }
+
+    /// Returns the size of the box on each axis.
+    pub fn get_size(&self) -> Vector3<f32> {
+        self.max - self.min
+    }
+
+    /// Returns the center of the box.
+    pub fn get_center(&self) -> Point3<f32> {
+        nalgebra::center(&self.min, &self.max)
+    }
+
+    /// Returns a copy of the box that is grown by `amount` in every direction.
+    pub fn inflate(&self, amount: f32) -> Self {
+        let amount = Vector3::repeat(amount);
+        Self {
+            min: self.min - amount,
+            max: self.max + amount,
+        }
+    }
+
+    /// Returns true if the boxes overlap, touching boxes do not overlap.
+    pub fn intersects(&self, other: &Aabb) -> bool {
+        (0..3).all(|axis| self.min[axis] < other.max[axis] && other.min[axis] < self.max[axis])
+    }
+}
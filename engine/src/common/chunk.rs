@@ -1,8 +1,8 @@
 use std::mem;
 
-use nalgebra::vector;
+use nalgebra::{vector, Vector3};
 
-use super::quad::Quad;
+use super::{face_dir::FaceDir, quad::Quad};
 
 /// Type to use for storing binary voxel data.
 pub type BinaryVoxelContainer = u64;
@@ -12,9 +12,135 @@ pub const CHUNK_LENGTH: usize = mem::size_of::<BinaryVoxelContainer>() * 8;
 pub const CHUNK_LENGTHI32: i32 = CHUNK_LENGTH as i32;
 /// The chunk side length as a u32.
 pub const CHUNK_LENGTHU32: u32 = CHUNK_LENGTH as u32;
+/// The amount of voxels in a single chunk.
+pub const CHUNK_VOLUME: usize = CHUNK_LENGTH * CHUNK_LENGTH * CHUNK_LENGTH;
+/// The ambient occlusion level of a voxel corner that nothing occludes.
+pub const MAX_AMBIENT_OCCLUSION: u8 = 3;
+
+/// Binary voxel data of a single border layer of a chunk.
+///
+/// The rows and bits are laid out the same way as the slices in the greedy mesher,
+/// see `border_position`.
+pub type ChunkBorder = [BinaryVoxelContainer; CHUNK_LENGTH];
+
+/// Returns the chunk local position of a voxel in a layer that is perpendicular to the face direction.
+///
+/// ## Arguments
+/// * `face_dir` - The face direction, whose axis the layer is perpendicular to.
+/// * `layer` - The position of the layer along the axis.
+/// * `row` - The row in the layer.
+/// * `bit` - The bit in the row.
+pub fn border_position(
+    face_dir: FaceDir,
+    layer: usize,
+    row: usize,
+    bit: usize,
+) -> (usize, usize, usize) {
+    match face_dir {
+        FaceDir::Down | FaceDir::Up => (row, layer, bit),
+        FaceDir::Left | FaceDir::Right => (layer, bit, row),
+        FaceDir::Forward | FaceDir::Back => (row, bit, layer),
+    }
+}
+
+/// Returns the row and the bit of a chunk local position in a layer that is perpendicular to the face direction.
+///
+/// This is the inverse of `border_position`.
+pub fn border_row_bit(face_dir: FaceDir, (x, y, z): (usize, usize, usize)) -> (usize, usize) {
+    match face_dir {
+        FaceDir::Down | FaceDir::Up => (x, z),
+        FaceDir::Left | FaceDir::Right => (z, y),
+        FaceDir::Forward | FaceDir::Back => (x, y),
+    }
+}
+
+/// Returns the layer of a chunk, that the faces with the specified direction are on the border of.
+pub fn border_layer(face_dir: FaceDir) -> usize {
+    match face_dir {
+        FaceDir::Down | FaceDir::Left | FaceDir::Forward => 0,
+        FaceDir::Up | FaceDir::Right | FaceDir::Back => CHUNK_LENGTH - 1,
+    }
+}
+
+/// Returns the face direction on the other side of the same axis.
+pub fn opposite_face(face_dir: FaceDir) -> FaceDir {
+    FaceDir::from_axis(face_dir as usize ^ 1)
+}
+
+/// Returns the offset to the chunk index of the neighbouring chunk in the specified face direction.
+pub fn neighbour_offset(face_dir: FaceDir) -> Vector3<i32> {
+    match face_dir {
+        FaceDir::Down => vector![0, -1, 0],
+        FaceDir::Up => vector![0, 1, 0],
+        FaceDir::Left => vector![-1, 0, 0],
+        FaceDir::Right => vector![1, 0, 0],
+        FaceDir::Forward => vector![0, 0, -1],
+        FaceDir::Back => vector![0, 0, 1],
+    }
+}
+
+/// Returns the directions of the two axes a face spans, in the order the quads of the face use them.
+pub fn face_tangents(face_dir: FaceDir) -> (Vector3<i32>, Vector3<i32>) {
+    match face_dir {
+        FaceDir::Down | FaceDir::Up => (Vector3::x(), Vector3::z()),
+        FaceDir::Left | FaceDir::Right => (Vector3::z(), Vector3::y()),
+        FaceDir::Forward | FaceDir::Back => (Vector3::x(), Vector3::y()),
+    }
+}
+
+/// Computes the ambient occlusion levels of the four corners of a voxel face.
+///
+/// Every corner is occluded by the two voxels next to it and the voxel diagonal to it,
+/// in the layer in front of the face.
+///
+/// ## Arguments
+/// * `face_dir` - The direction of the face.
+/// * `position` - The position of the voxel the face belongs to.
+/// * `is_solid` - Returns true if the voxel at a position occludes light.
+///
+/// ## Returns
+/// The levels from 0 (fully occluded) to `MAX_AMBIENT_OCCLUSION` (not occluded)
+/// in the corner order of `Quad::append_to_vertices`.
+pub fn face_ambient_occlusion(
+    face_dir: FaceDir,
+    position: Vector3<i32>,
+    is_solid: impl Fn(Vector3<i32>) -> bool,
+) -> [u8; 4] {
+    let front = position + neighbour_offset(face_dir);
+    let (u, v) = face_tangents(face_dir);
+
+    [(-u, -v), (u, -v), (-u, v), (u, v)].map(|(u, v)| {
+        let side_u = is_solid(front + u);
+        let side_v = is_solid(front + v);
+        if side_u && side_v {
+            return 0;
+        }
+        let corner = is_solid(front + u + v);
+        MAX_AMBIENT_OCCLUSION - side_u as u8 - side_v as u8 - corner as u8
+    })
+}
+
+/// Returns the index of the chunk that contains the specified world voxel position.
+pub fn world_to_chunk_index(position: Vector3<i32>) -> Vector3<i32> {
+    position.map(|c| c.div_euclid(CHUNK_LENGTHI32))
+}
+
+/// Returns the chunk local position of the specified world voxel position.
+pub fn world_to_local(position: Vector3<i32>) -> (usize, usize, usize) {
+    let local = position.map(|c| c.rem_euclid(CHUNK_LENGTHI32) as usize);
+    (local.x, local.y, local.z)
+}
+
+/// Returns the world voxel position of a chunk local position in the chunk with the specified index.
+pub fn local_to_world(index: Vector3<i32>, (x, y, z): (usize, usize, usize)) -> Vector3<i32> {
+    index * CHUNK_LENGTHI32 + vector![x as i32, y as i32, z as i32]
+}
 
 /// Meshes a slice of a chunk into quads.
 ///
+/// Every set bit is merged with its neighbours, so the slice should only contain faces
+/// that look the same, i.e. have the same voxel and ambient occlusion.
+///
 /// ## Arguments
 /// * `slice` - The slice to mesh. The slice is modified in place.
 pub fn mesh_slice(slice: &mut [BinaryVoxelContainer; CHUNK_LENGTH]) -> Vec<Quad> {
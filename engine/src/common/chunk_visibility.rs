@@ -0,0 +1,191 @@
+use std::collections::{HashSet, VecDeque};
+
+use nalgebra::Vector3;
+
+use super::{
+    chunk::{self, CHUNK_LENGTH, CHUNK_LENGTHI32},
+    face_dir::FaceDir,
+};
+
+/// The amount of pairs of different chunk faces.
+const FACE_PAIR_COUNT: u32 = 15;
+
+/// Which faces of a chunk can see each other through the voxels that do not hide what is behind them.
+///
+/// Every pair of different faces has one bit, so the whole set fits into 15 bits.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct FaceConnectivity(u16);
+
+impl FaceConnectivity {
+    /// No face can see any other face, like in a chunk of solid rock.
+    pub const NONE: Self = Self(0);
+    /// Every face can see every other face, like in an empty chunk.
+    pub const ALL: Self = Self((1 << FACE_PAIR_COUNT) - 1);
+
+    /// Computes the connectivity of a chunk by flood filling the voxels that do not hide what is behind them.
+    ///
+    /// Two faces are connected if a single filled region touches both of them.
+    ///
+    /// ## Arguments
+    /// * `is_occluder` - Returns true if the voxel at the chunk local position hides everything behind it.
+    pub fn compute(is_occluder: impl Fn((usize, usize, usize)) -> bool) -> Self {
+        // The occluders and the voxels that have already been filled, indexed by z and y with a bit for every x.
+        let mut filled = vec![[0u64; CHUNK_LENGTH]; CHUNK_LENGTH];
+        for (z, rows) in filled.iter_mut().enumerate() {
+            for (y, row) in rows.iter_mut().enumerate() {
+                for x in 0..CHUNK_LENGTH {
+                    if is_occluder((x, y, z)) {
+                        *row |= 1 << x;
+                    }
+                }
+            }
+        }
+
+        let mut connectivity = Self::NONE;
+        let mut stack = vec![];
+        for axis in 0..6 {
+            let face_dir = FaceDir::from_axis(axis);
+            let layer = chunk::border_layer(face_dir);
+            for row in 0..CHUNK_LENGTH {
+                for bit in 0..CHUNK_LENGTH {
+                    let (x, y, z) = chunk::border_position(face_dir, layer, row, bit);
+                    if filled[z][y] & (1 << x) != 0 {
+                        continue;
+                    }
+
+                    filled[z][y] |= 1 << x;
+                    stack.push(Vector3::new(x as i32, y as i32, z as i32));
+                    let mut faces = 0;
+                    while let Some(position) = stack.pop() {
+                        faces |= border_faces(position);
+                        for axis in 0..6 {
+                            let neighbour =
+                                position + chunk::neighbour_offset(FaceDir::from_axis(axis));
+                            if neighbour.iter().any(|c| !(0..CHUNK_LENGTHI32).contains(c)) {
+                                continue;
+                            }
+                            let (x, y, z) = (
+                                neighbour.x as usize,
+                                neighbour.y as usize,
+                                neighbour.z as usize,
+                            );
+                            if filled[z][y] & (1 << x) == 0 {
+                                filled[z][y] |= 1 << x;
+                                stack.push(neighbour);
+                            }
+                        }
+                    }
+                    connectivity.connect_all(faces);
+
+                    if connectivity == Self::ALL {
+                        return connectivity;
+                    }
+                }
+            }
+        }
+        connectivity
+    }
+
+    /// Returns true if the faces can see each other, a face can always see itself.
+    pub fn connects(&self, a: FaceDir, b: FaceDir) -> bool {
+        match pair_index(a, b) {
+            Some(index) => self.0 & (1 << index) != 0,
+            None => true,
+        }
+    }
+
+    /// Connects the two faces.
+    pub fn connect(&mut self, a: FaceDir, b: FaceDir) {
+        if let Some(index) = pair_index(a, b) {
+            self.0 |= 1 << index;
+        }
+    }
+
+    /// Connects all the faces in the mask with each other, the mask has a bit for every face direction.
+    fn connect_all(&mut self, faces: u8) {
+        for a in 0..6 {
+            for b in a + 1..6 {
+                if faces & (1 << a) != 0 && faces & (1 << b) != 0 {
+                    self.connect(FaceDir::from_axis(a), FaceDir::from_axis(b));
+                }
+            }
+        }
+    }
+}
+
+/// Returns the bit of a pair of different faces, the order of the faces does not matter.
+fn pair_index(a: FaceDir, b: FaceDir) -> Option<u32> {
+    let (a, b) = (a.min(b) as u32, a.max(b) as u32);
+    // The pairs are ordered by the first face, which has one pair less for every face before it.
+    (a != b).then(|| a * 5 - a * a.saturating_sub(1) / 2 + b - a - 1)
+}
+
+/// Returns a mask with a bit for every face of the chunk that the voxel at the chunk local position is on.
+fn border_faces(position: Vector3<i32>) -> u8 {
+    (0..6)
+        .map(FaceDir::from_axis)
+        .filter(|face_dir| {
+            let coordinate = match face_dir {
+                FaceDir::Down | FaceDir::Up => position.y,
+                FaceDir::Left | FaceDir::Right => position.x,
+                FaceDir::Forward | FaceDir::Back => position.z,
+            };
+            coordinate == chunk::border_layer(*face_dir) as i32
+        })
+        .fold(0, |mask, face_dir| mask | 1 << face_dir as u8)
+}
+
+/// Finds the chunks that can possibly be seen from the chunk the camera is in.
+///
+/// The chunks are walked breadth first from the camera chunk. A chunk is only left through a face that
+/// is connected to the face it was entered through, and never in the opposite direction of a step that
+/// led to it, so the walk can not bend back around an occluder towards the camera.
+///
+/// ## Arguments
+/// * `start` - The chunk index of the chunk the camera is in.
+/// * `get_connectivity` - Returns the face connectivity of a chunk, or `None` if the chunk is not loaded.
+/// * `is_in_view` - Returns true if the chunk is inside of the view of the camera, chunks outside of it are not walked through.
+///
+/// ## Returns
+/// The chunk indices of the chunks that can be seen, or `None` if the camera chunk is not loaded.
+pub fn find_visible_chunks(
+    start: Vector3<i32>,
+    get_connectivity: impl Fn(Vector3<i32>) -> Option<FaceConnectivity>,
+    is_in_view: impl Fn(Vector3<i32>) -> bool,
+) -> Option<HashSet<Vector3<i32>>> {
+    get_connectivity(start)?;
+
+    let mut visible = HashSet::from([start]);
+    // The chunk index, the face it was entered through and a mask of the directions of the steps that led to it.
+    let mut queue = VecDeque::from([(start, None, 0u8)]);
+    while let Some((index, entry_face, directions)) = queue.pop_front() {
+        let connectivity = match get_connectivity(index) {
+            Some(connectivity) => connectivity,
+            None => continue,
+        };
+
+        for axis in 0..6 {
+            let face_dir = FaceDir::from_axis(axis);
+            let opposite = chunk::opposite_face(face_dir);
+            if directions & (1 << opposite as u8) != 0 {
+                continue;
+            }
+            if entry_face.is_some_and(|entry_face| !connectivity.connects(entry_face, face_dir)) {
+                continue;
+            }
+
+            let neighbour = index + chunk::neighbour_offset(face_dir);
+            if visible.contains(&neighbour)
+                || get_connectivity(neighbour).is_none()
+                || !is_in_view(neighbour)
+            {
+                continue;
+            }
+
+            visible.insert(neighbour);
+            queue.push_back((neighbour, Some(opposite), directions | 1 << face_dir as u8));
+        }
+    }
+
+    Some(visible)
+}
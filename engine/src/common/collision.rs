@@ -0,0 +1,291 @@
+use std::collections::HashMap;
+
+use nalgebra::Vector3;
+
+use super::{aabb::Aabb, voxel::Voxel, VoxelHandle};
+
+/// How far apart two surfaces can be while still counting as touching.
+///
+/// This keeps a box that rests on a surface from being treated as overlapping the voxels below it.
+const EPSILON: f32 = 1e-4;
+/// The order the axes are resolved in, vertical first so walking up against a wall still lands.
+const AXIS_ORDER: [usize; 3] = [1, 0, 2];
+
+/// The result of moving a box through the voxel grid.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct CollisionResult {
+    /// The box after the movement.
+    pub aabb: Aabb,
+    /// The movement that was actually applied.
+    pub movement: Vector3<f32>,
+    /// The normals of the surfaces that blocked the movement, one component per axis.
+    ///
+    /// A component is 0 if the movement on that axis was not blocked, otherwise it points away
+    /// from the hit surface, so landing on the ground gives a y of 1.
+    pub contact_normal: Vector3<f32>,
+}
+
+impl CollisionResult {
+    /// Returns true if the box was stopped by a surface below it.
+    pub fn is_on_ground(&self) -> bool {
+        self.contact_normal.y > 0.0
+    }
+
+    /// Returns true if the movement was blocked on any axis.
+    pub fn has_collided(&self) -> bool {
+        self.contact_normal != Vector3::zeros()
+    }
+}
+
+/// Moves a box through the voxel grid and stops it at solid voxels.
+///
+/// The movement is resolved one axis at a time, so a blocked axis does not stop the others and
+/// the box slides along walls. Voxels the box already overlaps do not block it, so a box stuck
+/// inside the terrain can still move out. The result only depends on the arguments.
+///
+/// ## Arguments
+/// * `aabb` - The box to move.
+/// * `movement` - The movement to apply to the box.
+/// * `is_solid` - Returns true if the voxel at a world voxel position blocks the box.
+pub fn move_aabb(
+    aabb: &Aabb,
+    movement: Vector3<f32>,
+    mut is_solid: impl FnMut(Vector3<i32>) -> bool,
+) -> CollisionResult {
+    let mut aabb = *aabb;
+    let mut applied = Vector3::zeros();
+    let mut contact_normal = Vector3::zeros();
+
+    for axis in AXIS_ORDER {
+        let delta = sweep_axis(&aabb, axis, movement[axis], &mut is_solid);
+        if delta != movement[axis] {
+            contact_normal[axis] = -movement[axis].signum();
+        }
+        aabb.min[axis] += delta;
+        aabb.max[axis] += delta;
+        applied[axis] = delta;
+    }
+
+    CollisionResult {
+        aabb,
+        movement: applied,
+        contact_normal,
+    }
+}
+
+/// Returns true if any of the voxels overlapping the box is solid.
+pub fn is_overlapping(aabb: &Aabb, mut is_solid: impl FnMut(Vector3<i32>) -> bool) -> bool {
+    let min = aabb.min.coords.map(|c| (c + EPSILON).floor() as i32);
+    let max = aabb.max.coords.map(|c| (c - EPSILON).ceil() as i32 - 1);
+    (min.z..=max.z)
+        .any(|z| (min.y..=max.y).any(|y| (min.x..=max.x).any(|x| is_solid(Vector3::new(x, y, z)))))
+}
+
+/// Returns true if the voxel is present and its registry entry is collidable.
+///
+/// Voxels that are missing from the registry are treated as solid.
+pub fn is_collidable(registered_voxels: &HashMap<u32, Voxel>, voxel: Option<VoxelHandle>) -> bool {
+    match voxel {
+        Some(voxel) => registered_voxels
+            .get(&voxel.id)
+            .is_none_or(|voxel| voxel.collidable),
+        None => false,
+    }
+}
+
+/// Returns how far the box can move along a single axis before hitting a solid voxel.
+fn sweep_axis(
+    aabb: &Aabb,
+    axis: usize,
+    delta: f32,
+    is_solid: &mut impl FnMut(Vector3<i32>) -> bool,
+) -> f32 {
+    if delta == 0.0 {
+        return 0.0;
+    }
+
+    // The voxel range the box covers on the other two axes.
+    let (axis_a, axis_b) = ((axis + 1) % 3, (axis + 2) % 3);
+    let range = |axis: usize| {
+        (aabb.min[axis] + EPSILON).floor() as i32..=(aabb.max[axis] - EPSILON).ceil() as i32 - 1
+    };
+    let mut is_layer_solid = |layer: i32| {
+        range(axis_a).any(|a| {
+            range(axis_b).any(|b| {
+                let mut position = Vector3::zeros();
+                position[axis] = layer;
+                position[axis_a] = a;
+                position[axis_b] = b;
+                is_solid(position)
+            })
+        })
+    };
+
+    if delta > 0.0 {
+        let first_layer = (aabb.max[axis] - EPSILON).ceil() as i32;
+        let last_layer = (aabb.max[axis] + delta - EPSILON).ceil() as i32 - 1;
+        for layer in first_layer..=last_layer {
+            if is_layer_solid(layer) {
+                return (layer as f32 - aabb.max[axis]).clamp(0.0, delta);
+            }
+        }
+    } else {
+        let first_layer = (aabb.min[axis] + EPSILON).floor() as i32 - 1;
+        let last_layer = (aabb.min[axis] + delta + EPSILON).floor() as i32;
+        for layer in (last_layer..=first_layer).rev() {
+            if is_layer_solid(layer) {
+                return ((layer + 1) as f32 - aabb.min[axis]).clamp(delta, 0.0);
+            }
+        }
+    }
+    delta
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{point, vector, Point3};
+
+    use super::*;
+    use crate::{
+        common::{chunk, voxel::test_utils::create_test_voxels},
+        ecs::components::Chunk,
+    };
+
+    const STONE: VoxelHandle = VoxelHandle { id: 1 };
+    const WATER: VoxelHandle = VoxelHandle { id: 2 };
+    /// A voxel that is not registered.
+    const UNKNOWN: VoxelHandle = VoxelHandle { id: 3 };
+
+    /// The size of the test box, roughly the size of a player.
+    const BOX_SIZE: Vector3<f32> = Vector3::new(0.6, 1.8, 0.6);
+
+    fn registered_voxels() -> HashMap<u32, Voxel> {
+        create_test_voxels(&[(STONE, false), (WATER, true)])
+    }
+
+    /// Creates the chunk at the origin with a stone floor at y 0.
+    fn create_floor_chunk() -> Chunk {
+        let mut chunk = Chunk::new(vector![0, 0, 0]);
+        for x in 0..chunk::CHUNK_LENGTH {
+            for z in 0..chunk::CHUNK_LENGTH {
+                *chunk.sample_mut((x, 0, z)) = Some(STONE);
+            }
+        }
+        chunk
+    }
+
+    fn create_box(min: Point3<f32>) -> Aabb {
+        Aabb::new(min, min + BOX_SIZE)
+    }
+
+    /// Moves the box through the chunk, everything outside of the chunk is solid like unloaded chunks.
+    fn move_through(chunk: &Chunk, aabb: &Aabb, movement: Vector3<f32>) -> CollisionResult {
+        let registered_voxels = registered_voxels();
+        move_aabb(aabb, movement, |position| {
+            if chunk::world_to_chunk_index(position) != chunk.get_index() {
+                return true;
+            }
+            is_collidable(
+                &registered_voxels,
+                chunk.sample(chunk::world_to_local(position)),
+            )
+        })
+    }
+
+    fn assert_close(a: Vector3<f32>, b: Vector3<f32>) {
+        assert!((a - b).abs().max() < 1e-5, "{a:?} != {b:?}");
+    }
+
+    #[test]
+    fn lands_on_the_ground() {
+        let chunk = create_floor_chunk();
+        let result = move_through(
+            &chunk,
+            &create_box(point![10.2, 3.5, 10.2]),
+            vector![0.0, -5.0, 0.0],
+        );
+
+        assert!(result.is_on_ground());
+        assert_close(result.movement, vector![0.0, -2.5, 0.0]);
+        assert_close(result.aabb.min.coords, vector![10.2, 1.0, 10.2]);
+        assert_close(result.contact_normal, vector![0.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn slides_along_a_wall() {
+        let mut chunk = create_floor_chunk();
+        for y in 1..4 {
+            for z in 0..chunk::CHUNK_LENGTH {
+                *chunk.sample_mut((20, y, z)) = Some(STONE);
+            }
+        }
+
+        let result = move_through(
+            &chunk,
+            &create_box(point![18.9, 1.0, 10.2]),
+            vector![2.0, 0.0, 1.5],
+        );
+
+        assert!(result.has_collided());
+        assert!(!result.is_on_ground());
+        assert_close(result.movement, vector![0.5, 0.0, 1.5]);
+        assert_close(result.aabb.max.coords, vector![20.0, 2.8, 12.3]);
+        assert_close(result.contact_normal, vector![-1.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn moves_out_of_terrain_it_is_stuck_in() {
+        let mut chunk = create_floor_chunk();
+        *chunk.sample_mut((10, 1, 10)) = Some(STONE);
+        *chunk.sample_mut((10, 2, 10)) = Some(STONE);
+        let aabb = create_box(point![10.2, 1.1, 10.2]);
+        let registered_voxels = registered_voxels();
+        assert!(is_overlapping(&aabb, |position| is_collidable(
+            &registered_voxels,
+            chunk.sample(chunk::world_to_local(position))
+        )));
+
+        let result = move_through(&chunk, &aabb, vector![1.0, 0.0, 0.0]);
+
+        assert!(!result.has_collided());
+        assert_close(result.movement, vector![1.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn resting_on_a_surface_does_not_block_sideways_movement() {
+        let chunk = create_floor_chunk();
+        // The box may end up slightly inside or above the floor because of rounding errors.
+        for y in [1.0, 1.0 - EPSILON / 2.0, 1.0 + EPSILON / 2.0] {
+            let aabb = create_box(point![10.2, y, 10.2]);
+
+            let result = move_through(&chunk, &aabb, vector![1.0, 0.0, -1.0]);
+            assert!(!result.has_collided(), "blocked at y {y}");
+            assert_close(result.movement, vector![1.0, 0.0, -1.0]);
+
+            let result = move_through(&chunk, &aabb, vector![0.0, -0.5, 0.0]);
+            assert!(result.is_on_ground(), "fell through at y {y}");
+            assert!(result.aabb.min.y >= 1.0 - EPSILON);
+        }
+    }
+
+    #[test]
+    fn only_collidable_voxels_are_solid() {
+        let registered_voxels = registered_voxels();
+
+        assert!(is_collidable(&registered_voxels, Some(STONE)));
+        assert!(!is_collidable(&registered_voxels, Some(WATER)));
+        assert!(!is_collidable(&registered_voxels, None));
+        assert!(is_collidable(&registered_voxels, Some(UNKNOWN)));
+
+        let mut chunk = create_floor_chunk();
+        for y in 1..4 {
+            *chunk.sample_mut((10, y, 10)) = Some(WATER);
+        }
+        let result = move_through(
+            &chunk,
+            &create_box(point![10.2, 3.5, 10.2]),
+            vector![0.0, -5.0, 0.0],
+        );
+        assert_close(result.aabb.min.coords, vector![10.2, 1.0, 10.2]);
+    }
+}
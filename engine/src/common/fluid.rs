@@ -0,0 +1,547 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeSet, HashMap},
+    mem,
+};
+
+use nalgebra::Vector3;
+
+use super::{chunk, face_dir::FaceDir, voxel::Voxel, VoxelHandle};
+
+/// The amount of fluid ticks per second.
+pub const FLUID_TICKS_PER_SECOND: u32 = 20;
+/// The highest level of flowing fluid, the level drops by one for every voxel the fluid flows sideways.
+pub const MAX_FLOW_LEVEL: u8 = 7;
+/// The stored level of sources.
+///
+/// Fluid voxels are placed, generated and loaded with a stored level of 0, so they start out as sources.
+const SOURCE_LEVEL: u8 = 0;
+/// The stored level of falling fluid.
+const FALLING_LEVEL: u8 = MAX_FLOW_LEVEL + 1;
+/// The face directions that fluid flows sideways in.
+const SIDEWAYS_FACES: [FaceDir; 4] = [
+    FaceDir::Left,
+    FaceDir::Right,
+    FaceDir::Forward,
+    FaceDir::Back,
+];
+
+/// The level of a fluid voxel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FluidLevel {
+    /// The fluid never dries up.
+    Source,
+    /// The fluid falls down from the fluid above it and dries up without it.
+    Falling,
+    /// The fluid flows sideways from a neighbour with a higher level, from 1 to `MAX_FLOW_LEVEL`.
+    Flowing(u8),
+}
+
+impl FluidLevel {
+    /// Returns the fluid level of a stored level.
+    pub fn from_stored(level: u8) -> Self {
+        match level {
+            SOURCE_LEVEL => Self::Source,
+            1..=MAX_FLOW_LEVEL => Self::Flowing(level),
+            _ => Self::Falling,
+        }
+    }
+
+    /// Returns the level that is stored for the fluid level.
+    pub fn to_stored(self) -> u8 {
+        match self {
+            Self::Source => SOURCE_LEVEL,
+            Self::Falling => FALLING_LEVEL,
+            Self::Flowing(level) => level.clamp(1, MAX_FLOW_LEVEL),
+        }
+    }
+
+    /// Returns the level of the fluid that flows sideways out of a voxel with this level.
+    fn get_spread_level(self) -> u8 {
+        match self {
+            Self::Source | Self::Falling => MAX_FLOW_LEVEL,
+            Self::Flowing(level) => level.saturating_sub(1),
+        }
+    }
+}
+
+/// What a single voxel position contains, as far as fluids are concerned.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum FluidCell {
+    /// The position is not loaded, fluid does not flow into it.
+    Unloaded,
+    /// There is no voxel, fluid can flow into it.
+    Empty,
+    /// A voxel that is not a fluid, fluid can not flow into it.
+    Solid,
+    /// A fluid voxel and its level.
+    Fluid(VoxelHandle, FluidLevel),
+}
+
+/// The voxels that fluid flows through, all the positions are world voxel positions.
+pub trait FluidVolume {
+    /// Returns the voxel at the position and its stored fluid level, or `None` if the position is not loaded.
+    fn get_voxel(&self, position: Vector3<i32>) -> Option<(Option<VoxelHandle>, u8)>;
+    /// Sets the voxel at the position and its stored fluid level, positions that are not loaded are ignored.
+    fn set_voxel(&mut self, position: Vector3<i32>, voxel: Option<VoxelHandle>, level: u8);
+}
+
+/// A cellular fluid simulation.
+///
+/// Every tick the new state of all the active positions is computed from the current state of the volume
+/// and only then written back, so the result does not depend on the order of the positions.
+/// The active positions are also kept sorted, which makes the simulation fully deterministic.
+#[derive(Default)]
+pub struct FluidSimulation {
+    /// The positions whose fluid may change on the next tick.
+    active: BTreeSet<(i32, i32, i32)>,
+    /// The amount of ticks that have been simulated.
+    tick: u64,
+}
+
+impl FluidSimulation {
+    /// Queues a position whose fluid may change.
+    pub fn queue(&mut self, position: Vector3<i32>) {
+        self.active.insert((position.x, position.y, position.z));
+    }
+
+    /// Queues a changed position and its neighbours, whose fluid may change because of it.
+    pub fn queue_around(&mut self, position: Vector3<i32>) {
+        self.queue(position);
+        for axis in 0..6 {
+            self.queue(position + chunk::neighbour_offset(FaceDir::from_axis(axis)));
+        }
+    }
+
+    /// Returns true if there are no positions whose fluid may change.
+    pub fn is_idle(&self) -> bool {
+        self.active.is_empty()
+    }
+
+    /// Simulates a single tick.
+    ///
+    /// Fluids only flow on the ticks that match their `Voxel::flow_rate`,
+    /// the positions of the other fluids stay queued until their next flow tick.
+    ///
+    /// ## Arguments
+    /// * `volume` - The voxels the fluid flows through.
+    /// * `registered_voxels` - The registered voxels to look up the fluid flags and flow rates in.
+    ///
+    /// ## Returns
+    /// The positions whose voxel or fluid level has changed.
+    pub fn tick(
+        &mut self,
+        volume: &mut impl FluidVolume,
+        registered_voxels: &HashMap<u32, Voxel>,
+    ) -> Vec<Vector3<i32>> {
+        let tick = self.tick;
+        self.tick += 1;
+
+        let mut updates = vec![];
+        for (x, y, z) in mem::take(&mut self.active) {
+            let position = Vector3::new(x, y, z);
+            let (fluid, cell) = match next_cell(volume, registered_voxels, position) {
+                Some(next) => next,
+                None => continue,
+            };
+
+            if is_flow_tick(registered_voxels, fluid, tick) {
+                updates.push((position, cell));
+            } else {
+                self.active.insert((x, y, z));
+            }
+        }
+
+        for (position, cell) in &updates {
+            let (voxel, level) = match cell {
+                FluidCell::Fluid(voxel, level) => (Some(*voxel), level.to_stored()),
+                _ => (None, 0),
+            };
+            volume.set_voxel(*position, voxel, level);
+            self.queue_around(*position);
+        }
+
+        updates.into_iter().map(|(position, _)| position).collect()
+    }
+}
+
+/// Returns true if the voxel is a registered fluid.
+pub fn is_fluid(registered_voxels: &HashMap<u32, Voxel>, voxel: Option<VoxelHandle>) -> bool {
+    voxel
+        .and_then(|voxel| registered_voxels.get(&voxel.id))
+        .is_some_and(|voxel| voxel.fluid)
+}
+
+/// Returns true if the fluid flows on the specified tick.
+fn is_flow_tick(registered_voxels: &HashMap<u32, Voxel>, fluid: VoxelHandle, tick: u64) -> bool {
+    let flow_rate = registered_voxels
+        .get(&fluid.id)
+        .map_or(FLUID_TICKS_PER_SECOND, |voxel| voxel.flow_rate)
+        .clamp(1, FLUID_TICKS_PER_SECOND);
+    tick.is_multiple_of((FLUID_TICKS_PER_SECOND / flow_rate) as u64)
+}
+
+/// Returns what the position contains.
+fn get_cell(
+    volume: &impl FluidVolume,
+    registered_voxels: &HashMap<u32, Voxel>,
+    position: Vector3<i32>,
+) -> FluidCell {
+    match volume.get_voxel(position) {
+        None => FluidCell::Unloaded,
+        Some((None, _)) => FluidCell::Empty,
+        Some((Some(voxel), level)) if is_fluid(registered_voxels, Some(voxel)) => {
+            FluidCell::Fluid(voxel, FluidLevel::from_stored(level))
+        }
+        Some(_) => FluidCell::Solid,
+    }
+}
+
+/// Computes what the position contains after the next flow step.
+///
+/// Fluid falls into the empty voxels below it. Fluid that rests on a solid voxel or a source
+/// spreads sideways, losing a level for every voxel.
+/// An empty or flowing voxel between at least two sources of the same fluid that rests on
+/// a solid voxel or a source becomes a source itself. Fluid that is not fed by a neighbour dries up.
+/// When different fluids flow into the same voxel, the one with the highest level wins and
+/// the one with the lowest id breaks ties.
+///
+/// ## Returns
+/// The fluid that flows and the new content of the position,
+/// or `None` if the position does not change.
+fn next_cell(
+    volume: &impl FluidVolume,
+    registered_voxels: &HashMap<u32, Voxel>,
+    position: Vector3<i32>,
+) -> Option<(VoxelHandle, FluidCell)> {
+    let old_cell = get_cell(volume, registered_voxels, position);
+    let old_fluid = match old_cell {
+        FluidCell::Empty => None,
+        FluidCell::Fluid(fluid, level) if level != FluidLevel::Source => Some(fluid),
+        _ => return None,
+    };
+
+    let cell_at = |face_dir: FaceDir, position: Vector3<i32>| {
+        get_cell(
+            volume,
+            registered_voxels,
+            position + chunk::neighbour_offset(face_dir),
+        )
+    };
+
+    // Flowing fluid may be fed by a neighbour that is not loaded, so it is left alone until that neighbour loads.
+    let has_unloaded_feeder = [FaceDir::Up]
+        .into_iter()
+        .chain(SIDEWAYS_FACES)
+        .any(|face_dir| cell_at(face_dir, position) == FluidCell::Unloaded);
+    if old_fluid.is_some() && has_unloaded_feeder {
+        return None;
+    }
+
+    let next = match cell_at(FaceDir::Up, position) {
+        FluidCell::Fluid(fluid, _) => (fluid, FluidCell::Fluid(fluid, FluidLevel::Falling)),
+        _ => {
+            let below = cell_at(FaceDir::Down, position);
+            let mut best: Option<(VoxelHandle, u8)> = None;
+            let mut sources: Vec<(VoxelHandle, usize)> = vec![];
+            for face_dir in SIDEWAYS_FACES {
+                let neighbour = position + chunk::neighbour_offset(face_dir);
+                let (fluid, level) = match get_cell(volume, registered_voxels, neighbour) {
+                    FluidCell::Fluid(fluid, level) => (fluid, level),
+                    _ => continue,
+                };
+
+                if level == FluidLevel::Source {
+                    match sources.iter_mut().find(|(source, _)| *source == fluid) {
+                        Some((_, count)) => *count += 1,
+                        None => sources.push((fluid, 1)),
+                    }
+                }
+
+                // Fluid only spreads sideways when it rests on something, otherwise it keeps falling.
+                let rests = matches!(
+                    cell_at(FaceDir::Down, neighbour),
+                    FluidCell::Unloaded
+                        | FluidCell::Solid
+                        | FluidCell::Fluid(_, FluidLevel::Source)
+                );
+                if !rests {
+                    continue;
+                }
+
+                let spread_level = level.get_spread_level();
+                let key = |(fluid, level): (VoxelHandle, u8)| (level, Reverse(fluid.id));
+                if spread_level > 0
+                    && best.is_none_or(|best| key((fluid, spread_level)) > key(best))
+                {
+                    best = Some((fluid, spread_level));
+                }
+            }
+
+            let new_source = sources
+                .iter()
+                .filter(|(fluid, count)| {
+                    *count >= 2
+                        && match below {
+                            FluidCell::Solid => true,
+                            FluidCell::Fluid(below_fluid, FluidLevel::Source) => {
+                                below_fluid == *fluid
+                            }
+                            _ => false,
+                        }
+                })
+                .map(|(fluid, _)| *fluid)
+                .min_by_key(|fluid| fluid.id);
+
+            match (new_source, best, old_fluid) {
+                (Some(fluid), _, _) => (fluid, FluidCell::Fluid(fluid, FluidLevel::Source)),
+                (None, Some((fluid, level)), _) => {
+                    (fluid, FluidCell::Fluid(fluid, FluidLevel::Flowing(level)))
+                }
+                (None, None, Some(fluid)) => (fluid, FluidCell::Empty),
+                (None, None, None) => return None,
+            }
+        }
+    };
+
+    (next.1 != old_cell).then_some(next)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use nalgebra::{vector, Vector3};
+
+    use super::*;
+    use crate::common::voxel::test_utils::create_test_voxels;
+
+    const WATER: VoxelHandle = VoxelHandle { id: 1 };
+    const LAVA: VoxelHandle = VoxelHandle { id: 2 };
+    const STONE: VoxelHandle = VoxelHandle { id: 3 };
+
+    /// The horizontal distance from the origin that is inside of the test volume.
+    const HORIZONTAL_EXTENT: i32 = 10;
+    /// The highest voxel y position inside of the test volume, the lowest one is 1.
+    const TOP: i32 = 8;
+
+    /// A box of voxels that is surrounded by stone, the stone below it is the floor at y 0.
+    #[derive(Default, Clone, PartialEq, Debug)]
+    struct TestVolume {
+        voxels: HashMap<(i32, i32, i32), (VoxelHandle, u8)>,
+    }
+
+    impl TestVolume {
+        fn contains(position: Vector3<i32>) -> bool {
+            (-HORIZONTAL_EXTENT..=HORIZONTAL_EXTENT).contains(&position.x)
+                && (-HORIZONTAL_EXTENT..=HORIZONTAL_EXTENT).contains(&position.z)
+                && (1..=TOP).contains(&position.y)
+        }
+
+        fn get_level(&self, position: Vector3<i32>) -> Option<(VoxelHandle, FluidLevel)> {
+            self.voxels
+                .get(&(position.x, position.y, position.z))
+                .map(|(voxel, level)| (*voxel, FluidLevel::from_stored(*level)))
+        }
+    }
+
+    impl FluidVolume for TestVolume {
+        fn get_voxel(&self, position: Vector3<i32>) -> Option<(Option<VoxelHandle>, u8)> {
+            if !Self::contains(position) {
+                return Some((Some(STONE), 0));
+            }
+            match self.voxels.get(&(position.x, position.y, position.z)) {
+                Some((voxel, level)) => Some((Some(*voxel), *level)),
+                None => Some((None, 0)),
+            }
+        }
+
+        fn set_voxel(&mut self, position: Vector3<i32>, voxel: Option<VoxelHandle>, level: u8) {
+            if !Self::contains(position) {
+                return;
+            }
+            let key = (position.x, position.y, position.z);
+            match voxel {
+                Some(voxel) => self.voxels.insert(key, (voxel, level)),
+                None => self.voxels.remove(&key),
+            };
+        }
+    }
+
+    fn registered_voxels() -> HashMap<u32, Voxel> {
+        create_test_voxels(&[(WATER, true), (LAVA, true), (STONE, false)])
+    }
+
+    /// Places sources and queues them, like placing them in the world does.
+    fn place_sources(
+        volume: &mut TestVolume,
+        simulation: &mut FluidSimulation,
+        sources: &[(VoxelHandle, Vector3<i32>)],
+    ) {
+        for (fluid, position) in sources {
+            volume.set_voxel(*position, Some(*fluid), FluidLevel::Source.to_stored());
+            simulation.queue_around(*position);
+        }
+    }
+
+    /// Ticks the simulation until no fluid changes anymore.
+    fn settle(volume: &mut TestVolume, simulation: &mut FluidSimulation) {
+        let registered_voxels = registered_voxels();
+        for _ in 0..1000 {
+            if simulation.is_idle() {
+                return;
+            }
+            simulation.tick(volume, &registered_voxels);
+        }
+        panic!("the fluid did not settle");
+    }
+
+    #[test]
+    fn spreads_sideways_and_drops_a_level_per_voxel() {
+        let mut volume = TestVolume::default();
+        let mut simulation = FluidSimulation::default();
+        place_sources(&mut volume, &mut simulation, &[(WATER, vector![0, 1, 0])]);
+        settle(&mut volume, &mut simulation);
+
+        assert_eq!(
+            volume.get_level(vector![0, 1, 0]),
+            Some((WATER, FluidLevel::Source))
+        );
+        for distance in 1..=MAX_FLOW_LEVEL as i32 {
+            let level = Some((
+                WATER,
+                FluidLevel::Flowing(MAX_FLOW_LEVEL + 1 - distance as u8),
+            ));
+            assert_eq!(volume.get_level(vector![distance, 1, 0]), level);
+            assert_eq!(volume.get_level(vector![-distance, 1, 0]), level);
+            assert_eq!(volume.get_level(vector![0, 1, distance]), level);
+            assert_eq!(volume.get_level(vector![0, 1, -distance]), level);
+        }
+        let beyond = MAX_FLOW_LEVEL as i32 + 1;
+        assert_eq!(volume.get_level(vector![beyond, 1, 0]), None);
+        assert_eq!(volume.get_level(vector![0, 2, 0]), None);
+    }
+
+    #[test]
+    fn falls_down_and_spreads_on_the_floor() {
+        let mut volume = TestVolume::default();
+        let mut simulation = FluidSimulation::default();
+        place_sources(&mut volume, &mut simulation, &[(WATER, vector![0, 5, 0])]);
+        settle(&mut volume, &mut simulation);
+
+        for y in 1..5 {
+            assert_eq!(
+                volume.get_level(vector![0, y, 0]),
+                Some((WATER, FluidLevel::Falling))
+            );
+        }
+        // Fluid that does not rest on anything does not spread sideways.
+        assert_eq!(volume.get_level(vector![1, 5, 0]), None);
+        assert_eq!(volume.get_level(vector![1, 3, 0]), None);
+        assert_eq!(
+            volume.get_level(vector![1, 1, 0]),
+            Some((WATER, FluidLevel::Flowing(MAX_FLOW_LEVEL)))
+        );
+    }
+
+    #[test]
+    fn two_sources_create_a_new_source() {
+        let mut volume = TestVolume::default();
+        let mut simulation = FluidSimulation::default();
+        place_sources(
+            &mut volume,
+            &mut simulation,
+            &[(WATER, vector![-1, 1, 0]), (WATER, vector![1, 1, 0])],
+        );
+        settle(&mut volume, &mut simulation);
+
+        assert_eq!(
+            volume.get_level(vector![0, 1, 0]),
+            Some((WATER, FluidLevel::Source))
+        );
+        // A voxel next to a single source keeps flowing.
+        assert_eq!(
+            volume.get_level(vector![2, 1, 0]),
+            Some((WATER, FluidLevel::Flowing(MAX_FLOW_LEVEL)))
+        );
+    }
+
+    #[test]
+    fn flowing_fluid_dries_up_without_its_source() {
+        let mut volume = TestVolume::default();
+        let mut simulation = FluidSimulation::default();
+        let source = vector![0, 4, 0];
+        place_sources(&mut volume, &mut simulation, &[(WATER, source)]);
+        settle(&mut volume, &mut simulation);
+        assert!(volume.voxels.len() > 1);
+
+        volume.set_voxel(source, None, 0);
+        simulation.queue_around(source);
+        settle(&mut volume, &mut simulation);
+
+        assert!(volume.voxels.is_empty(), "{:?}", volume.voxels);
+    }
+
+    #[test]
+    fn lower_id_wins_a_tie_between_fluids() {
+        let mut volume = TestVolume::default();
+        let mut simulation = FluidSimulation::default();
+        // The fluid with the higher id is on the side that is looked at first.
+        let left = chunk::neighbour_offset(FaceDir::Left);
+        place_sources(
+            &mut volume,
+            &mut simulation,
+            &[
+                (LAVA, vector![0, 1, 0] + left),
+                (WATER, vector![0, 1, 0] - left),
+            ],
+        );
+        settle(&mut volume, &mut simulation);
+
+        assert_eq!(
+            volume.get_level(vector![0, 1, 0]),
+            Some((WATER, FluidLevel::Flowing(MAX_FLOW_LEVEL)))
+        );
+    }
+
+    #[test]
+    fn result_does_not_depend_on_the_queue_order() {
+        let sources = [
+            (WATER, vector![-3, 6, 2]),
+            (LAVA, vector![2, 1, -1]),
+            (WATER, vector![4, 1, 4]),
+            (WATER, vector![4, 1, 2]),
+            (LAVA, vector![-5, 3, -5]),
+        ];
+
+        let mut forward_volume = TestVolume::default();
+        let mut forward_simulation = FluidSimulation::default();
+        place_sources(&mut forward_volume, &mut forward_simulation, &sources);
+
+        let mut reversed_sources = sources;
+        reversed_sources.reverse();
+        let mut reversed_volume = TestVolume::default();
+        let mut reversed_simulation = FluidSimulation::default();
+        place_sources(
+            &mut reversed_volume,
+            &mut reversed_simulation,
+            &reversed_sources,
+        );
+
+        let registered_voxels = registered_voxels();
+        for _ in 0..1000 {
+            let mut forward_updates =
+                forward_simulation.tick(&mut forward_volume, &registered_voxels);
+            let mut reversed_updates =
+                reversed_simulation.tick(&mut reversed_volume, &registered_voxels);
+            forward_updates.sort_by_key(|position| (position.x, position.y, position.z));
+            reversed_updates.sort_by_key(|position| (position.x, position.y, position.z));
+            assert_eq!(forward_updates, reversed_updates);
+            assert_eq!(forward_volume, reversed_volume);
+            if forward_simulation.is_idle() {
+                break;
+            }
+        }
+        assert!(forward_simulation.is_idle() && reversed_simulation.is_idle());
+    }
+}
@@ -0,0 +1,192 @@
+use nalgebra::{Matrix4, Point3, Vector3, Vector4};
+
+use super::aabb::Aabb;
+
+/// A plane in world space, the points in front of it have a positive distance.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Plane {
+    /// The unit normal of the plane, pointing to the front.
+    pub normal: Vector3<f32>,
+    /// The signed distance of the origin from the plane.
+    pub distance: f32,
+}
+
+impl Plane {
+    /// Creates a plane from the coefficients of the plane equation `ax + by + cz + d = 0`.
+    ///
+    /// The coefficients are normalized, so the distances to the plane are in world units.
+    pub fn from_coefficients(coefficients: Vector4<f32>) -> Self {
+        let normal = coefficients.xyz();
+        let length = normal.norm();
+        if length == 0.0 {
+            return Self {
+                normal,
+                distance: coefficients.w,
+            };
+        }
+
+        Self {
+            normal: normal / length,
+            distance: coefficients.w / length,
+        }
+    }
+
+    /// Returns the signed distance of the point from the plane.
+    pub fn distance_to(&self, point: &Point3<f32>) -> f32 {
+        self.normal.dot(&point.coords) + self.distance
+    }
+}
+
+/// The volume that a camera can see, bounded by six planes facing inwards.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Frustum {
+    /// The left, right, bottom, top, near and far planes.
+    pub planes: [Plane; 6],
+}
+
+impl Frustum {
+    /// Extracts the planes of the frustum from a view projection matrix.
+    ///
+    /// The depth is expected to be mapped from 0 to 1 like in wgpu, so everything that wgpu clips
+    /// away is outside of the frustum.
+    pub fn from_view_proj(view_proj: &Matrix4<f32>) -> Self {
+        let row = |index| view_proj.row(index).transpose();
+        let (x, y, z, w) = (row(0), row(1), row(2), row(3));
+
+        Self {
+            planes: [w + x, w - x, w + y, w - y, z, w - z].map(Plane::from_coefficients),
+        }
+    }
+
+    /// Returns true if the point is inside of the frustum.
+    pub fn contains_point(&self, point: &Point3<f32>) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| plane.distance_to(point) >= 0.0)
+    }
+
+    /// Returns true if the box is at least partly inside of the frustum.
+    ///
+    /// This is conservative, a box near a corner of the frustum may be outside of it and still pass.
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        self.planes.iter().all(|plane| {
+            // The corner that is the farthest in front of the plane.
+            let corner = Point3::from(Vector3::from_fn(|axis, _| {
+                if plane.normal[axis] >= 0.0 {
+                    aabb.max[axis]
+                } else {
+                    aabb.min[axis]
+                }
+            }));
+            plane.distance_to(&corner) >= 0.0
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{point, Perspective3};
+
+    use super::*;
+
+    const LEFT: usize = 0;
+    const RIGHT: usize = 1;
+    const BOTTOM: usize = 2;
+    const TOP: usize = 3;
+    const NEAR: usize = 4;
+    const FAR: usize = 5;
+
+    /// Creates the frustum of a camera at z 10 looking towards -z, with a field of view of 90 degrees,
+    /// so the sides are as far from the center as the point is from the camera.
+    /// The near plane is at z 9 and the far plane at z -90.
+    fn create_frustum() -> Frustum {
+        let view = Matrix4::look_at_rh(
+            &point![0.0, 0.0, 10.0],
+            &point![0.0, 0.0, 0.0],
+            &Vector3::y(),
+        );
+        let proj = Perspective3::new(1.0, std::f32::consts::FRAC_PI_2, 1.0, 100.0);
+        // Maps the depth from -1..1 to 0..1 like wgpu expects.
+        #[rustfmt::skip]
+        let depth_to_wgpu = Matrix4::new(
+            1.0, 0.0, 0.0, 0.0,
+            0.0, 1.0, 0.0, 0.0,
+            0.0, 0.0, 0.5, 0.5,
+            0.0, 0.0, 0.0, 1.0,
+        );
+        Frustum::from_view_proj(&(depth_to_wgpu * proj.as_matrix() * view))
+    }
+
+    fn create_aabb(center: Point3<f32>, half_size: f32) -> Aabb {
+        let half_size = Vector3::repeat(half_size);
+        Aabb::new(center - half_size, center + half_size)
+    }
+
+    #[test]
+    fn planes_are_normalized() {
+        let frustum = create_frustum();
+        for plane in frustum.planes {
+            assert!((plane.normal.norm() - 1.0).abs() < 1e-5);
+        }
+        assert!((frustum.planes[NEAR].distance_to(&point![0.0, 0.0, 0.0]) - 9.0).abs() < 1e-3);
+        assert!((frustum.planes[FAR].distance_to(&point![0.0, 0.0, 0.0]) - 90.0).abs() < 1e-2);
+    }
+
+    #[test]
+    fn box_inside_intersects() {
+        let frustum = create_frustum();
+
+        assert!(frustum.contains_point(&point![0.0, 0.0, 0.0]));
+        assert!(frustum.intersects_aabb(&create_aabb(point![0.0, 0.0, 0.0], 1.0)));
+        assert!(frustum.intersects_aabb(&create_aabb(point![20.0, -20.0, -50.0], 5.0)));
+    }
+
+    #[test]
+    fn box_outside_of_each_plane_does_not_intersect() {
+        let frustum = create_frustum();
+        let outside = [
+            (LEFT, point![-25.0, 0.0, 0.0]),
+            (RIGHT, point![25.0, 0.0, 0.0]),
+            (BOTTOM, point![0.0, -25.0, 0.0]),
+            (TOP, point![0.0, 25.0, 0.0]),
+            (NEAR, point![0.0, 0.0, 9.5]),
+            (NEAR, point![0.0, 0.0, 20.0]),
+            (FAR, point![0.0, 0.0, -150.0]),
+        ];
+
+        for (plane, center) in outside {
+            let aabb = create_aabb(center, 0.25);
+            assert!(
+                frustum.planes[plane].distance_to(&center) < 0.0,
+                "{center:?} is in front of plane {plane}"
+            );
+            assert!(!frustum.contains_point(&center));
+            assert!(!frustum.intersects_aabb(&aabb), "{aabb:?} intersects");
+        }
+    }
+
+    #[test]
+    fn box_straddling_a_plane_intersects() {
+        let frustum = create_frustum();
+        let straddling = [
+            (LEFT, point![-10.0, 0.0, 0.0]),
+            (RIGHT, point![10.0, 0.0, 0.0]),
+            (BOTTOM, point![0.0, -10.0, 0.0]),
+            (TOP, point![0.0, 10.0, 0.0]),
+            (NEAR, point![0.0, 0.0, 9.0]),
+            (FAR, point![0.0, 0.0, -90.0]),
+        ];
+
+        for (plane, center) in straddling {
+            let aabb = create_aabb(center, 1.0);
+            assert!(
+                frustum.planes[plane].distance_to(&center).abs() < 1e-2,
+                "{center:?} is not on plane {plane}"
+            );
+            assert!(
+                frustum.intersects_aabb(&aabb),
+                "{aabb:?} does not intersect"
+            );
+        }
+    }
+}
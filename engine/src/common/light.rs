@@ -0,0 +1,184 @@
+use std::collections::{HashMap, VecDeque};
+
+use nalgebra::Vector3;
+
+use super::{chunk, face_dir::FaceDir, voxel::Voxel, VoxelHandle};
+
+/// The highest light level, the light level drops by one for every voxel it travels.
+pub const MAX_LIGHT_LEVEL: u8 = 15;
+
+/// The light levels of a chunk border layer, indexed by the row and the bit of `chunk::border_position`.
+pub type LightBorder = [[u8; chunk::CHUNK_LENGTH]; chunk::CHUNK_LENGTH];
+
+/// The kinds of light that are stored for every voxel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LightChannel {
+    /// The light of emissive voxels.
+    Block,
+    /// The light of the sky, it falls straight down without getting darker.
+    Sky,
+}
+
+impl LightChannel {
+    /// Returns the light level that spreads from a voxel with the specified level to its neighbour in the face direction.
+    pub fn get_spread_level(self, level: u8, face_dir: FaceDir) -> u8 {
+        match (self, face_dir) {
+            (Self::Sky, FaceDir::Down) if level == MAX_LIGHT_LEVEL => MAX_LIGHT_LEVEL,
+            _ => level.saturating_sub(1),
+        }
+    }
+}
+
+/// The block and the sky light level of a voxel.
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct LightLevels {
+    /// The block light level.
+    pub block: u8,
+    /// The sky light level.
+    pub sky: u8,
+}
+
+/// The lighting of a single voxel face, faces can only be merged if their lighting is the same.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct FaceLighting {
+    /// The ambient occlusion levels of the corners, see `chunk::face_ambient_occlusion`.
+    pub ambient_occlusion: [u8; 4],
+    /// The light levels of the voxel in front of the face.
+    pub light: LightLevels,
+}
+
+/// The voxels that light spreads through, all the positions are world voxel positions.
+pub trait LightVolume {
+    /// Returns the light level at the position, or `None` if the position is not loaded.
+    fn get_light(&self, position: Vector3<i32>) -> Option<u8>;
+    /// Sets the light level at the position, positions that are not loaded are ignored.
+    fn set_light(&mut self, position: Vector3<i32>, level: u8);
+    /// Returns true if light can spread into the voxel at the position.
+    fn is_transparent(&self, position: Vector3<i32>) -> bool;
+    /// Returns the light level that the voxel at the position emits.
+    fn get_emission(&self, position: Vector3<i32>) -> u8;
+
+    /// Returns the light level that spreads from a voxel with the specified level to its neighbour in the face direction.
+    fn get_spread_level(&self, level: u8, _face_dir: FaceDir) -> u8 {
+        level.saturating_sub(1)
+    }
+}
+
+/// Spreads and removes light with breadth first flood fills.
+///
+/// How much darker the light gets with every step is decided by `LightVolume::get_spread_level`.
+/// The queues are kept between updates, so their allocations can be reused.
+#[derive(Default)]
+pub struct LightPropagator {
+    /// The lit positions whose light has to spread to their neighbours.
+    add_queue: VecDeque<Vector3<i32>>,
+    /// The darkened positions and the light levels they had before.
+    remove_queue: VecDeque<(Vector3<i32>, u8)>,
+}
+
+impl LightPropagator {
+    /// Queues the light at a lit position to spread to its neighbours.
+    pub fn queue_add(&mut self, position: Vector3<i32>) {
+        self.add_queue.push_back(position);
+    }
+
+    /// Updates the light around a voxel that has changed.
+    ///
+    /// The old light of the voxel is removed, then the voxel emits its own light
+    /// and the light of its neighbours spreads back into it.
+    pub fn update_voxel(&mut self, volume: &mut impl LightVolume, position: Vector3<i32>) {
+        if let Some(level) = volume.get_light(position).filter(|level| *level > 0) {
+            volume.set_light(position, 0);
+            self.remove_queue.push_back((position, level));
+        }
+
+        let emission = volume.get_emission(position);
+        if emission > 0 {
+            volume.set_light(position, emission);
+            self.add_queue.push_back(position);
+        }
+
+        for (_, neighbour) in neighbour_positions(position) {
+            if volume.get_light(neighbour).is_some_and(|level| level > 0) {
+                self.add_queue.push_back(neighbour);
+            }
+        }
+    }
+
+    /// Processes the queued updates until the light has settled.
+    ///
+    /// Removals are processed first, every lit voxel that the removed light did not reach
+    /// is queued to spread its light back into the darkened area.
+    pub fn propagate(&mut self, volume: &mut impl LightVolume) {
+        while let Some((position, level)) = self.remove_queue.pop_front() {
+            for (face_dir, neighbour) in neighbour_positions(position) {
+                let neighbour_level = match volume.get_light(neighbour) {
+                    Some(neighbour_level) if neighbour_level > 0 => neighbour_level,
+                    _ => continue,
+                };
+
+                // A brighter neighbour was not lit by the removed light, so it spreads back into the darkened area.
+                if neighbour_level > volume.get_spread_level(level, face_dir) {
+                    self.add_queue.push_back(neighbour);
+                    continue;
+                }
+
+                volume.set_light(neighbour, 0);
+                self.remove_queue.push_back((neighbour, neighbour_level));
+                let emission = volume.get_emission(neighbour);
+                if emission > 0 {
+                    volume.set_light(neighbour, emission);
+                    self.add_queue.push_back(neighbour);
+                }
+            }
+        }
+
+        while let Some(position) = self.add_queue.pop_front() {
+            // The level is read again, because it may have changed since the position was queued.
+            let level = match volume.get_light(position) {
+                Some(level) if level > 0 => level,
+                _ => continue,
+            };
+
+            for (face_dir, neighbour) in neighbour_positions(position) {
+                let spread_level = volume.get_spread_level(level, face_dir);
+                if spread_level > 0
+                    && volume
+                        .get_light(neighbour)
+                        .is_some_and(|l| l < spread_level)
+                    && volume.is_transparent(neighbour)
+                {
+                    volume.set_light(neighbour, spread_level);
+                    self.add_queue.push_back(neighbour);
+                }
+            }
+        }
+    }
+}
+
+/// Returns true if light can spread through the voxel.
+///
+/// Voxels that are missing from the registry block light.
+pub fn is_transparent(registered_voxels: &HashMap<u32, Voxel>, voxel: Option<VoxelHandle>) -> bool {
+    match voxel {
+        Some(voxel) => registered_voxels
+            .get(&voxel.id)
+            .is_some_and(|voxel| !voxel.blocks_light()),
+        None => true,
+    }
+}
+
+/// Returns the light level that the voxel emits, clamped to `MAX_LIGHT_LEVEL`.
+pub fn get_emission(registered_voxels: &HashMap<u32, Voxel>, voxel: Option<VoxelHandle>) -> u8 {
+    voxel
+        .and_then(|voxel| registered_voxels.get(&voxel.id))
+        .map_or(0, |voxel| voxel.emission.min(MAX_LIGHT_LEVEL))
+}
+
+/// Returns the six positions that share a face with the specified one and the directions they are in.
+fn neighbour_positions(position: Vector3<i32>) -> impl Iterator<Item = (FaceDir, Vector3<i32>)> {
+    (0..6).map(move |axis| {
+        let face_dir = FaceDir::from_axis(axis);
+        (face_dir, position + chunk::neighbour_offset(face_dir))
+    })
+}
@@ -1,5 +1,18 @@
 pub mod voxel;
 pub use voxel::VoxelHandle;
+pub mod voxel_shape;
+pub mod aabb;
 pub mod chunk;
+pub mod chunk_visibility;
+pub mod collision;
 pub mod face_dir;
+pub mod fluid;
+pub mod frustum;
+pub mod light;
+pub mod nibble_storage;
+pub mod palette;
 pub mod quad;
+pub mod raycast;
+pub mod region;
+pub mod world;
+pub mod world_generator;
@@ -0,0 +1,86 @@
+use std::mem;
+
+/// The highest value that fits into a nibble.
+pub const MAX_NIBBLE: u8 = 0xF;
+
+/// Values from 0 to `MAX_NIBBLE` that are packed into two per byte.
+///
+/// Nothing is allocated while all the values are the same, so chunks that are completely dark,
+/// completely open to the sky or without any flowing fluid do not use any extra memory.
+#[derive(Clone, Debug)]
+pub struct NibbleStorage {
+    values: Option<Box<[u8]>>,
+    /// The value at every index while `values` is not allocated.
+    uniform_value: u8,
+    len: usize,
+}
+
+impl NibbleStorage {
+    /// Creates a new storage of `len` values that are all 0.
+    pub fn new(len: usize) -> Self {
+        Self {
+            values: None,
+            uniform_value: 0,
+            len,
+        }
+    }
+
+    /// Returns the amount of values in the storage.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if the storage does not contain any values.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the value at every index if all of them are known to be the same.
+    ///
+    /// This only checks if the storage is allocated, so it can return `None` even if the values are the same.
+    pub fn get_uniform_value(&self) -> Option<u8> {
+        self.values.is_none().then_some(self.uniform_value)
+    }
+
+    /// Returns the value at the specified index.
+    ///
+    /// ## Panics
+    /// If the index is out of bounds.
+    pub fn get(&self, index: usize) -> u8 {
+        assert!(index < self.len, "nibble index {index} out of bounds");
+        match &self.values {
+            Some(values) => (values[index / 2] >> (index % 2 * 4)) & MAX_NIBBLE,
+            None => self.uniform_value,
+        }
+    }
+
+    /// Sets the value at the specified index, the value is clamped to `MAX_NIBBLE`.
+    ///
+    /// ## Panics
+    /// If the index is out of bounds.
+    pub fn set(&mut self, index: usize, value: u8) {
+        assert!(index < self.len, "nibble index {index} out of bounds");
+        let value = value.min(MAX_NIBBLE);
+        if value == self.uniform_value && self.values.is_none() {
+            return;
+        }
+
+        let (len, uniform_value) = (self.len, self.uniform_value);
+        let values = self.values.get_or_insert_with(|| {
+            vec![uniform_value | (uniform_value << 4); len.div_ceil(2)].into_boxed_slice()
+        });
+        let shift = index % 2 * 4;
+        values[index / 2] = (values[index / 2] & !(MAX_NIBBLE << shift)) | (value << shift);
+    }
+
+    /// Sets every value to the same one and frees the storage.
+    pub fn fill(&mut self, value: u8) {
+        self.values = None;
+        self.uniform_value = value.min(MAX_NIBBLE);
+    }
+
+    /// Returns the approximate amount of memory used by this storage in bytes.
+    pub fn memory_usage(&self) -> usize {
+        mem::size_of::<Self>() + self.values.as_ref().map_or(0, |values| values.len())
+    }
+}
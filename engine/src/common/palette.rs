@@ -0,0 +1,492 @@
+use std::{mem, slice};
+
+use super::VoxelHandle;
+
+/// Type used for storing the bit-packed palette indices.
+type PackedWord = u64;
+/// The amount of bits in a single `PackedWord`.
+const WORD_BITS: u32 = PackedWord::BITS;
+/// The smallest amount of bits used for a single palette index.
+pub const MIN_BITS_PER_INDEX: u32 = 1;
+/// The largest amount of bits used for a single palette index.
+pub const MAX_BITS_PER_INDEX: u32 = 16;
+
+/// Palette-compressed storage for voxels.
+///
+/// Every stored value is an index into a small local palette of voxels. The indices are
+/// bit-packed and grow from `MIN_BITS_PER_INDEX` to `MAX_BITS_PER_INDEX` bits as the palette grows.
+/// Indices never span two words, so some bits at the end of each word can stay unused.
+#[derive(Clone, Debug)]
+pub struct PalettedVoxels {
+    /// The local palette, the packed indices point into this.
+    palette: Vec<Option<VoxelHandle>>,
+    /// The amount of bits used by a single index.
+    bits_per_index: u32,
+    /// The bit-packed palette indices.
+    data: Vec<PackedWord>,
+    /// The amount of stored voxels.
+    len: usize,
+}
+
+impl PalettedVoxels {
+    /// Creates a new storage of `len` voxels that are all set to `value`.
+    pub fn new(len: usize, value: Option<VoxelHandle>) -> Self {
+        Self {
+            palette: vec![value],
+            bits_per_index: MIN_BITS_PER_INDEX,
+            data: vec![0; word_count(len, MIN_BITS_PER_INDEX)],
+            len,
+        }
+    }
+
+    /// Creates a new storage from a slice of voxels.
+    pub fn from_slice(voxels: &[Option<VoxelHandle>]) -> Self {
+        let mut storage = Self::new(voxels.len(), voxels.first().copied().flatten());
+        for (index, voxel) in voxels.iter().enumerate() {
+            storage.set(index, *voxel);
+        }
+        storage
+    }
+
+    /// Returns the amount of stored voxels.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if there are no stored voxels.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the local palette.
+    pub fn get_palette(&self) -> &[Option<VoxelHandle>] {
+        &self.palette
+    }
+
+    /// Returns the amount of bits a single palette index currently takes up.
+    pub fn get_bits_per_index(&self) -> u32 {
+        self.bits_per_index
+    }
+
+    /// Returns the voxel at the specified index.
+    ///
+    /// ## Panics
+    /// If the index is out of bounds.
+    pub fn get(&self, index: usize) -> Option<VoxelHandle> {
+        assert!(index < self.len, "voxel index {index} out of bounds");
+        self.palette[self.get_palette_index(index)]
+    }
+
+    /// Sets the voxel at the specified index.
+    ///
+    /// ## Panics
+    /// If the index is out of bounds.
+    pub fn set(&mut self, index: usize, value: Option<VoxelHandle>) {
+        assert!(index < self.len, "voxel index {index} out of bounds");
+        let palette_index = match self.palette.iter().position(|v| *v == value) {
+            Some(palette_index) => palette_index,
+            None => self.add_to_palette(value),
+        };
+        self.set_palette_index(index, palette_index);
+    }
+
+    /// Removes the unused palette entries and shrinks the packed indices if possible.
+    pub fn optimize(&mut self) {
+        let mut used = vec![false; self.palette.len()];
+        for index in 0..self.len {
+            used[self.get_palette_index(index)] = true;
+        }
+
+        let mut remap = vec![0; self.palette.len()];
+        let mut palette = Vec::with_capacity(self.palette.len());
+        for (old_index, voxel) in self.palette.iter().enumerate() {
+            if used[old_index] {
+                remap[old_index] = palette.len();
+                palette.push(*voxel);
+            }
+        }
+        if palette.is_empty() {
+            palette.push(self.palette[0]);
+        }
+
+        let bits_per_index = bits_for_palette_len(palette.len());
+        self.repack(bits_per_index, |old_index| remap[old_index]);
+        palette.shrink_to_fit();
+        self.palette = palette;
+    }
+
+    /// Returns the approximate amount of memory used by this storage in bytes.
+    pub fn memory_usage(&self) -> usize {
+        mem::size_of::<Self>()
+            + self.palette.capacity() * mem::size_of::<Option<VoxelHandle>>()
+            + self.data.capacity() * mem::size_of::<PackedWord>()
+    }
+
+    /// Adds a new value to the palette, grows the indices if needed and returns the new palette index.
+    fn add_to_palette(&mut self, value: Option<VoxelHandle>) -> usize {
+        if self.palette.len() >= 1 << MAX_BITS_PER_INDEX {
+            // Try to reclaim unused palette entries before giving up.
+            self.optimize();
+            assert!(
+                self.palette.len() < 1 << MAX_BITS_PER_INDEX,
+                "voxel palette cannot hold more than {} entries",
+                1 << MAX_BITS_PER_INDEX
+            );
+        }
+
+        self.palette.push(value);
+        let bits_per_index = bits_for_palette_len(self.palette.len());
+        if bits_per_index > self.bits_per_index {
+            self.repack(bits_per_index, |index| index);
+        }
+        self.palette.len() - 1
+    }
+
+    /// Repacks all the indices with a new bit width, mapping each index with `map`.
+    fn repack(&mut self, bits_per_index: u32, map: impl Fn(usize) -> usize) {
+        let mut repacked = Self {
+            palette: vec![],
+            bits_per_index,
+            data: vec![0; word_count(self.len, bits_per_index)],
+            len: self.len,
+        };
+        for index in 0..self.len {
+            repacked.set_palette_index(index, map(self.get_palette_index(index)));
+        }
+        self.bits_per_index = repacked.bits_per_index;
+        self.data = repacked.data;
+    }
+
+    /// Reads the packed palette index at the specified voxel index.
+    fn get_palette_index(&self, index: usize) -> usize {
+        let (word, shift) = locate(index, self.bits_per_index);
+        ((self.data[word] >> shift) & index_mask(self.bits_per_index)) as usize
+    }
+
+    /// Writes the packed palette index at the specified voxel index.
+    fn set_palette_index(&mut self, index: usize, palette_index: usize) {
+        let (word, shift) = locate(index, self.bits_per_index);
+        let mask = index_mask(self.bits_per_index);
+        self.data[word] &= !(mask << shift);
+        self.data[word] |= (palette_index as PackedWord & mask) << shift;
+    }
+}
+
+/// Returns the amount of bits needed to index a palette of the given length.
+fn bits_for_palette_len(len: usize) -> u32 {
+    let bits = usize::BITS - len.saturating_sub(1).leading_zeros();
+    bits.clamp(MIN_BITS_PER_INDEX, MAX_BITS_PER_INDEX)
+}
+
+/// Returns the amount of words needed to store `len` indices of the given bit width.
+fn word_count(len: usize, bits_per_index: u32) -> usize {
+    let per_word = (WORD_BITS / bits_per_index) as usize;
+    len.div_ceil(per_word)
+}
+
+/// Returns the word and the bit shift of an index.
+fn locate(index: usize, bits_per_index: u32) -> (usize, u32) {
+    let per_word = (WORD_BITS / bits_per_index) as usize;
+    (index / per_word, (index % per_word) as u32 * bits_per_index)
+}
+
+/// Returns a mask that covers a single index of the given bit width.
+fn index_mask(bits_per_index: u32) -> PackedWord {
+    (1 << bits_per_index) - 1
+}
+
+/// Voxel storage that only allocates when the voxels are not all the same.
+#[derive(Clone, Debug)]
+pub enum VoxelStorage {
+    /// Every voxel has the same value, so only the value and the amount of voxels are stored.
+    Uniform {
+        value: Option<VoxelHandle>,
+        len: usize,
+    },
+    /// The voxels are stored in a palette-compressed container.
+    Paletted(PalettedVoxels),
+}
+
+impl VoxelStorage {
+    /// Creates a new uniform storage of `len` voxels that are all set to `value`.
+    pub fn new(len: usize, value: Option<VoxelHandle>) -> Self {
+        Self::Uniform { value, len }
+    }
+
+    /// Creates a new storage from a slice of voxels.
+    ///
+    /// If every voxel in the slice is the same, the storage will be uniform.
+    pub fn from_slice(voxels: &[Option<VoxelHandle>]) -> Self {
+        match voxels.split_first() {
+            Some((first, rest)) if rest.iter().any(|v| v != first) => {
+                Self::Paletted(PalettedVoxels::from_slice(voxels))
+            }
+            first => Self::new(voxels.len(), first.and_then(|(first, _)| *first)),
+        }
+    }
+
+    /// Returns the amount of stored voxels.
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Uniform { len, .. } => *len,
+            Self::Paletted(voxels) => voxels.len(),
+        }
+    }
+
+    /// Returns true if there are no stored voxels.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the value of every voxel if the storage is uniform.
+    pub fn get_uniform_value(&self) -> Option<Option<VoxelHandle>> {
+        match self {
+            Self::Uniform { value, .. } => Some(*value),
+            Self::Paletted(_) => None,
+        }
+    }
+
+    /// Returns the voxel values the storage can contain.
+    ///
+    /// The palette may contain values that are no longer used until the storage is optimized.
+    pub fn get_palette(&self) -> &[Option<VoxelHandle>] {
+        match self {
+            Self::Uniform { value, .. } => slice::from_ref(value),
+            Self::Paletted(voxels) => voxels.get_palette(),
+        }
+    }
+
+    /// Returns the voxel at the specified index.
+    ///
+    /// ## Panics
+    /// If the index is out of bounds.
+    pub fn get(&self, index: usize) -> Option<VoxelHandle> {
+        match self {
+            Self::Uniform { value, len } => {
+                assert!(index < *len, "voxel index {index} out of bounds");
+                *value
+            }
+            Self::Paletted(voxels) => voxels.get(index),
+        }
+    }
+
+    /// Sets the voxel at the specified index.
+    ///
+    /// A uniform storage gets promoted to a paletted one on the first differing write.
+    ///
+    /// ## Panics
+    /// If the index is out of bounds.
+    pub fn set(&mut self, index: usize, value: Option<VoxelHandle>) {
+        match self {
+            Self::Uniform {
+                value: uniform_value,
+                len,
+            } => {
+                assert!(index < *len, "voxel index {index} out of bounds");
+                if *uniform_value != value {
+                    let mut voxels = PalettedVoxels::new(*len, *uniform_value);
+                    voxels.set(index, value);
+                    *self = Self::Paletted(voxels);
+                }
+            }
+            Self::Paletted(voxels) => voxels.set(index, value),
+        }
+    }
+
+    /// Removes the unused palette entries and turns the storage uniform if possible.
+    pub fn optimize(&mut self) {
+        if let Self::Paletted(voxels) = self {
+            voxels.optimize();
+            if let [value] = voxels.get_palette() {
+                *self = Self::new(voxels.len(), *value);
+            }
+        }
+    }
+
+    /// Returns the approximate amount of memory used by this storage in bytes.
+    pub fn memory_usage(&self) -> usize {
+        match self {
+            Self::Uniform { .. } => mem::size_of::<Self>(),
+            Self::Paletted(voxels) => {
+                mem::size_of::<Self>() - mem::size_of::<PalettedVoxels>() + voxels.memory_usage()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn voxel(id: u32) -> Option<VoxelHandle> {
+        Some(VoxelHandle { id })
+    }
+
+    fn assert_contents(storage: &VoxelStorage, expected: &[Option<VoxelHandle>]) {
+        assert_eq!(storage.len(), expected.len());
+        for (index, value) in expected.iter().enumerate() {
+            assert_eq!(storage.get(index), *value, "voxel {index}");
+        }
+    }
+
+    #[test]
+    fn values_survive_every_bit_width() {
+        // Large enough for a palette that needs the maximum bit width.
+        let len = (1 << (MAX_BITS_PER_INDEX - 1)) + 100;
+        let mut voxels = PalettedVoxels::new(len, None);
+        let mut expected = vec![None; len];
+        let mut bit_widths = vec![voxels.get_bits_per_index()];
+
+        // Every set adds a new palette entry.
+        for index in 0..=1 << (MAX_BITS_PER_INDEX - 1) {
+            voxels.set(index, voxel(index as u32));
+            expected[index] = voxel(index as u32);
+
+            if voxels.get_bits_per_index() != *bit_widths.last().unwrap() {
+                bit_widths.push(voxels.get_bits_per_index());
+                for (index, value) in expected.iter().enumerate() {
+                    assert_eq!(voxels.get(index), *value, "voxel {index}");
+                }
+            }
+        }
+
+        assert_eq!(
+            bit_widths,
+            (MIN_BITS_PER_INDEX..=MAX_BITS_PER_INDEX).collect::<Vec<_>>()
+        );
+        for (index, value) in expected.iter().enumerate() {
+            assert_eq!(voxels.get(index), *value, "voxel {index}");
+        }
+    }
+
+    #[test]
+    fn indices_never_span_two_words() {
+        for bits_per_index in MIN_BITS_PER_INDEX..=MAX_BITS_PER_INDEX {
+            let per_word = (WORD_BITS / bits_per_index) as usize;
+            assert_eq!(
+                word_count(1000, bits_per_index),
+                1000usize.div_ceil(per_word)
+            );
+            for index in 0..1000 {
+                let (word, shift) = locate(index, bits_per_index);
+                assert_eq!(word, index / per_word);
+                assert!(shift + bits_per_index <= WORD_BITS);
+            }
+        }
+
+        // With 3 bits only 21 indices fit into a word, the last bit of every word stays unused.
+        let mut voxels = PalettedVoxels::new(64, None);
+        for id in 1..=4 {
+            voxels.set(id as usize, voxel(id));
+        }
+        assert_eq!(voxels.get_bits_per_index(), 3);
+        assert_eq!(voxels.data.len(), 4);
+        assert_eq!(locate(20, 3), (0, 60));
+        assert_eq!(locate(21, 3), (1, 0));
+
+        voxels.set(20, voxel(4));
+        voxels.set(21, voxel(3));
+        voxels.set(19, voxel(2));
+        voxels.set(63, voxel(4));
+        assert_eq!(voxels.get(19), voxel(2));
+        assert_eq!(voxels.get(20), voxel(4));
+        assert_eq!(voxels.get(21), voxel(3));
+        assert_eq!(voxels.get(22), None);
+        assert_eq!(voxels.get(63), voxel(4));
+        assert!(voxels.data.iter().all(|word| word >> 63 == 0));
+    }
+
+    #[test]
+    fn optimize_turns_overwritten_storage_uniform() {
+        let mut storage = VoxelStorage::new(4096, None);
+        for index in 0..4096 {
+            storage.set(index, voxel(index as u32 % 7));
+        }
+        assert_eq!(storage.get_uniform_value(), None);
+
+        for index in 0..4096 {
+            storage.set(index, voxel(1));
+        }
+        // The palette still holds the values that are not used anymore.
+        assert!(storage.get_palette().len() > 1);
+
+        storage.optimize();
+        assert_eq!(storage.get_uniform_value(), Some(voxel(1)));
+        assert_eq!(storage.get_palette(), &[voxel(1)]);
+        assert_contents(&storage, &[voxel(1); 4096]);
+    }
+
+    #[test]
+    fn optimize_shrinks_the_indices() {
+        let mut voxels = PalettedVoxels::new(256, None);
+        for index in 0..256 {
+            voxels.set(index, voxel(index as u32 % 20));
+        }
+        assert_eq!(voxels.get_bits_per_index(), 5);
+
+        for index in 0..256 {
+            voxels.set(index, voxel(index as u32 % 2));
+        }
+        voxels.optimize();
+
+        assert_eq!(voxels.get_bits_per_index(), 1);
+        assert_eq!(voxels.get_palette().len(), 2);
+        for index in 0..256 {
+            assert_eq!(voxels.get(index), voxel(index as u32 % 2));
+        }
+    }
+
+    #[test]
+    fn from_slice_matches_setting_every_voxel() {
+        let values = (0..5000)
+            .map(|index: u32| {
+                (!index.is_multiple_of(13)).then_some(VoxelHandle { id: index / 100 })
+            })
+            .collect::<Vec<_>>();
+
+        let from_slice = VoxelStorage::from_slice(&values);
+        let mut set = VoxelStorage::new(values.len(), None);
+        for (index, value) in values.iter().enumerate() {
+            set.set(index, *value);
+        }
+
+        assert_contents(&from_slice, &values);
+        assert_contents(&set, &values);
+        assert_eq!(from_slice.get_palette().len(), set.get_palette().len());
+
+        let uniform = VoxelStorage::from_slice(&[voxel(3); 100]);
+        assert_eq!(uniform.get_uniform_value(), Some(voxel(3)));
+        assert_eq!(uniform.len(), 100);
+        let empty = VoxelStorage::from_slice(&[]);
+        assert_eq!(empty.get_uniform_value(), Some(None));
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn memory_usage_grows_with_the_palette() {
+        let mut storage = VoxelStorage::new(4096, None);
+        let mut memory_usage = storage.memory_usage();
+        let mut bits_per_index = 0;
+
+        for id in 0..300 {
+            storage.set(id as usize, voxel(id));
+            let new_memory_usage = storage.memory_usage();
+            assert!(
+                new_memory_usage >= memory_usage,
+                "palette of {} entries",
+                id + 2
+            );
+
+            let new_bits_per_index = match &storage {
+                VoxelStorage::Paletted(voxels) => voxels.get_bits_per_index(),
+                VoxelStorage::Uniform { .. } => unreachable!(),
+            };
+            // Wider indices need more words.
+            if new_bits_per_index != bits_per_index {
+                assert!(new_memory_usage > memory_usage);
+                bits_per_index = new_bits_per_index;
+            }
+            memory_usage = new_memory_usage;
+        }
+    }
+}
@@ -2,7 +2,7 @@ use nalgebra::{Vector2, Vector3};
 
 use crate::rendering::{index::Index, vertex::Vertex};
 
-use super::face_dir::FaceDir;
+use super::{face_dir::FaceDir, light::FaceLighting, voxel::Voxel};
 
 /// Represents a quad in 2d space.
 pub struct Quad {
@@ -19,6 +19,10 @@ impl Quad {
     /// * `voxel_texture_index` - The texture index of the voxel, that this quad represents.
     /// * `face_dir` - The face direction of the quad.
     /// * `axis_pos` - The axis position of the quad.
+    /// * `lighting` - The ambient occlusion and the light levels of the quad.
+    ///
+    /// ## Panics
+    /// If the vertices can not be indexed with `Index` anymore.
     pub fn append_to_vertices(
         self,
         vertices: &mut Vec<Vertex>,
@@ -26,48 +30,46 @@ impl Quad {
         voxel_texture_index: Vector3<u32>,
         face_dir: FaceDir,
         axis_pos: i32,
+        lighting: FaceLighting,
     ) {
-        let normal = face_dir.get_normal().into();
-        let get_pos = |x, y| {
-            face_dir
-                .world_to_sample(axis_pos, x, y)
-                .map(|i| i as f32)
-                .into()
-        };
-
-        let new_vertices = [
-            Vertex {
-                position: get_pos(self.position.x, self.position.y),
-                tex_coords: [0.0, 0.0],
-                normal,
-                texture_index: voxel_texture_index.into(),
-            },
-            Vertex {
-                position: get_pos(self.position.x + self.size.x, self.position.y),
-                tex_coords: [self.size.x as f32, 0.0],
-                normal,
-                texture_index: voxel_texture_index.into(),
-            },
-            Vertex {
-                position: get_pos(self.position.x, self.position.y + self.size.y),
-                tex_coords: [0.0, self.size.y as f32],
-                normal,
-                texture_index: voxel_texture_index.into(),
-            },
-            Vertex {
-                position: get_pos(self.position.x + self.size.x, self.position.y + self.size.y),
-                tex_coords: [self.size.x as f32, self.size.y as f32],
-                normal,
-                texture_index: voxel_texture_index.into(),
-            },
+        let texture_layer = Voxel::get_face_texture_index(voxel_texture_index, face_dir);
+        let ambient_occlusion = lighting.ambient_occlusion;
+        let corners = [
+            (0, 0),
+            (self.size.x, 0),
+            (0, self.size.y),
+            (self.size.x, self.size.y),
         ];
 
-        let new_indices: [Index; 6] = match face_dir.reverse_direction() {
-            true => [0, 1, 3, 0, 3, 2],
-            false => [0, 3, 1, 0, 2, 3],
+        let new_vertices = corners
+            .into_iter()
+            .zip(ambient_occlusion)
+            .map(|((x, y), ao)| {
+                let position =
+                    face_dir.world_to_sample(axis_pos, self.position.x + x, self.position.y + y);
+                Vertex::new(
+                    position.map(|i| i as f32),
+                    [x as f32, y as f32],
+                    face_dir as u8,
+                    texture_layer,
+                    ao,
+                    lighting.light,
+                )
+            });
+
+        // Split the quad along the brighter diagonal, otherwise the occlusion gets interpolated unevenly.
+        let [ao00, ao10, ao01, ao11] = ambient_occlusion.map(u32::from);
+        let flip_diagonal = ao00 + ao11 < ao10 + ao01;
+        let new_indices: [Index; 6] = match (face_dir.reverse_direction(), flip_diagonal) {
+            (true, false) => [0, 1, 3, 0, 3, 2],
+            (false, false) => [0, 3, 1, 0, 2, 3],
+            (true, true) => [0, 1, 2, 1, 3, 2],
+            (false, true) => [0, 2, 1, 1, 2, 3],
         };
 
-        indices.extend(new_indices.into_iter().map(|i| i + vertices.len() as Index));
+        let first_vertex =
+            Index::try_from(vertices.len()).expect("too many vertices for the index type");
+        indices.extend(new_indices.into_iter().map(|i| i + first_vertex));
         vertices.extend(new_vertices);
     }
 }
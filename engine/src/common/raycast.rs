@@ -0,0 +1,273 @@
+use nalgebra::{Point3, Vector3};
+
+use super::{chunk, face_dir::FaceDir, VoxelHandle};
+
+/// The result of a ray hitting a voxel.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct RaycastHit {
+    /// The world position of the hit voxel.
+    pub position: Vector3<i32>,
+    /// The hit voxel.
+    pub voxel: VoxelHandle,
+    /// The face of the voxel the ray entered through.
+    pub face: FaceDir,
+    /// The distance from the ray origin to the hit point.
+    pub distance: f32,
+}
+
+impl RaycastHit {
+    /// Returns the position of the voxel next to the hit face, where a new voxel would be placed.
+    pub fn get_adjacent_position(&self) -> Vector3<i32> {
+        self.position + chunk::neighbour_offset(self.face)
+    }
+}
+
+/// A ray that is cast through the voxel grid.
+///
+/// The ray walks the grid voxel by voxel (DDA), so it never skips thin voxels and works across
+/// chunk boundaries as long as the sampler can see the voxels.
+pub struct Raycast<'a> {
+    origin: Point3<f32>,
+    direction: Vector3<f32>,
+    max_distance: f32,
+    filter: Option<Box<dyn Fn(VoxelHandle) -> bool + 'a>>,
+}
+
+impl<'a> Raycast<'a> {
+    /// Creates a new ray.
+    ///
+    /// ## Arguments
+    /// * `origin` - The world position the ray starts from.
+    /// * `direction` - The direction of the ray, it does not need to be normalized.
+    /// * `max_distance` - How far the ray can travel before giving up.
+    pub fn new(origin: Point3<f32>, direction: Vector3<f32>, max_distance: f32) -> Self {
+        Self {
+            origin,
+            direction,
+            max_distance,
+            filter: None,
+        }
+    }
+
+    /// Only voxels for which the filter returns true can be hit, the others are passed through.
+    pub fn with_filter(mut self, filter: impl Fn(VoxelHandle) -> bool + 'a) -> Self {
+        self.filter = Some(Box::new(filter));
+        self
+    }
+
+    /// Casts the ray.
+    ///
+    /// The voxel containing the origin is never hit, so a ray starting inside a voxel can get out of it.
+    ///
+    /// ## Arguments
+    /// * `sample` - Returns the voxel at a world position, the outer option is `None` if the
+    ///   position is not loaded. The ray stops at unloaded positions.
+    ///
+    /// ## Returns
+    /// The first voxel that was hit, or `None` if nothing was hit within the max distance.
+    pub fn cast(
+        &self,
+        mut sample: impl FnMut(Vector3<i32>) -> Option<Option<VoxelHandle>>,
+    ) -> Option<RaycastHit> {
+        let direction = self.direction.try_normalize(f32::EPSILON)?;
+
+        let mut position = self.origin.coords.map(|c| c.floor() as i32);
+        let mut step = Vector3::zeros();
+        // The distance along the ray to the next voxel boundary on each axis.
+        let mut next_boundary = Vector3::repeat(f32::INFINITY);
+        // The distance along the ray between two voxel boundaries on each axis.
+        let mut boundary_delta = Vector3::repeat(f32::INFINITY);
+        for axis in 0..3 {
+            if direction[axis] > 0.0 {
+                step[axis] = 1;
+                boundary_delta[axis] = 1.0 / direction[axis];
+                next_boundary[axis] =
+                    (position[axis] as f32 + 1.0 - self.origin[axis]) * boundary_delta[axis];
+            } else if direction[axis] < 0.0 {
+                step[axis] = -1;
+                boundary_delta[axis] = -1.0 / direction[axis];
+                next_boundary[axis] =
+                    (self.origin[axis] - position[axis] as f32) * boundary_delta[axis];
+            }
+        }
+
+        loop {
+            let axis = next_boundary.imin();
+            let distance = next_boundary[axis];
+            if distance > self.max_distance {
+                return None;
+            }
+
+            position[axis] += step[axis];
+            next_boundary[axis] += boundary_delta[axis];
+
+            if let Some(voxel) = sample(position)? {
+                if self.filter.as_ref().is_none_or(|filter| filter(voxel)) {
+                    return Some(RaycastHit {
+                        position,
+                        voxel,
+                        face: entered_face(axis, step[axis]),
+                        distance,
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// Returns the face a ray enters a voxel through when it steps along an axis.
+fn entered_face(axis: usize, step: i32) -> FaceDir {
+    match (axis, step > 0) {
+        (0, true) => FaceDir::Left,
+        (0, false) => FaceDir::Right,
+        (1, true) => FaceDir::Down,
+        (1, false) => FaceDir::Up,
+        (_, true) => FaceDir::Forward,
+        (_, false) => FaceDir::Back,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use nalgebra::{point, vector};
+
+    use super::*;
+    use crate::ecs::components::Chunk;
+
+    const STONE: VoxelHandle = VoxelHandle { id: 1 };
+    const WATER: VoxelHandle = VoxelHandle { id: 2 };
+
+    /// Samples the voxels of the chunks, positions outside of them are not loaded.
+    fn sample(chunks: &[Chunk], position: Vector3<i32>) -> Option<Option<VoxelHandle>> {
+        let index = chunk::world_to_chunk_index(position);
+        chunks
+            .iter()
+            .find(|chunk| chunk.get_index() == index)
+            .map(|chunk| chunk.sample(chunk::world_to_local(position)))
+    }
+
+    fn set_voxel(chunks: &mut [Chunk], position: Vector3<i32>, voxel: VoxelHandle) {
+        let index = chunk::world_to_chunk_index(position);
+        let chunk = chunks
+            .iter_mut()
+            .find(|chunk| chunk.get_index() == index)
+            .expect("position is not loaded");
+        *chunk.sample_mut(chunk::world_to_local(position)) = Some(voxel);
+    }
+
+    #[test]
+    fn hits_the_face_facing_the_ray_on_every_axis() {
+        let center = vector![32, 32, 32];
+        for axis in 0..6 {
+            let face_dir = FaceDir::from_axis(axis);
+            let offset = chunk::neighbour_offset(face_dir);
+            let mut chunks = [Chunk::new(vector![0, 0, 0])];
+            set_voxel(&mut chunks, center + offset * 3, STONE);
+
+            let origin = Point3::from(center.map(|c| c as f32 + 0.5));
+            let hit = Raycast::new(origin, offset.map(|c| c as f32), 10.0)
+                .cast(|position| sample(&chunks, position))
+                .unwrap_or_else(|| panic!("nothing hit towards {face_dir:?}"));
+
+            assert_eq!(hit.position, center + offset * 3);
+            assert_eq!(hit.voxel, STONE);
+            assert_eq!(chunk::neighbour_offset(hit.face), -offset);
+            assert_eq!(hit.get_adjacent_position(), center + offset * 2);
+            assert!((hit.distance - 2.5).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn crosses_chunk_borders() {
+        let mut chunks = [Chunk::new(vector![0, 0, 0]), Chunk::new(vector![1, 0, 0])];
+        for y in 0..chunk::CHUNK_LENGTHI32 {
+            for z in 0..chunk::CHUNK_LENGTHI32 {
+                set_voxel(&mut chunks, vector![66, y, z], STONE);
+            }
+        }
+
+        let origin = point![62.2, 10.3, 10.4];
+        let direction = vector![1.0, 0.7, 0.3];
+        let hit = Raycast::new(origin, direction, 20.0)
+            .cast(|position| sample(&chunks, position))
+            .expect("nothing hit");
+
+        assert_eq!(hit.position.x, 66);
+        assert_eq!(chunk::world_to_chunk_index(hit.position), vector![1, 0, 0]);
+        assert_eq!(hit.face, FaceDir::Left);
+        let expected_distance = (66.0 - origin.x) / direction.normalize().x;
+        assert!((hit.distance - expected_distance).abs() < 1e-4);
+        let hit_point = origin + direction.normalize() * hit.distance;
+        assert_eq!(
+            hit_point.coords.map(|c| c.floor() as i32).yz(),
+            hit.position.yz()
+        );
+    }
+
+    #[test]
+    fn never_hits_the_origin_voxel() {
+        let mut chunks = [Chunk::new(vector![0, 0, 0])];
+        set_voxel(&mut chunks, vector![10, 10, 10], STONE);
+
+        let ray = Raycast::new(point![10.5, 10.5, 10.5], vector![1.0, 0.0, 0.0], 5.0);
+        assert_eq!(ray.cast(|position| sample(&chunks, position)), None);
+
+        set_voxel(&mut chunks, vector![11, 10, 10], STONE);
+        let hit = ray
+            .cast(|position| sample(&chunks, position))
+            .expect("nothing hit");
+        assert_eq!(hit.position, vector![11, 10, 10]);
+    }
+
+    #[test]
+    fn stops_at_unloaded_positions() {
+        let chunks = [Chunk::new(vector![0, 0, 0])];
+        let farthest = Cell::new(0);
+
+        let hit = Raycast::new(point![60.5, 10.5, 10.5], vector![1.0, 0.0, 0.0], 100.0).cast(
+            |position| {
+                farthest.set(farthest.get().max(position.x));
+                sample(&chunks, position)
+            },
+        );
+
+        assert_eq!(hit, None);
+        assert_eq!(farthest.get(), chunk::CHUNK_LENGTHI32);
+    }
+
+    #[test]
+    fn gives_up_after_the_max_distance() {
+        let mut chunks = [Chunk::new(vector![0, 0, 0])];
+        set_voxel(&mut chunks, vector![15, 10, 10], STONE);
+        let origin = point![10.5, 10.5, 10.5];
+        let direction = vector![1.0, 0.0, 0.0];
+
+        let cast = |max_distance| {
+            Raycast::new(origin, direction, max_distance).cast(|position| sample(&chunks, position))
+        };
+        assert_eq!(cast(4.0), None);
+        assert_eq!(cast(5.0).map(|hit| hit.position), Some(vector![15, 10, 10]));
+    }
+
+    #[test]
+    fn passes_through_filtered_voxels() {
+        let mut chunks = [Chunk::new(vector![0, 0, 0])];
+        set_voxel(&mut chunks, vector![12, 10, 10], WATER);
+        set_voxel(&mut chunks, vector![14, 10, 10], STONE);
+        let origin = point![10.5, 10.5, 10.5];
+        let direction = vector![1.0, 0.0, 0.0];
+
+        let hit = Raycast::new(origin, direction, 10.0)
+            .cast(|position| sample(&chunks, position))
+            .expect("nothing hit");
+        assert_eq!((hit.position, hit.voxel), (vector![12, 10, 10], WATER));
+
+        let hit = Raycast::new(origin, direction, 10.0)
+            .with_filter(|voxel| voxel != WATER)
+            .cast(|position| sample(&chunks, position))
+            .expect("nothing hit");
+        assert_eq!((hit.position, hit.voxel), (vector![14, 10, 10], STONE));
+    }
+}
@@ -0,0 +1,821 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, File},
+    io::{self, Read, Seek, SeekFrom, Write},
+    mem,
+    path::{Path, PathBuf},
+    sync::{Mutex, RwLock},
+};
+
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+use nalgebra::Vector3;
+use thiserror::Error;
+
+use crate::ecs::components::Chunk;
+
+use super::{
+    chunk::CHUNK_VOLUME,
+    nibble_storage::{NibbleStorage, MAX_NIBBLE},
+    VoxelHandle,
+};
+
+/// The amount of chunks along each side of a region.
+pub const REGION_LENGTH: i32 = 16;
+/// The amount of chunks in a single region.
+pub const REGION_VOLUME: usize = (REGION_LENGTH * REGION_LENGTH * REGION_LENGTH) as usize;
+/// The magic bytes at the start of every region file.
+const REGION_MAGIC: [u8; 4] = *b"VXRG";
+/// The version of the region file format.
+const REGION_VERSION: u32 = 1;
+/// The size of the magic bytes and the version in bytes.
+const HEADER_SIZE: u64 = 8;
+/// The size of a single offset table entry in bytes.
+const TABLE_ENTRY_SIZE: u64 = 8;
+/// The size of the header and the offset table in bytes.
+const TABLE_END: u64 = HEADER_SIZE + REGION_VOLUME as u64 * TABLE_ENTRY_SIZE;
+
+/// Describes how reading or writing a region failed.
+#[derive(Error, Debug)]
+pub enum RegionError {
+    #[error("Region file {0:?} is not a region file.")]
+    InvalidMagic(PathBuf),
+    #[error("Region file {0:?} has unsupported version {1}.")]
+    UnsupportedVersion(PathBuf, u32),
+    #[error("Chunk data is corrupted: {0}")]
+    CorruptChunk(&'static str),
+    #[error(transparent)]
+    IoError(#[from] io::Error),
+}
+
+/// Translates between the voxel ids stored in the region files of a world and the ids of the registered voxels.
+///
+/// The ids of the registered voxels can change when voxels are added or removed, the region files keep
+/// using the ids the world was saved with. Ids without a mapping are the same on both sides.
+/// Stored ids of voxels that are not registered anymore are loaded as air.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct VoxelIdMap {
+    to_current: HashMap<u32, u32>,
+    to_stored: HashMap<u32, u32>,
+    removed: HashSet<u32>,
+}
+
+impl VoxelIdMap {
+    /// Creates a map from pairs of a stored id and the id of the registered voxel.
+    pub fn new(pairs: impl IntoIterator<Item = (u32, u32)>) -> Self {
+        let mut map = Self::default();
+        for (stored, current) in pairs {
+            if stored != current {
+                map.to_current.insert(stored, current);
+                map.to_stored.insert(current, stored);
+            }
+        }
+        map
+    }
+
+    /// Marks stored ids whose voxels are not registered anymore.
+    pub fn with_removed(mut self, removed: impl IntoIterator<Item = u32>) -> Self {
+        self.removed.extend(removed);
+        self
+    }
+
+    /// Returns the id of the registered voxel for an id stored in a region file.
+    ///
+    /// ## Returns
+    /// `None` if the stored voxel is not registered anymore.
+    pub fn to_current(&self, stored: u32) -> Option<u32> {
+        if self.removed.contains(&stored) {
+            return None;
+        }
+        Some(self.to_current.get(&stored).copied().unwrap_or(stored))
+    }
+
+    /// Returns the id that is stored in a region file for the id of a registered voxel.
+    pub fn to_stored(&self, current: u32) -> u32 {
+        self.to_stored.get(&current).copied().unwrap_or(current)
+    }
+}
+
+/// Returns the index of the region that contains the chunk with the specified index.
+pub fn chunk_to_region_index(chunk_index: Vector3<i32>) -> Vector3<i32> {
+    chunk_index.map(|c| c.div_euclid(REGION_LENGTH))
+}
+
+/// Returns the index of the chunk in the offset table of its region.
+fn chunk_to_table_index(chunk_index: Vector3<i32>) -> usize {
+    let local = chunk_index.map(|c| c.rem_euclid(REGION_LENGTH) as usize);
+    let length = REGION_LENGTH as usize;
+    local.x + local.y * length + local.z * length * length
+}
+
+/// Encodes the voxels and the fluid levels of a chunk.
+///
+/// The voxels are run-length encoded as pairs of a run length and a voxel id plus one,
+/// with zero meaning no voxel. If any fluid level is not zero, the fluid levels follow
+/// as pairs of a run length and a level. Everything is then zlib compressed.
+///
+/// The voxel ids are translated to the stored ids with `voxel_ids`.
+pub fn encode_chunk(chunk: &Chunk, voxel_ids: &VoxelIdMap) -> Vec<u8> {
+    let voxels = chunk.get_voxels();
+    let mut runs = vec![];
+    encode_runs(&mut runs, voxels.len(), |index| {
+        voxels
+            .get(index)
+            .map_or(0, |voxel| voxel_ids.to_stored(voxel.id) + 1)
+    });
+
+    let fluid_levels = chunk.get_fluid_levels();
+    if fluid_levels.get_uniform_value() != Some(0) {
+        encode_runs(&mut runs, fluid_levels.len(), |index| {
+            fluid_levels.get(index) as u32
+        });
+    }
+
+    let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+    // Writing into a `Vec` can not fail.
+    encoder
+        .write_all(&runs)
+        .and_then(|_| encoder.finish())
+        .expect("failed to compress chunk data")
+}
+
+/// Appends `len` values as pairs of a run length and a value.
+fn encode_runs(runs: &mut Vec<u8>, len: usize, value_at: impl Fn(usize) -> u32) {
+    let mut index = 0;
+    while index < len {
+        let value = value_at(index);
+        let mut length = 1;
+        while index + length < len && value_at(index + length) == value {
+            length += 1;
+        }
+        runs.extend_from_slice(&(length as u32).to_le_bytes());
+        runs.extend_from_slice(&value.to_le_bytes());
+        index += length;
+    }
+}
+
+/// Decodes chunk data that was encoded with `encode_chunk`.
+///
+/// Chunks without fluid levels, including the ones that were saved before fluids existed, only contain sources.
+/// The stored voxel ids are translated to the ids of the registered voxels with `voxel_ids`.
+pub fn decode_chunk(
+    index: Vector3<i32>,
+    data: &[u8],
+    voxel_ids: &VoxelIdMap,
+) -> Result<Chunk, RegionError> {
+    let mut runs = vec![];
+    ZlibDecoder::new(data).read_to_end(&mut runs)?;
+    if runs.len() % 8 != 0 {
+        return Err(RegionError::CorruptChunk("truncated run"));
+    }
+
+    let mut voxels = Vec::with_capacity(CHUNK_VOLUME);
+    let mut fluid_levels = NibbleStorage::new(CHUNK_VOLUME);
+    let mut fluid_level_count = 0;
+    for run in runs.chunks_exact(8) {
+        let length = u32::from_le_bytes([run[0], run[1], run[2], run[3]]) as usize;
+        let value = u32::from_le_bytes([run[4], run[5], run[6], run[7]]);
+        if voxels.len() < CHUNK_VOLUME {
+            if voxels.len() + length > CHUNK_VOLUME {
+                return Err(RegionError::CorruptChunk("too many voxels"));
+            }
+            let voxel = value
+                .checked_sub(1)
+                .and_then(|id| voxel_ids.to_current(id))
+                .map(|id| VoxelHandle { id });
+            voxels.extend(std::iter::repeat_n(voxel, length));
+        } else {
+            if fluid_level_count + length > CHUNK_VOLUME || value > MAX_NIBBLE as u32 {
+                return Err(RegionError::CorruptChunk("invalid fluid levels"));
+            }
+            for index in fluid_level_count..fluid_level_count + length {
+                fluid_levels.set(index, value as u8);
+            }
+            fluid_level_count += length;
+        }
+    }
+    if voxels.len() != CHUNK_VOLUME {
+        return Err(RegionError::CorruptChunk("too few voxels"));
+    }
+    if fluid_level_count != 0 && fluid_level_count != CHUNK_VOLUME {
+        return Err(RegionError::CorruptChunk("too few fluid levels"));
+    }
+
+    let mut chunk = Chunk::new(index);
+    chunk.set_voxels(&voxels);
+    chunk.set_fluid_levels(fluid_levels);
+    chunk.mark_saved();
+    Ok(chunk)
+}
+
+/// A single region file, which stores the encoded data of up to `REGION_VOLUME` chunks.
+///
+/// The file starts with the magic bytes and the version, followed by an offset table with an
+/// offset and a length for every chunk of the region. An offset of zero means that the chunk
+/// is not stored. The chunk data follows the table, data that the table does not point at
+/// anymore stays unused until the file gets compacted.
+pub struct RegionFile {
+    path: PathBuf,
+}
+
+impl RegionFile {
+    /// Creates a handle to the region file at the specified path, the file does not need to exist.
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_owned(),
+        }
+    }
+
+    /// Reads the encoded data of a single chunk.
+    ///
+    /// ## Returns
+    /// `None` if the file or the chunk does not exist.
+    pub fn read_chunk(&self, chunk_index: Vector3<i32>) -> Result<Option<Vec<u8>>, RegionError> {
+        let mut file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        self.read_header(&mut file)?;
+
+        let table_index = chunk_to_table_index(chunk_index) as u64;
+        file.seek(SeekFrom::Start(
+            HEADER_SIZE + table_index * TABLE_ENTRY_SIZE,
+        ))?;
+        let (offset, length) = read_table_entry(&mut file)?;
+        if offset == 0 {
+            return Ok(None);
+        }
+
+        let mut data = vec![0; length as usize];
+        file.seek(SeekFrom::Start(offset as u64))?;
+        file.read_exact(&mut data)?;
+        Ok(Some(data))
+    }
+
+    /// Writes the encoded data of multiple chunks of this region.
+    ///
+    /// Only the changed chunks are written, they are appended to the end of the file and then
+    /// their offset table entries are updated, so a failed write does not corrupt the chunks
+    /// that are already stored. The space of the replaced chunk data is reclaimed by compacting
+    /// the file once it takes up more than half of the file.
+    pub fn write_chunks(&self, chunks: &[(Vector3<i32>, Vec<u8>)]) -> Result<(), RegionError> {
+        let mut file = File::options()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&self.path)?;
+        let mut table = if file.metadata()?.len() == 0 {
+            file.write_all(&REGION_MAGIC)?;
+            file.write_all(&REGION_VERSION.to_le_bytes())?;
+            file.write_all(&[0; (TABLE_END - HEADER_SIZE) as usize])?;
+            vec![(0, 0); REGION_VOLUME]
+        } else {
+            let mut reader = io::BufReader::new(&mut file);
+            self.read_header(&mut reader)?;
+            read_table(&mut reader)?
+        };
+
+        let mut end = file.seek(SeekFrom::End(0))?;
+        let mut changed = Vec::with_capacity(chunks.len());
+        {
+            let mut writer = io::BufWriter::new(&mut file);
+            for (chunk_index, data) in chunks {
+                let offset =
+                    u32::try_from(end).map_err(|_| io::Error::other("region file is too large"))?;
+                writer.write_all(data)?;
+                end += data.len() as u64;
+
+                let table_index = chunk_to_table_index(*chunk_index);
+                table[table_index] = (offset, data.len() as u32);
+                changed.push(table_index);
+            }
+            writer.flush()?;
+        }
+        // The data has to be on disk before the table points at it.
+        file.sync_data()?;
+
+        for table_index in changed {
+            let (offset, length) = table[table_index];
+            file.seek(SeekFrom::Start(
+                HEADER_SIZE + table_index as u64 * TABLE_ENTRY_SIZE,
+            ))?;
+            file.write_all(&offset.to_le_bytes())?;
+            file.write_all(&length.to_le_bytes())?;
+        }
+        file.sync_data()?;
+
+        let used = table.iter().map(|(_, length)| *length as u64).sum::<u64>();
+        if end - TABLE_END > used * 2 {
+            self.compact()?;
+        }
+        Ok(())
+    }
+
+    /// Rewrites the file with only the chunk data that is still in use.
+    ///
+    /// The file is rewritten next to the old one and then moved over it, so a failed write does not corrupt the region.
+    fn compact(&self) -> Result<(), RegionError> {
+        let entries = self.read_all()?;
+
+        let mut table = Vec::with_capacity((TABLE_END - HEADER_SIZE) as usize);
+        let mut offset = TABLE_END;
+        for entry in entries.iter() {
+            let (entry_offset, length) = match entry {
+                Some(data) => (offset as u32, data.len() as u32),
+                None => (0, 0),
+            };
+            table.extend_from_slice(&entry_offset.to_le_bytes());
+            table.extend_from_slice(&length.to_le_bytes());
+            offset += length as u64;
+        }
+
+        let temp_path = self.path.with_extension("tmp");
+        {
+            let mut file = io::BufWriter::new(File::create(&temp_path)?);
+            file.write_all(&REGION_MAGIC)?;
+            file.write_all(&REGION_VERSION.to_le_bytes())?;
+            file.write_all(&table)?;
+            for data in entries.iter().flatten() {
+                file.write_all(data)?;
+            }
+            file.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        }
+        fs::rename(temp_path, &self.path)?;
+        Ok(())
+    }
+
+    /// Reads the encoded data of every chunk in the file, indexed by their offset table index.
+    fn read_all(&self) -> Result<Vec<Option<Vec<u8>>>, RegionError> {
+        let mut entries = vec![None; REGION_VOLUME];
+        let mut file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(entries),
+            Err(e) => return Err(e.into()),
+        };
+        let mut content = vec![];
+        file.read_to_end(&mut content)?;
+        let mut reader = io::Cursor::new(&content);
+        self.read_header(&mut reader)?;
+
+        for (entry, (offset, length)) in entries.iter_mut().zip(read_table(&mut reader)?) {
+            if offset == 0 {
+                continue;
+            }
+            let data = content
+                .get(offset as usize..offset as usize + length as usize)
+                .ok_or(RegionError::CorruptChunk("chunk data out of bounds"))?;
+            *entry = Some(data.to_vec());
+        }
+        Ok(entries)
+    }
+
+    /// Reads and validates the magic bytes and the version.
+    fn read_header(&self, reader: &mut impl Read) -> Result<(), RegionError> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if magic != REGION_MAGIC {
+            return Err(RegionError::InvalidMagic(self.path.clone()));
+        }
+        let mut version = [0; 4];
+        reader.read_exact(&mut version)?;
+        let version = u32::from_le_bytes(version);
+        if version != REGION_VERSION {
+            return Err(RegionError::UnsupportedVersion(self.path.clone(), version));
+        }
+        Ok(())
+    }
+}
+
+/// Reads the whole offset table.
+fn read_table(reader: &mut impl Read) -> io::Result<Vec<(u32, u32)>> {
+    (0..REGION_VOLUME)
+        .map(|_| read_table_entry(reader))
+        .collect()
+}
+
+/// Reads a single offset table entry.
+fn read_table_entry(reader: &mut impl Read) -> io::Result<(u32, u32)> {
+    let mut entry = [0; TABLE_ENTRY_SIZE as usize];
+    reader.read_exact(&mut entry)?;
+    Ok((
+        u32::from_le_bytes([entry[0], entry[1], entry[2], entry[3]]),
+        u32::from_le_bytes([entry[4], entry[5], entry[6], entry[7]]),
+    ))
+}
+
+/// Thread safe access to all the region files of a world.
+///
+/// Changed chunks are kept in memory in their encoded form until `flush` writes them to their
+/// region files, loading a chunk that has not been flushed yet decodes the in-memory version.
+pub struct RegionStorage {
+    regions_dir: PathBuf,
+    /// Translates between the stored voxel ids and the ids of the registered voxels.
+    voxel_ids: VoxelIdMap,
+    /// The encoded data of the changed chunks that have not been written yet.
+    unsaved: Mutex<HashMap<Vector3<i32>, Vec<u8>>>,
+    /// Keeps chunks from being read while their region file is being replaced.
+    files: RwLock<()>,
+}
+
+impl RegionStorage {
+    /// Creates the storage for the region files in the specified directory, the directory is created if needed.
+    ///
+    /// ## Arguments
+    /// * `regions_dir` - The directory of the region files.
+    /// * `voxel_ids` - Translates between the stored voxel ids and the ids of the registered voxels.
+    pub fn new<P: AsRef<Path>>(regions_dir: P, voxel_ids: VoxelIdMap) -> io::Result<Self> {
+        fs::create_dir_all(&regions_dir)?;
+        Ok(Self {
+            regions_dir: regions_dir.as_ref().to_owned(),
+            voxel_ids,
+            unsaved: Mutex::new(HashMap::new()),
+            files: RwLock::new(()),
+        })
+    }
+
+    /// Returns the path of the region file with the specified region index.
+    pub fn get_region_path(&self, region_index: Vector3<i32>) -> PathBuf {
+        self.regions_dir.join(format!(
+            "r.{}.{}.{}.region",
+            region_index.x, region_index.y, region_index.z
+        ))
+    }
+
+    /// Queues a changed chunk to be written on the next flush.
+    ///
+    /// The chunk is encoded right away, it replaces an older version that has not been written yet.
+    pub fn queue_save(&self, chunk: &Chunk) {
+        let data = encode_chunk(chunk, &self.voxel_ids);
+        let mut unsaved = self.unsaved.lock().unwrap_or_else(|e| e.into_inner());
+        unsaved.insert(chunk.get_index(), data);
+    }
+
+    /// Returns the amount of chunks waiting to be written.
+    pub fn get_unsaved_count(&self) -> usize {
+        self.unsaved.lock().unwrap_or_else(|e| e.into_inner()).len()
+    }
+
+    /// Loads the chunk with the specified index.
+    ///
+    /// ## Returns
+    /// `None` if the chunk has never been saved.
+    pub fn load_chunk(&self, chunk_index: Vector3<i32>) -> Result<Option<Chunk>, RegionError> {
+        let unsaved = self
+            .unsaved
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&chunk_index)
+            .cloned();
+        if let Some(data) = unsaved {
+            return decode_chunk(chunk_index, &data, &self.voxel_ids).map(Some);
+        }
+
+        let _files = self.files.read().unwrap_or_else(|e| e.into_inner());
+        let region = RegionFile::new(self.get_region_path(chunk_to_region_index(chunk_index)));
+        match region.read_chunk(chunk_index)? {
+            Some(data) => decode_chunk(chunk_index, &data, &self.voxel_ids).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Writes all the queued chunks to their region files.
+    ///
+    /// ## Returns
+    /// The amount of written chunks.
+    pub fn flush(&self) -> Result<usize, RegionError> {
+        // The file lock is taken first, so chunks taken out of `unsaved` can not be read from
+        // their old region files before they are written.
+        let _files = self.files.write().unwrap_or_else(|e| e.into_inner());
+        let chunks = mem::take(&mut *self.unsaved.lock().unwrap_or_else(|e| e.into_inner()));
+        let count = chunks.len();
+
+        let mut regions = HashMap::<Vector3<i32>, Vec<(Vector3<i32>, Vec<u8>)>>::new();
+        for (chunk_index, data) in chunks {
+            regions
+                .entry(chunk_to_region_index(chunk_index))
+                .or_default()
+                .push((chunk_index, data));
+        }
+
+        let mut result = Ok(count);
+        for (region_index, chunks) in regions {
+            let region = RegionFile::new(self.get_region_path(region_index));
+            if let Err(e) = region.write_chunks(&chunks) {
+                // Keep the chunks around for the next flush, unless they changed again in the meantime.
+                let mut unsaved = self.unsaved.lock().unwrap_or_else(|e| e.into_inner());
+                for (chunk_index, data) in chunks {
+                    unsaved.entry(chunk_index).or_insert(data);
+                }
+                result = Err(e);
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::vector;
+
+    use super::*;
+    use crate::common::chunk::CHUNK_LENGTH;
+
+    const STONE: VoxelHandle = VoxelHandle { id: 1 };
+    const DIRT: VoxelHandle = VoxelHandle { id: 2 };
+    const WATER: VoxelHandle = VoxelHandle { id: 3 };
+
+    /// Creates an empty directory for a test, the test removes it when it is done.
+    fn create_test_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("voxel-engine-region-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).expect("failed to create the test directory");
+        dir
+    }
+
+    fn create_uniform_chunk(index: Vector3<i32>) -> Chunk {
+        let mut chunk = Chunk::new(index);
+        chunk.fill(Some(STONE));
+        chunk
+    }
+
+    /// Creates a chunk with runs that cross rows and layers, single voxels and fluid levels.
+    fn create_mixed_chunk(index: Vector3<i32>, seed: u32) -> Chunk {
+        let layer = CHUNK_LENGTH * CHUNK_LENGTH;
+        let mut voxels = vec![None; CHUNK_VOLUME];
+        // A run from the end of one layer into the next one.
+        voxels[layer - 100..layer + 100].fill(Some(STONE));
+        // A run that covers several whole layers.
+        voxels[layer * 10..layer * 13 + 7].fill(Some(DIRT));
+        // Scattered voxels that all end up in runs of one.
+        for index in (layer * 20..layer * 21).step_by(3 + seed as usize) {
+            voxels[index] = Some(VoxelHandle {
+                id: index as u32 % 5 + 1,
+            });
+        }
+
+        let mut chunk = Chunk::new(index);
+        chunk.set_voxels(&voxels);
+        chunk.set_fluid((5, 40, 5), Some(WATER), 3);
+        chunk.set_fluid((6, 40, 5), Some(WATER), 8);
+        chunk
+    }
+
+    fn assert_same_chunk(a: &Chunk, b: &Chunk) {
+        assert_eq!(a.get_index(), b.get_index());
+        for index in 0..CHUNK_VOLUME {
+            assert_eq!(
+                a.get_voxels().get(index),
+                b.get_voxels().get(index),
+                "voxel {index}"
+            );
+            assert_eq!(
+                a.get_fluid_levels().get(index),
+                b.get_fluid_levels().get(index),
+                "fluid level {index}"
+            );
+        }
+    }
+
+    fn read_chunk(region: &RegionFile, index: Vector3<i32>) -> Chunk {
+        let data = region
+            .read_chunk(index)
+            .expect("failed to read the chunk")
+            .expect("the chunk is not stored");
+        decode_chunk(index, &data, &VoxelIdMap::default()).expect("failed to decode the chunk")
+    }
+
+    /// Reads the offset table entry of a chunk straight from the file.
+    fn read_entry(path: &Path, index: Vector3<i32>) -> (u32, u32) {
+        let mut file = File::open(path).expect("failed to open the region file");
+        file.seek(SeekFrom::Start(
+            HEADER_SIZE + chunk_to_table_index(index) as u64 * TABLE_ENTRY_SIZE,
+        ))
+        .unwrap();
+        read_table_entry(&mut file).unwrap()
+    }
+
+    #[test]
+    fn uniform_chunk_round_trips() {
+        let chunk = create_uniform_chunk(vector![1, -2, 3]);
+        let data = encode_chunk(&chunk, &VoxelIdMap::default());
+        // A single run compresses to almost nothing.
+        assert!(data.len() < 64, "{} bytes", data.len());
+
+        let decoded = decode_chunk(chunk.get_index(), &data, &VoxelIdMap::default()).unwrap();
+        assert_same_chunk(&chunk, &decoded);
+        assert!(!decoded.is_unsaved());
+    }
+
+    #[test]
+    fn mixed_chunk_round_trips() {
+        let chunk = create_mixed_chunk(vector![0, 0, 0], 0);
+        let data = encode_chunk(&chunk, &VoxelIdMap::default());
+        let decoded = decode_chunk(chunk.get_index(), &data, &VoxelIdMap::default()).unwrap();
+
+        assert_same_chunk(&chunk, &decoded);
+        assert_eq!(decoded.get_fluid_level((6, 40, 5)), 8);
+    }
+
+    #[test]
+    fn voxel_ids_are_translated() {
+        let chunk = create_mixed_chunk(vector![0, 0, 0], 0);
+        // Stone is stored as 10 and dirt as 1.
+        let voxel_ids = VoxelIdMap::new([(10, STONE.id), (1, DIRT.id)]);
+        let data = encode_chunk(&chunk, &voxel_ids);
+
+        let stored = decode_chunk(chunk.get_index(), &data, &VoxelIdMap::default()).unwrap();
+        let layer = CHUNK_LENGTH * CHUNK_LENGTH;
+        assert_eq!(stored.get_voxels().get(layer), Some(VoxelHandle { id: 10 }));
+        assert_eq!(
+            stored.get_voxels().get(layer * 10),
+            Some(VoxelHandle { id: 1 })
+        );
+
+        let decoded = decode_chunk(chunk.get_index(), &data, &voxel_ids).unwrap();
+        assert_same_chunk(&chunk, &decoded);
+    }
+
+    #[test]
+    fn removed_voxels_are_loaded_as_air() {
+        let chunk = create_mixed_chunk(vector![0, 0, 0], 0);
+        let data = encode_chunk(&chunk, &VoxelIdMap::default());
+
+        let voxel_ids = VoxelIdMap::default().with_removed([DIRT.id]);
+        let decoded = decode_chunk(chunk.get_index(), &data, &voxel_ids).unwrap();
+        for index in 0..CHUNK_VOLUME {
+            let expected = chunk.get_voxels().get(index).filter(|voxel| *voxel != DIRT);
+            assert_eq!(decoded.get_voxels().get(index), expected, "voxel {index}");
+        }
+        assert_eq!(decoded.get_fluid_level((6, 40, 5)), 8);
+    }
+
+    #[test]
+    fn corrupt_data_is_rejected() {
+        let chunk = create_mixed_chunk(vector![0, 0, 0], 0);
+        let data = encode_chunk(&chunk, &VoxelIdMap::default());
+
+        assert!(decode_chunk(
+            chunk.get_index(),
+            &data[..data.len() / 2],
+            &VoxelIdMap::default()
+        )
+        .is_err());
+        assert!(decode_chunk(chunk.get_index(), b"not a chunk", &VoxelIdMap::default()).is_err());
+    }
+
+    #[test]
+    fn chunks_are_read_back_after_reopening() {
+        let dir = create_test_dir("reopen");
+        let path = dir.join("region");
+        let chunks = [
+            create_uniform_chunk(vector![0, 0, 0]),
+            create_mixed_chunk(vector![1, 0, 0], 0),
+            create_mixed_chunk(vector![15, 15, 15], 1),
+        ];
+        let encoded = chunks
+            .iter()
+            .map(|chunk| {
+                (
+                    chunk.get_index(),
+                    encode_chunk(chunk, &VoxelIdMap::default()),
+                )
+            })
+            .collect::<Vec<_>>();
+        RegionFile::new(&path).write_chunks(&encoded).unwrap();
+
+        let region = RegionFile::new(&path);
+        for chunk in &chunks {
+            assert_same_chunk(chunk, &read_chunk(&region, chunk.get_index()));
+        }
+        assert_eq!(region.read_chunk(vector![2, 0, 0]).unwrap(), None);
+        assert_eq!(
+            RegionFile::new(dir.join("missing"))
+                .read_chunk(vector![0, 0, 0])
+                .unwrap(),
+            None
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn larger_chunk_is_appended() {
+        let dir = create_test_dir("append");
+        let path = dir.join("region");
+        let region = RegionFile::new(&path);
+        let a = create_uniform_chunk(vector![0, 0, 0]);
+        let b = create_uniform_chunk(vector![0, 1, 0]);
+        region
+            .write_chunks(&[
+                (a.get_index(), encode_chunk(&a, &VoxelIdMap::default())),
+                (b.get_index(), encode_chunk(&b, &VoxelIdMap::default())),
+            ])
+            .unwrap();
+        let old_len = fs::metadata(&path).unwrap().len();
+        let b_entry = read_entry(&path, b.get_index());
+
+        let larger = create_mixed_chunk(a.get_index(), 0);
+        let data = encode_chunk(&larger, &VoxelIdMap::default());
+        assert!(data.len() > read_entry(&path, a.get_index()).1 as usize);
+        region
+            .write_chunks(&[(a.get_index(), data.clone())])
+            .unwrap();
+
+        assert_eq!(
+            read_entry(&path, a.get_index()),
+            (old_len as u32, data.len() as u32)
+        );
+        assert_eq!(read_entry(&path, b.get_index()), b_entry);
+        assert_eq!(
+            fs::metadata(&path).unwrap().len(),
+            old_len + data.len() as u64
+        );
+        assert_same_chunk(&larger, &read_chunk(&region, a.get_index()));
+        assert_same_chunk(&b, &read_chunk(&region, b.get_index()));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn unused_data_is_compacted() {
+        let dir = create_test_dir("compact");
+        let path = dir.join("region");
+        let region = RegionFile::new(&path);
+        let chunks = [
+            create_mixed_chunk(vector![0, 0, 0], 0),
+            create_mixed_chunk(vector![3, 0, 0], 1),
+        ];
+        let encoded = chunks
+            .iter()
+            .map(|chunk| {
+                (
+                    chunk.get_index(),
+                    encode_chunk(chunk, &VoxelIdMap::default()),
+                )
+            })
+            .collect::<Vec<_>>();
+        region.write_chunks(&encoded).unwrap();
+        let used = encoded
+            .iter()
+            .map(|(_, data)| data.len() as u64)
+            .sum::<u64>();
+
+        // Every rewrite leaves the old data behind, until it takes up more than half of the file.
+        let mut largest_len = fs::metadata(&path).unwrap().len();
+        let mut compacted = false;
+        for _ in 0..10 {
+            region.write_chunks(&encoded[..1]).unwrap();
+            let len = fs::metadata(&path).unwrap().len();
+            if len < largest_len {
+                compacted = true;
+                break;
+            }
+            largest_len = len;
+        }
+
+        assert!(compacted, "the file was never compacted");
+        assert_eq!(fs::metadata(&path).unwrap().len(), TABLE_END + used);
+        assert!(!path.with_extension("tmp").exists());
+        for chunk in &chunks {
+            assert_same_chunk(chunk, &read_chunk(&region, chunk.get_index()));
+        }
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn storage_loads_queued_and_flushed_chunks() {
+        let dir = create_test_dir("storage");
+        let storage = RegionStorage::new(&dir, VoxelIdMap::default()).unwrap();
+        // The chunks are in different regions.
+        let chunks = [
+            create_mixed_chunk(vector![0, 0, 0], 0),
+            create_mixed_chunk(vector![-1, 20, 0], 1),
+        ];
+        for chunk in &chunks {
+            storage.queue_save(chunk);
+        }
+        assert_eq!(storage.get_unsaved_count(), 2);
+        for chunk in &chunks {
+            assert_same_chunk(
+                chunk,
+                &storage.load_chunk(chunk.get_index()).unwrap().unwrap(),
+            );
+        }
+
+        assert_eq!(storage.flush().unwrap(), 2);
+        assert_eq!(storage.get_unsaved_count(), 0);
+        let storage = RegionStorage::new(&dir, VoxelIdMap::default()).unwrap();
+        for chunk in &chunks {
+            assert_same_chunk(
+                chunk,
+                &storage.load_chunk(chunk.get_index()).unwrap().unwrap(),
+            );
+        }
+        assert!(storage.load_chunk(vector![5, 5, 5]).unwrap().is_none());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -3,6 +3,8 @@ use std::path::PathBuf;
 use nalgebra::{vector, Vector3};
 use serde::{Deserialize, Serialize};
 
+use super::{face_dir::FaceDir, voxel_shape::VoxelShape};
+
 /// Lightweight handle to a voxel.
 #[derive(Default, Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct VoxelHandle {
@@ -19,6 +21,34 @@ pub struct Voxel {
     pub name: String,
     /// The voxel texture.
     pub texture: VoxelTexture,
+    /// Whether entities collide with the voxel, defaults to true.
+    #[serde(default = "default_collidable")]
+    pub collidable: bool,
+    /// How the voxel lets light through, defaults to opaque.
+    #[serde(default)]
+    pub opacity: VoxelOpacity,
+    /// The shape of the voxel inside its cell, defaults to a cube.
+    #[serde(default)]
+    pub shape: VoxelShape,
+    /// The block light level the voxel emits, from 0 to `light::MAX_LIGHT_LEVEL`, defaults to 0.
+    #[serde(default)]
+    pub emission: u8,
+    /// Whether the voxel is a fluid that flows into the empty voxels around it, defaults to false.
+    #[serde(default)]
+    pub fluid: bool,
+    /// How many times per second the fluid flows, up to `fluid::FLUID_TICKS_PER_SECOND`, defaults to 4.
+    #[serde(default = "default_flow_rate")]
+    pub flow_rate: u32,
+}
+
+/// The default value of `Voxel::collidable`.
+fn default_collidable() -> bool {
+    true
+}
+
+/// The default value of `Voxel::flow_rate`.
+fn default_flow_rate() -> u32 {
+    4
 }
 
 impl Voxel {
@@ -36,6 +66,43 @@ impl Voxel {
             }
         }
     }
+
+    /// Returns the texture index of the side of the voxel that faces the face direction.
+    pub fn get_face_texture_index(texture_index: Vector3<u32>, face_dir: FaceDir) -> u32 {
+        match face_dir {
+            FaceDir::Up => texture_index.x,
+            FaceDir::Down => texture_index.z,
+            _ => texture_index.y,
+        }
+    }
+
+    /// Returns true if the voxel stops light, which only opaque cubes do.
+    pub fn blocks_light(&self) -> bool {
+        self.opacity.is_opaque() && self.shape.is_cube()
+    }
+}
+
+/// How a voxel lets light through.
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum VoxelOpacity {
+    /// The voxel hides everything behind it.
+    #[default]
+    Opaque,
+    /// The texels of the voxel are either fully opaque or fully transparent, like leaves.
+    ///
+    /// The transparent texels are discarded, so the voxel is rendered with the opaque geometry.
+    Cutout,
+    /// The voxel is blended with what is behind it, like glass or water.
+    ///
+    /// Faces between two voxels of the same translucent type are not rendered.
+    Translucent,
+}
+
+impl VoxelOpacity {
+    /// Returns true if the voxel hides the faces behind it.
+    pub fn is_opaque(self) -> bool {
+        self == Self::Opaque
+    }
 }
 
 /// Contains data for a voxel texture.
@@ -63,3 +130,42 @@ pub enum VoxelTexture {
         array_index_start: Option<u32>,
     },
 }
+
+/// Helpers for tests that need registered voxels.
+#[cfg(test)]
+pub(crate) mod test_utils {
+    use std::{collections::HashMap, path::PathBuf};
+
+    use super::*;
+    use crate::common::fluid::FLUID_TICKS_PER_SECOND;
+
+    /// Creates the registered voxels for the specified handles.
+    ///
+    /// ## Arguments
+    /// * `voxels` - The voxel handles and whether each voxel is a fluid, the other voxels are solid cubes.
+    ///
+    /// ## Returns
+    /// The voxels by their ids, the fluids flow on every fluid tick.
+    pub fn create_test_voxels(voxels: &[(VoxelHandle, bool)]) -> HashMap<u32, Voxel> {
+        voxels
+            .iter()
+            .map(|&(handle, fluid)| {
+                let voxel = Voxel {
+                    id: handle.id,
+                    name: format!("voxel_{}", handle.id),
+                    texture: VoxelTexture::Single {
+                        path: PathBuf::new(),
+                        array_index: None,
+                    },
+                    collidable: !fluid,
+                    opacity: VoxelOpacity::default(),
+                    shape: VoxelShape::default(),
+                    emission: 0,
+                    fluid,
+                    flow_rate: FLUID_TICKS_PER_SECOND,
+                };
+                (handle.id, voxel)
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,226 @@
+use nalgebra::{vector, Vector3};
+use serde::{Deserialize, Serialize};
+
+use crate::rendering::{
+    index::Index,
+    vertex::{self, Vertex},
+};
+
+use super::{chunk, face_dir::FaceDir, light::LightLevels, voxel::Voxel};
+
+/// The shape of a voxel inside its cell.
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum VoxelShape {
+    /// Fills the whole cell, these voxels are meshed by the greedy mesher.
+    #[default]
+    Cube,
+    /// Fills the bottom half of the cell.
+    Slab,
+    /// A slab with a step on the back half of the cell, so it rises towards positive z.
+    Stairs,
+    /// Two quads that cross diagonally through the cell, used for plants.
+    Cross,
+}
+
+/// An axis aligned box inside a voxel cell, the cell goes from 0 to 1 on every axis.
+struct ShapeBox {
+    min: [f32; 3],
+    max: [f32; 3],
+}
+
+/// The boxes of `VoxelShape::Cube`.
+const CUBE_BOXES: &[ShapeBox] = &[ShapeBox {
+    min: [0.0, 0.0, 0.0],
+    max: [1.0, 1.0, 1.0],
+}];
+/// The boxes of `VoxelShape::Slab`.
+const SLAB_BOXES: &[ShapeBox] = &[ShapeBox {
+    min: [0.0, 0.0, 0.0],
+    max: [1.0, 0.5, 1.0],
+}];
+/// The boxes of `VoxelShape::Stairs`.
+const STAIRS_BOXES: &[ShapeBox] = &[
+    ShapeBox {
+        min: [0.0, 0.0, 0.0],
+        max: [1.0, 0.5, 1.0],
+    },
+    ShapeBox {
+        min: [0.0, 0.5, 0.5],
+        max: [1.0, 1.0, 1.0],
+    },
+];
+
+impl VoxelShape {
+    /// Returns true if the shape fills the whole cell.
+    pub fn is_cube(self) -> bool {
+        self == Self::Cube
+    }
+
+    /// Returns true if the shape completely covers the side of its cell in the face direction,
+    /// so the face of the neighbouring voxel on that side is hidden.
+    pub fn covers_face(self, face_dir: FaceDir) -> bool {
+        match self {
+            Self::Cube => true,
+            Self::Slab => face_dir == FaceDir::Down,
+            Self::Stairs => matches!(face_dir, FaceDir::Down | FaceDir::Back),
+            Self::Cross => false,
+        }
+    }
+
+    /// Returns the boxes the shape is made of.
+    fn get_boxes(self) -> &'static [ShapeBox] {
+        match self {
+            Self::Cube => CUBE_BOXES,
+            Self::Slab => SLAB_BOXES,
+            Self::Stairs => STAIRS_BOXES,
+            Self::Cross => &[],
+        }
+    }
+
+    /// Generates the vertices and indices of a single voxel with this shape and appends them to the given vectors.
+    ///
+    /// The faces that lie on a covered side of the cell are skipped.
+    ///
+    /// ## Arguments
+    /// * `vertices` - The vector to append the vertices to.
+    /// * `indices` - The vector to append the indices to.
+    /// * `position` - The position of the voxel cell.
+    /// * `voxel_texture_index` - The texture index of the voxel.
+    /// * `light` - The light levels of the voxel cell.
+    /// * `is_side_covered` - Returns true if the neighbour in a face direction covers that side of the cell.
+    pub fn append_to_vertices(
+        self,
+        vertices: &mut Vec<Vertex>,
+        indices: &mut Vec<Index>,
+        position: Vector3<f32>,
+        voxel_texture_index: Vector3<u32>,
+        light: LightLevels,
+        is_side_covered: impl Fn(FaceDir) -> bool,
+    ) {
+        // The faces are lit by the voxel cell itself, because the shape does not fill it.
+        if self == Self::Cross {
+            let texture_layer = Voxel::get_face_texture_index(voxel_texture_index, FaceDir::Left);
+            append_cross(vertices, indices, position, texture_layer, light);
+            return;
+        }
+
+        for shape_box in self.get_boxes() {
+            for axis in 0..6 {
+                let face_dir = FaceDir::from_axis(axis);
+                let bound = |high: bool, axis: usize| match high {
+                    true => shape_box.max[axis],
+                    false => shape_box.min[axis],
+                };
+
+                let normal_axis = get_normal_axis(face_dir);
+                let is_high_side = axis % 2 == 1;
+                let plane = bound(is_high_side, normal_axis);
+                let on_side = plane == if is_high_side { 1.0 } else { 0.0 };
+                if on_side && is_side_covered(face_dir) {
+                    continue;
+                }
+
+                let (u, v) = chunk::face_tangents(face_dir);
+                let (u, v) = (u.iamax(), v.iamax());
+                let corners = [(false, false), (true, false), (false, true), (true, true)].map(
+                    |(high_u, high_v)| {
+                        let mut corner = Vector3::zeros();
+                        corner[normal_axis] = plane;
+                        corner[u] = bound(high_u, u);
+                        corner[v] = bound(high_v, v);
+                        corner
+                    },
+                );
+
+                append_face(
+                    vertices,
+                    indices,
+                    corners.map(|corner| position + corner),
+                    corners.map(|corner| [corner[u], corner[v]]),
+                    face_dir as u8,
+                    Voxel::get_face_texture_index(voxel_texture_index, face_dir),
+                    light,
+                    face_dir.reverse_direction(),
+                );
+            }
+        }
+    }
+}
+
+/// Returns the index of the axis the face direction points along.
+fn get_normal_axis(face_dir: FaceDir) -> usize {
+    match face_dir {
+        FaceDir::Left | FaceDir::Right => 0,
+        FaceDir::Down | FaceDir::Up => 1,
+        FaceDir::Forward | FaceDir::Back => 2,
+    }
+}
+
+/// Appends the two diagonal quads of a `VoxelShape::Cross`, both of them are visible from either side.
+///
+/// The normals of the two sides of the diagonals are `vertex::CROSS_NORMAL_INDEX` and the three indices after it.
+fn append_cross(
+    vertices: &mut Vec<Vertex>,
+    indices: &mut Vec<Index>,
+    position: Vector3<f32>,
+    texture_layer: u32,
+    light: LightLevels,
+) {
+    let diagonals = [
+        (vector![0.0, 0.0, 0.0], vector![1.0, 0.0, 1.0]),
+        (vector![1.0, 0.0, 0.0], vector![0.0, 0.0, 1.0]),
+    ];
+    let tex_coords = [[0.0, 0.0], [1.0, 0.0], [0.0, 1.0], [1.0, 1.0]];
+
+    for (diagonal, (start, end)) in diagonals.into_iter().enumerate() {
+        let corners = [start, end, start + Vector3::y(), end + Vector3::y()];
+        for (side, reversed) in [(0, true), (1, false)] {
+            append_face(
+                vertices,
+                indices,
+                corners.map(|corner| position + corner),
+                tex_coords,
+                vertex::CROSS_NORMAL_INDEX + diagonal as u8 * 2 + side,
+                texture_layer,
+                light,
+                reversed,
+            );
+        }
+    }
+}
+
+/// Appends a single quad.
+///
+/// The corners are in the same order as in `Quad::append_to_vertices`.
+/// If `reversed` is true, the quad is visible from the side that `(c1 - c0) x (c2 - c0)` points to,
+/// otherwise it is visible from the other side.
+#[allow(clippy::too_many_arguments)]
+fn append_face(
+    vertices: &mut Vec<Vertex>,
+    indices: &mut Vec<Index>,
+    corners: [Vector3<f32>; 4],
+    tex_coords: [[f32; 2]; 4],
+    normal_index: u8,
+    texture_layer: u32,
+    light: LightLevels,
+    reversed: bool,
+) {
+    let new_indices: [Index; 6] = match reversed {
+        true => [0, 1, 3, 0, 3, 2],
+        false => [0, 3, 1, 0, 2, 3],
+    };
+    let first_vertex =
+        Index::try_from(vertices.len()).expect("too many vertices for the index type");
+    indices.extend(new_indices.into_iter().map(|i| i + first_vertex));
+
+    vertices.extend((0..4).map(|i| {
+        Vertex::new(
+            corners[i],
+            tex_coords[i],
+            normal_index,
+            texture_layer,
+            chunk::MAX_AMBIENT_OCCLUSION,
+            light,
+        )
+    }));
+}
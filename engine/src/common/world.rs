@@ -0,0 +1,486 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::utils::file_system;
+
+use super::region::VoxelIdMap;
+
+/// The current version of the world format.
+pub const WORLD_FORMAT_VERSION: u32 = 1;
+/// The name of the metadata file in a world directory.
+const METADATA_FILE_NAME: &str = "world.ron";
+/// The name of the region file directory in a world directory.
+const REGIONS_DIR_NAME: &str = "regions";
+
+/// Describes how managing a world failed.
+#[derive(Error, Debug)]
+pub enum WorldError {
+    #[error("World name {0:?} is invalid.")]
+    InvalidName(String),
+    #[error("World {0:?} does not exist.")]
+    NotFound(String),
+    #[error("World {0:?} already exists.")]
+    AlreadyExists(String),
+    #[error("World {0:?} has unsupported format version {1}.")]
+    UnsupportedVersion(String, u32),
+    #[error(transparent)]
+    IoError(#[from] io::Error),
+    #[error(transparent)]
+    SerializationError(#[from] ron::Error),
+    #[error(transparent)]
+    DeserializationError(#[from] ron::error::SpannedError),
+}
+
+/// The metadata of a saved world.
+///
+/// The generator options are defined by the game, so a world always reopens with the
+/// generator settings it was created with.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct WorldMetadata<O> {
+    /// The version of the world format the world was saved with.
+    pub version: u32,
+    /// The name of the world, it is also the name of its directory.
+    pub name: String,
+    /// The seed of the world generator.
+    pub seed: u64,
+    /// The options of the world generator.
+    pub generator_options: O,
+    /// Maps the voxel names to the voxel ids that are stored in the region files.
+    pub voxel_ids: BTreeMap<String, u32>,
+    /// The last position of the camera.
+    pub camera_position: [f32; 3],
+}
+
+impl<O> WorldMetadata<O> {
+    /// Creates the metadata for a new world.
+    pub fn new(
+        name: String,
+        seed: u64,
+        generator_options: O,
+        voxel_ids: BTreeMap<String, u32>,
+    ) -> Self {
+        Self {
+            version: WORLD_FORMAT_VERSION,
+            name,
+            seed,
+            generator_options,
+            voxel_ids,
+            camera_position: [0.0; 3],
+        }
+    }
+
+    /// Records the registered voxels that are not known to the world yet.
+    ///
+    /// A new voxel keeps its registered id in the region files unless another voxel is already stored with it,
+    /// then it gets the lowest free id.
+    ///
+    /// ## Returns
+    /// True if any voxel has been recorded.
+    pub fn register_voxel_ids(&mut self, voxel_ids: &BTreeMap<String, u32>) -> bool {
+        let mut used_ids = self.voxel_ids.values().copied().collect::<BTreeSet<_>>();
+        let mut has_new_voxels = false;
+        for (voxel_name, id) in voxel_ids {
+            if self.voxel_ids.contains_key(voxel_name) {
+                continue;
+            }
+            let stored_id = if used_ids.contains(id) {
+                (0..).find(|id| !used_ids.contains(id)).unwrap_or(*id)
+            } else {
+                *id
+            };
+            used_ids.insert(stored_id);
+            self.voxel_ids.insert(voxel_name.clone(), stored_id);
+            has_new_voxels = true;
+        }
+        has_new_voxels
+    }
+
+    /// Creates the map that translates the voxel ids stored in the region files to the ids of the registered voxels.
+    ///
+    /// Voxels of the world that are not registered anymore are loaded as air. They stay in the metadata,
+    /// so their stored ids are not given to other voxels.
+    ///
+    /// ## Arguments
+    /// * `voxel_ids` - The ids of the registered voxels by their names.
+    pub fn create_voxel_id_map(&self, voxel_ids: &BTreeMap<String, u32>) -> VoxelIdMap {
+        let mut pairs = vec![];
+        let mut removed = vec![];
+        for (voxel_name, stored_id) in &self.voxel_ids {
+            match voxel_ids.get(voxel_name) {
+                Some(id) => pairs.push((*stored_id, *id)),
+                None => {
+                    log::warn!(
+                        "World {:?} contains voxel {voxel_name:?}, which is not registered anymore. It is replaced with air.",
+                        self.name
+                    );
+                    removed.push(*stored_id);
+                }
+            }
+        }
+        VoxelIdMap::new(pairs).with_removed(removed)
+    }
+}
+
+/// The part of the world metadata that does not depend on the game, used for listing worlds.
+#[derive(Deserialize, Clone, Debug)]
+pub struct WorldSummary {
+    /// The version of the world format the world was saved with.
+    pub version: u32,
+    /// The name of the world.
+    pub name: String,
+    /// The seed of the world generator.
+    pub seed: u64,
+}
+
+/// Returns the directory of the region files of the world with the specified name.
+pub fn get_regions_dir(name: &str) -> PathBuf {
+    get_regions_dir_in(saves_dir(), name)
+}
+
+/// Returns true if a world with the specified name exists.
+pub fn world_exists(name: &str) -> bool {
+    world_exists_in(saves_dir(), name)
+}
+
+/// Creates a new world.
+///
+/// ## Returns
+/// The directory of the created world.
+pub fn create_world<O: Serialize>(metadata: &WorldMetadata<O>) -> Result<PathBuf, WorldError> {
+    create_world_in(saves_dir(), metadata)
+}
+
+/// Opens the world with the specified name and returns its metadata.
+pub fn open_world<O: DeserializeOwned>(name: &str) -> Result<WorldMetadata<O>, WorldError> {
+    open_world_in(saves_dir(), name)
+}
+
+/// Overwrites the metadata of an existing world.
+pub fn save_world_metadata<O: Serialize>(metadata: &WorldMetadata<O>) -> Result<(), WorldError> {
+    save_world_metadata_in(saves_dir(), metadata)
+}
+
+/// Returns the summaries of all the saved worlds, sorted by name.
+///
+/// Worlds whose metadata can not be read are skipped.
+pub fn list_worlds() -> Result<Vec<WorldSummary>, WorldError> {
+    list_worlds_in(saves_dir())
+}
+
+/// Deletes the world with the specified name, including all of its chunks.
+pub fn delete_world(name: &str) -> Result<(), WorldError> {
+    delete_world_in(saves_dir(), name)
+}
+
+/// Returns the directory the worlds are saved in.
+fn saves_dir() -> &'static Path {
+    Path::new(file_system::SAVES_DIR)
+}
+
+fn get_regions_dir_in(saves_dir: &Path, name: &str) -> PathBuf {
+    saves_dir.join(name).join(REGIONS_DIR_NAME)
+}
+
+fn world_exists_in(saves_dir: &Path, name: &str) -> bool {
+    get_metadata_path(saves_dir, name).is_file()
+}
+
+fn create_world_in<O: Serialize>(
+    saves_dir: &Path,
+    metadata: &WorldMetadata<O>,
+) -> Result<PathBuf, WorldError> {
+    validate_name(&metadata.name)?;
+    if world_exists_in(saves_dir, &metadata.name) {
+        return Err(WorldError::AlreadyExists(metadata.name.clone()));
+    }
+
+    fs::create_dir_all(get_regions_dir_in(saves_dir, &metadata.name))?;
+    save_world_metadata_in(saves_dir, metadata)?;
+    Ok(saves_dir.join(&metadata.name))
+}
+
+fn open_world_in<O: DeserializeOwned>(
+    saves_dir: &Path,
+    name: &str,
+) -> Result<WorldMetadata<O>, WorldError> {
+    validate_name(name)?;
+    let metadata = read_metadata_text(saves_dir, name)?;
+    // Checks the version before the rest, so old worlds report the version instead of a parsing error.
+    let summary = ron::from_str::<WorldSummary>(&metadata)?;
+    if summary.version != WORLD_FORMAT_VERSION {
+        return Err(WorldError::UnsupportedVersion(
+            name.to_owned(),
+            summary.version,
+        ));
+    }
+    Ok(ron::from_str(&metadata)?)
+}
+
+fn save_world_metadata_in<O: Serialize>(
+    saves_dir: &Path,
+    metadata: &WorldMetadata<O>,
+) -> Result<(), WorldError> {
+    validate_name(&metadata.name)?;
+    let text = ron::ser::to_string_pretty(metadata, ron::ser::PrettyConfig::default())?;
+    file_system::write::write_text(get_metadata_path(saves_dir, &metadata.name), &text)?;
+    Ok(())
+}
+
+fn list_worlds_in(saves_dir: &Path) -> Result<Vec<WorldSummary>, WorldError> {
+    let entries = match fs::read_dir(saves_dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut worlds = vec![];
+    for entry in entries {
+        let name = entry?.file_name();
+        let name = match name.to_str() {
+            Some(name) => name,
+            None => continue,
+        };
+        if !world_exists_in(saves_dir, name) {
+            continue;
+        }
+        match read_metadata_text(saves_dir, name)
+            .and_then(|text| Ok(ron::from_str::<WorldSummary>(&text)?))
+        {
+            Ok(summary) => worlds.push(summary),
+            Err(e) => log::error!("Failed to read world {name:?}: {e}"),
+        }
+    }
+    worlds.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(worlds)
+}
+
+fn delete_world_in(saves_dir: &Path, name: &str) -> Result<(), WorldError> {
+    validate_name(name)?;
+    if !world_exists_in(saves_dir, name) {
+        return Err(WorldError::NotFound(name.to_owned()));
+    }
+    fs::remove_dir_all(saves_dir.join(name))?;
+    Ok(())
+}
+
+/// Returns the path of the metadata file of the world with the specified name.
+fn get_metadata_path(saves_dir: &Path, name: &str) -> PathBuf {
+    saves_dir.join(name).join(METADATA_FILE_NAME)
+}
+
+/// Reads the metadata file of the world with the specified name.
+fn read_metadata_text(saves_dir: &Path, name: &str) -> Result<String, WorldError> {
+    let path = get_metadata_path(saves_dir, name);
+    if !path.is_file() {
+        return Err(WorldError::NotFound(name.to_owned()));
+    }
+    Ok(file_system::read::read_text(path)?)
+}
+
+/// Makes sure the world name can be used as a directory name.
+fn validate_name(name: &str) -> Result<(), WorldError> {
+    let is_valid = !name.is_empty()
+        && name.len() <= 64
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, ' ' | '-' | '_' | '.'));
+    if is_valid {
+        Ok(())
+    } else {
+        Err(WorldError::InvalidName(name.to_owned()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::vector;
+
+    use super::*;
+    use crate::{
+        common::{
+            region::{decode_chunk, encode_chunk},
+            VoxelHandle,
+        },
+        ecs::components::Chunk,
+    };
+
+    /// Creates an empty directory for a test, the test removes it when it is done.
+    fn create_test_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("voxel-engine-world-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).expect("failed to create the test directory");
+        dir
+    }
+
+    fn create_voxel_ids(voxels: &[(&str, u32)]) -> BTreeMap<String, u32> {
+        voxels
+            .iter()
+            .map(|(name, id)| (name.to_string(), *id))
+            .collect()
+    }
+
+    /// Saves a chunk with a single voxel and loads it back with other voxel ids, returning the loaded voxel.
+    fn reload_voxel(
+        voxel: VoxelHandle,
+        saved_with: &VoxelIdMap,
+        loaded_with: &VoxelIdMap,
+    ) -> Option<VoxelHandle> {
+        let mut chunk = Chunk::new(vector![0, 0, 0]);
+        *chunk.sample_mut((1, 2, 3)) = Some(voxel);
+        let data = encode_chunk(&chunk, saved_with);
+        let chunk = decode_chunk(chunk.get_index(), &data, loaded_with).unwrap();
+        chunk.sample((1, 2, 3))
+    }
+
+    #[test]
+    fn new_voxels_keep_free_ids() {
+        let mut metadata =
+            WorldMetadata::new("test".to_owned(), 0, (), create_voxel_ids(&[("stone", 1)]));
+
+        let voxel_ids = create_voxel_ids(&[("stone", 5), ("dirt", 2), ("sand", 1)]);
+        assert!(metadata.register_voxel_ids(&voxel_ids));
+        // Stone keeps its stored id, dirt keeps its registered id and sand takes the lowest free one.
+        assert_eq!(
+            metadata.voxel_ids,
+            create_voxel_ids(&[("stone", 1), ("dirt", 2), ("sand", 0)])
+        );
+
+        assert!(!metadata.register_voxel_ids(&voxel_ids));
+    }
+
+    #[test]
+    fn voxel_ids_stay_stable_across_runs() {
+        let first_ids = create_voxel_ids(&[("dirt", 1), ("stone", 2)]);
+        let metadata = WorldMetadata::new("test".to_owned(), 0, (), first_ids.clone());
+        let first_map = metadata.create_voxel_id_map(&first_ids);
+        let text = ron::to_string(&metadata).unwrap();
+
+        // A new voxel is registered before the existing ones in the next run.
+        let second_ids = create_voxel_ids(&[("grass", 1), ("dirt", 2), ("stone", 3)]);
+        let mut metadata = ron::from_str::<WorldMetadata<()>>(&text).unwrap();
+        assert!(metadata.register_voxel_ids(&second_ids));
+        let second_map = metadata.create_voxel_id_map(&second_ids);
+        let text = ron::to_string(&metadata).unwrap();
+
+        for (name, id) in &first_ids {
+            assert_eq!(
+                reload_voxel(VoxelHandle { id: *id }, &first_map, &second_map),
+                Some(VoxelHandle {
+                    id: second_ids[name]
+                }),
+                "{name}"
+            );
+        }
+
+        // The registered ids of the first run are used again.
+        let metadata = ron::from_str::<WorldMetadata<()>>(&text).unwrap();
+        let third_map = metadata.create_voxel_id_map(&first_ids);
+        assert_eq!(
+            reload_voxel(VoxelHandle { id: 3 }, &second_map, &third_map),
+            Some(VoxelHandle { id: 2 })
+        );
+    }
+
+    #[test]
+    fn removed_voxels_are_replaced_with_air() {
+        let metadata = WorldMetadata::new(
+            "test".to_owned(),
+            0,
+            (),
+            create_voxel_ids(&[("dirt", 1), ("stone", 2)]),
+        );
+
+        let voxel_id_map = metadata.create_voxel_id_map(&create_voxel_ids(&[("stone", 1)]));
+        assert_eq!(voxel_id_map.to_current(1), None);
+        assert_eq!(voxel_id_map.to_current(2), Some(1));
+    }
+
+    #[test]
+    fn invalid_names_are_rejected() {
+        for name in ["", ".hidden", "..", "a/b", "a\\b", "ä", &"a".repeat(65)] {
+            assert!(
+                matches!(validate_name(name), Err(WorldError::InvalidName(_))),
+                "{name:?}"
+            );
+        }
+        for name in ["World", "my world 2", "a-b_c.d", &"a".repeat(64)] {
+            assert!(validate_name(name).is_ok(), "{name:?}");
+        }
+    }
+
+    #[test]
+    fn worlds_are_created_listed_and_deleted() {
+        let dir = create_test_dir("round-trip");
+        assert!(list_worlds_in(&dir).unwrap().is_empty());
+
+        let mut metadata = WorldMetadata::new(
+            "b".to_owned(),
+            7,
+            "options".to_owned(),
+            create_voxel_ids(&[("stone", 1)]),
+        );
+        let world_dir = create_world_in(&dir, &metadata).unwrap();
+        assert_eq!(world_dir, dir.join("b"));
+        assert!(get_regions_dir_in(&dir, "b").is_dir());
+        assert!(matches!(
+            create_world_in(&dir, &metadata),
+            Err(WorldError::AlreadyExists(_))
+        ));
+
+        metadata.name = "a".to_owned();
+        metadata.camera_position = [1.0, 2.0, 3.0];
+        create_world_in(&dir, &metadata).unwrap();
+        // Directories without metadata are not worlds.
+        fs::create_dir(dir.join("c")).unwrap();
+
+        let names = list_worlds_in(&dir)
+            .unwrap()
+            .into_iter()
+            .map(|summary| summary.name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["a", "b"]);
+
+        let opened = open_world_in::<String>(&dir, "a").unwrap();
+        assert_eq!(opened.seed, 7);
+        assert_eq!(opened.generator_options, "options");
+        assert_eq!(opened.voxel_ids, metadata.voxel_ids);
+        assert_eq!(opened.camera_position, [1.0, 2.0, 3.0]);
+
+        delete_world_in(&dir, "a").unwrap();
+        assert!(!world_exists_in(&dir, "a"));
+        assert!(matches!(
+            open_world_in::<String>(&dir, "a"),
+            Err(WorldError::NotFound(_))
+        ));
+        assert!(matches!(
+            delete_world_in(&dir, "c"),
+            Err(WorldError::NotFound(_))
+        ));
+        assert_eq!(list_worlds_in(&dir).unwrap().len(), 1);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn unsupported_versions_are_reported() {
+        let dir = create_test_dir("version");
+        let mut metadata = WorldMetadata::new("old".to_owned(), 0, (), BTreeMap::new());
+        metadata.version = WORLD_FORMAT_VERSION + 1;
+        create_world_in(&dir, &metadata).unwrap();
+
+        assert!(matches!(
+            open_world_in::<()>(&dir, "old"),
+            Err(WorldError::UnsupportedVersion(_, version)) if version == WORLD_FORMAT_VERSION + 1
+        ));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,217 @@
+use nalgebra::Vector3;
+
+use crate::ecs::components::Chunk;
+
+use super::{chunk, VoxelHandle};
+
+/// Generates the voxels of chunks.
+///
+/// Generators run on background threads, so they have to be shareable between threads.
+///
+/// ## Determinism
+/// The voxels of a chunk must only depend on the seed and the index of the chunk.
+/// Chunks are generated in any order, on any thread and again after being unloaded,
+/// so global state and non-seeded random numbers must not be used.
+/// `random_at` can be used for deterministic per-voxel randomness.
+pub trait WorldGenerator: Send + Sync + 'static {
+    /// Returns the seed the generator was created with.
+    fn get_seed(&self) -> u64;
+
+    /// Fills the specified chunk with voxels.
+    ///
+    /// The chunk is empty and already has its index set, see `Chunk::get_index`.
+    fn generate_chunk(&self, chunk: &mut Chunk);
+}
+
+/// Hashes a seed and a world position into a pseudo-random number.
+pub fn hash_position(seed: u64, position: Vector3<i32>) -> u64 {
+    let mut hash = seed;
+    for c in position.iter() {
+        // SplitMix64 finalizer, mixes every coordinate into all the bits of the hash.
+        hash = hash
+            .wrapping_add(*c as u32 as u64)
+            .wrapping_add(0x9E37_79B9_7F4A_7C15);
+        hash = (hash ^ (hash >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        hash = (hash ^ (hash >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        hash ^= hash >> 31;
+    }
+    hash
+}
+
+/// Returns a pseudo-random number in \[0; 1) for a seed and a world position.
+pub fn random_at(seed: u64, position: Vector3<i32>) -> f32 {
+    // The top 24 bits fit into the mantissa of an f32 exactly.
+    (hash_position(seed, position) >> 40) as f32 / (1 << 24) as f32
+}
+
+/// Generates a flat world, mostly useful for tests and debugging.
+#[derive(Clone, Debug, Default)]
+pub struct FlatWorldGenerator {
+    /// The voxels of the layers from the bottom up, the bottom layer is at world y 0.
+    ///
+    /// Everything below the bottom layer is filled with it and everything above the top layer is empty.
+    pub layers: Vec<Option<VoxelHandle>>,
+    /// The seed of the world, it does not affect the generated voxels.
+    pub seed: u64,
+}
+
+impl FlatWorldGenerator {
+    /// Returns the voxel of the layer at the specified world y position.
+    fn get_layer(&self, y: i32) -> Option<VoxelHandle> {
+        if y < 0 {
+            self.layers.first().copied().flatten()
+        } else {
+            self.layers.get(y as usize).copied().flatten()
+        }
+    }
+}
+
+impl WorldGenerator for FlatWorldGenerator {
+    fn get_seed(&self) -> u64 {
+        self.seed
+    }
+
+    fn generate_chunk(&self, chunk: &mut Chunk) {
+        let bottom = chunk.get_index().y * chunk::CHUNK_LENGTHI32;
+        if bottom + chunk::CHUNK_LENGTHI32 <= 0 {
+            chunk.fill(self.get_layer(-1));
+            return;
+        }
+        if bottom >= self.layers.len() as i32 {
+            return;
+        }
+
+        let mut voxels = Vec::with_capacity(chunk::CHUNK_VOLUME);
+        for _ in 0..chunk::CHUNK_LENGTH {
+            for y in 0..chunk::CHUNK_LENGTHI32 {
+                let voxel = self.get_layer(bottom + y);
+                voxels.extend(std::iter::repeat_n(voxel, chunk::CHUNK_LENGTH));
+            }
+        }
+        chunk.set_voxels(&voxels);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use nalgebra::vector;
+
+    use super::*;
+
+    const STONE: VoxelHandle = VoxelHandle { id: 1 };
+    const DIRT: VoxelHandle = VoxelHandle { id: 2 };
+    const GRASS: VoxelHandle = VoxelHandle { id: 3 };
+
+    /// Scatters stone with `random_at`, like caves or ores would be.
+    struct ScatterGenerator {
+        seed: u64,
+    }
+
+    impl WorldGenerator for ScatterGenerator {
+        fn get_seed(&self) -> u64 {
+            self.seed
+        }
+
+        fn generate_chunk(&self, chunk: &mut Chunk) {
+            let index = chunk.get_index();
+            for z in 0..chunk::CHUNK_LENGTH {
+                for y in 0..chunk::CHUNK_LENGTH {
+                    for x in 0..chunk::CHUNK_LENGTH {
+                        let position = chunk::local_to_world(index, (x, y, z));
+                        if random_at(self.seed, position) < 0.5 {
+                            *chunk.sample_mut((x, y, z)) = Some(STONE);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn generate(generator: &impl WorldGenerator, index: Vector3<i32>) -> Chunk {
+        let mut chunk = Chunk::new(index);
+        generator.generate_chunk(&mut chunk);
+        chunk
+    }
+
+    fn count_differences(a: &Chunk, b: &Chunk) -> usize {
+        (0..chunk::CHUNK_VOLUME)
+            .filter(|index| a.get_voxels().get(*index) != b.get_voxels().get(*index))
+            .count()
+    }
+
+    #[test]
+    fn same_seed_generates_the_same_chunk() {
+        let index = vector![3, -1, 7];
+        let a = generate(&ScatterGenerator { seed: 42 }, index);
+        // A chunk generated in between must not change the result.
+        generate(&ScatterGenerator { seed: 42 }, vector![0, 0, 0]);
+        let b = generate(&ScatterGenerator { seed: 42 }, index);
+
+        assert_eq!(count_differences(&a, &b), 0);
+        assert!(count_differences(&a, &generate(&ScatterGenerator { seed: 43 }, index)) > 0);
+        assert!(
+            count_differences(
+                &a,
+                &generate(&ScatterGenerator { seed: 42 }, vector![3, -1, 8])
+            ) > 0
+        );
+    }
+
+    #[test]
+    fn random_at_differs_between_neighbours() {
+        let seed = 7;
+        let mut values = HashSet::new();
+        for x in -4..4 {
+            for y in -4..4 {
+                for z in -4..4 {
+                    let position = vector![x, y, z];
+                    let value = random_at(seed, position);
+                    assert!((0.0..1.0).contains(&value));
+                    assert_eq!(value, random_at(seed, position));
+                    assert_ne!(value, random_at(seed + 1, position));
+                    for axis in 0..3 {
+                        let mut neighbour = position;
+                        neighbour[axis] += 1;
+                        assert_ne!(
+                            value,
+                            random_at(seed, neighbour),
+                            "{position:?} {neighbour:?}"
+                        );
+                    }
+                    values.insert(hash_position(seed, position));
+                }
+            }
+        }
+        // Mirrored and swapped positions do not collide either.
+        assert_eq!(values.len(), 8 * 8 * 8);
+    }
+
+    #[test]
+    fn flat_world_is_layered() {
+        let generator = FlatWorldGenerator {
+            layers: vec![Some(STONE), Some(DIRT), Some(GRASS)],
+            seed: 0,
+        };
+
+        let surface = generate(&generator, vector![5, 0, -5]);
+        for (y, voxel) in [Some(STONE), Some(DIRT), Some(GRASS), None]
+            .into_iter()
+            .enumerate()
+        {
+            assert_eq!(surface.sample((0, y, 0)), voxel);
+            assert_eq!(surface.sample((63, y, 17)), voxel);
+        }
+
+        let below = generate(&generator, vector![0, -1, 0]);
+        assert_eq!(below.get_voxels().get_uniform_value(), Some(Some(STONE)));
+        let above = generate(&generator, vector![0, 1, 0]);
+        assert_eq!(above.get_voxels().get_uniform_value(), Some(None));
+
+        assert_eq!(
+            count_differences(&surface, &generate(&generator, vector![-9, 0, 2])),
+            0
+        );
+    }
+}
@@ -1,27 +1,53 @@
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    mem,
+    ops::{Deref, DerefMut},
+};
 
 use crate::{
     common::{
         self,
-        chunk::{self, BinaryVoxelContainer},
+        aabb::Aabb,
+        chunk::{self, BinaryVoxelContainer, ChunkBorder, CHUNK_LENGTHI32},
+        chunk_visibility::FaceConnectivity,
         face_dir::FaceDir,
+        light::{self, FaceLighting, LightBorder, LightChannel, LightLevels},
+        nibble_storage::NibbleStorage,
+        palette::VoxelStorage,
+        voxel::{Voxel, VoxelOpacity},
+        voxel_shape::VoxelShape,
         VoxelHandle,
     },
-    ecs::packages::{render_init::RenderContext, voxel_registry::VoxelRegistry},
-    rendering::{index, instance::Instance},
+    rendering::{index::Index, instance::Instance, vertex::Vertex},
 };
 use bevy_ecs::component::Component;
 use nalgebra::{Matrix4, Vector3};
 
-pub use crate::common::chunk::CHUNK_LENGTH;
-
-use super::Geometry;
+pub use crate::common::chunk::{CHUNK_LENGTH, CHUNK_VOLUME};
 
 /// Contains the data for a single chunk.
 #[derive(Component)]
 pub struct Chunk {
-    pub voxels: Vec<Option<VoxelHandle>>,
+    voxels: VoxelStorage,
     index: Vector3<i32>,
+    /// Which borders have had voxels changed, indexed by face direction.
+    dirty_borders: [bool; 6],
+    /// Whether the voxels have changed since the chunk was generated, loaded or saved.
+    unsaved: bool,
+    /// The block light level of every voxel.
+    block_light: NibbleStorage,
+    /// The sky light level of every voxel.
+    sky_light: NibbleStorage,
+    /// The positions of the voxels that have changed since the light was last updated.
+    changed_voxels: Vec<(usize, usize, usize)>,
+    /// Whether all the voxels have been replaced, so the light has to be computed from scratch.
+    needs_relight: bool,
+    /// The stored fluid level of every voxel, see `fluid::FluidLevel`.
+    fluid_levels: NibbleStorage,
+    /// The positions of the voxels that have been edited since the fluids were last updated.
+    fluid_updates: Vec<(usize, usize, usize)>,
+    /// Whether all the voxels have been replaced, so every fluid voxel has to be simulated again.
+    needs_fluid_scan: bool,
 }
 
 impl Chunk {
@@ -29,12 +55,21 @@ impl Chunk {
     #[allow(unused)]
     pub fn new<V2: Into<Vector3<i32>>>(index: V2) -> Self {
         Self {
-            voxels: vec![None; CHUNK_LENGTH * CHUNK_LENGTH * CHUNK_LENGTH],
+            voxels: VoxelStorage::new(CHUNK_VOLUME, None),
             index: index.into(),
+            dirty_borders: [false; 6],
+            unsaved: false,
+            block_light: NibbleStorage::new(CHUNK_VOLUME),
+            sky_light: NibbleStorage::new(CHUNK_VOLUME),
+            changed_voxels: vec![],
+            needs_relight: true,
+            fluid_levels: NibbleStorage::new(CHUNK_VOLUME),
+            fluid_updates: vec![],
+            needs_fluid_scan: true,
         }
     }
 
-    /// Tries to sample a reference to a voxel at the specified position.
+    /// Tries to sample a voxel at the specified position.
     ///
     /// ## Returns
     /// The outter option indicates whether the index is out of bounds or not.
@@ -43,21 +78,18 @@ impl Chunk {
     pub fn try_sample<V3: Into<(usize, usize, usize)>>(
         &self,
         position: V3,
-    ) -> Option<&Option<VoxelHandle>> {
-        let position = position.into();
-        self.voxels
-            .get(position.0 + position.1 * CHUNK_LENGTH + position.2 * CHUNK_LENGTH * CHUNK_LENGTH)
+    ) -> Option<Option<VoxelHandle>> {
+        let index = try_flatten_position(position.into())?;
+        Some(self.voxels.get(index))
     }
 
-    /// Samples a reference to a voxel at the specified position.
+    /// Samples a voxel at the specified position.
     ///
     /// ## Panics
     /// If the position is out of bounds.
     #[allow(unused)]
-    pub fn sample<V3: Into<(usize, usize, usize)>>(&self, position: V3) -> &Option<VoxelHandle> {
-        let position = position.into();
-        &self.voxels
-            [position.0 + position.1 * CHUNK_LENGTH + position.2 * CHUNK_LENGTH * CHUNK_LENGTH]
+    pub fn sample<V3: Into<(usize, usize, usize)>>(&self, position: V3) -> Option<VoxelHandle> {
+        self.voxels.get(flatten_position(position.into()))
     }
 
     /// Tries to sample a mutable reference to a voxel at the specified position.
@@ -69,25 +101,289 @@ impl Chunk {
     pub fn try_sample_mut<V3: Into<(usize, usize, usize)>>(
         &mut self,
         position: V3,
-    ) -> Option<&mut Option<VoxelHandle>> {
-        let position = position.into();
-        self.voxels.get_mut(
-            position.0 + position.1 * CHUNK_LENGTH + position.2 * CHUNK_LENGTH * CHUNK_LENGTH,
-        )
+    ) -> Option<VoxelMut<'_>> {
+        let index = try_flatten_position(position.into())?;
+        Some(VoxelMut::new(self, index))
     }
 
     /// Samples a mutable reference to a voxel at the specified position.
     ///
+    /// The voxel gets written back into the chunk when the returned `VoxelMut` is dropped.
+    ///
     /// ## Panics
     /// If the position is out of bounds.
     #[allow(unused)]
-    pub fn sample_mut<V3: Into<(usize, usize, usize)>>(
+    pub fn sample_mut<V3: Into<(usize, usize, usize)>>(&mut self, position: V3) -> VoxelMut<'_> {
+        let index = flatten_position(position.into());
+        VoxelMut::new(self, index)
+    }
+
+    /// Replaces all the voxels of the chunk.
+    ///
+    /// The voxels are laid out as `x + y * CHUNK_LENGTH + z * CHUNK_LENGTH * CHUNK_LENGTH`.
+    ///
+    /// ## Panics
+    /// If the length of `voxels` is not `CHUNK_VOLUME`.
+    pub fn set_voxels(&mut self, voxels: &[Option<VoxelHandle>]) {
+        assert_eq!(voxels.len(), CHUNK_VOLUME, "invalid chunk voxel count");
+        self.voxels = VoxelStorage::from_slice(voxels);
+        self.dirty_borders = [true; 6];
+        self.unsaved = true;
+        self.reset_light();
+        self.reset_fluids();
+    }
+
+    /// Sets every voxel of the chunk to the same value.
+    pub fn fill(&mut self, voxel: Option<VoxelHandle>) {
+        self.voxels = VoxelStorage::new(CHUNK_VOLUME, voxel);
+        self.dirty_borders = [true; 6];
+        self.unsaved = true;
+        self.reset_light();
+        self.reset_fluids();
+    }
+
+    /// Clears the light of the chunk, so it gets computed from scratch.
+    fn reset_light(&mut self) {
+        self.block_light.fill(0);
+        self.sky_light.fill(0);
+        self.changed_voxels.clear();
+        self.needs_relight = true;
+    }
+
+    /// Turns every fluid voxel of the chunk into a source, so the fluids get simulated from scratch.
+    fn reset_fluids(&mut self) {
+        self.fluid_levels.fill(0);
+        self.fluid_updates.clear();
+        self.needs_fluid_scan = true;
+    }
+
+    /// Returns the light storage of the specified channel.
+    fn get_light_storage(&self, channel: LightChannel) -> &NibbleStorage {
+        match channel {
+            LightChannel::Block => &self.block_light,
+            LightChannel::Sky => &self.sky_light,
+        }
+    }
+
+    /// Returns the light level of the channel at the specified position.
+    ///
+    /// ## Panics
+    /// If the position is out of bounds.
+    pub fn get_light<V3: Into<(usize, usize, usize)>>(
+        &self,
+        channel: LightChannel,
+        position: V3,
+    ) -> u8 {
+        self.get_light_storage(channel)
+            .get(flatten_position(position.into()))
+    }
+
+    /// Returns the block and the sky light level at the specified position.
+    ///
+    /// ## Panics
+    /// If the position is out of bounds.
+    pub fn get_light_levels<V3: Into<(usize, usize, usize)>>(&self, position: V3) -> LightLevels {
+        let index = flatten_position(position.into());
+        LightLevels {
+            block: self.block_light.get(index),
+            sky: self.sky_light.get(index),
+        }
+    }
+
+    /// Returns the light level of the channel at every position if it is the same everywhere.
+    ///
+    /// See `NibbleStorage::get_uniform_value`.
+    pub fn get_uniform_light(&self, channel: LightChannel) -> Option<u8> {
+        self.get_light_storage(channel).get_uniform_value()
+    }
+
+    /// Sets the light level of the channel at the specified position.
+    ///
+    /// The light is not saved, so this does not mark the chunk as unsaved.
+    ///
+    /// ## Panics
+    /// If the position is out of bounds.
+    pub fn set_light<V3: Into<(usize, usize, usize)>>(
+        &mut self,
+        channel: LightChannel,
+        position: V3,
+        level: u8,
+    ) {
+        let index = flatten_position(position.into());
+        let storage = match channel {
+            LightChannel::Block => &mut self.block_light,
+            LightChannel::Sky => &mut self.sky_light,
+        };
+        if storage.get(index) != level {
+            storage.set(index, level);
+            mark_dirty_borders(&mut self.dirty_borders, index);
+        }
+    }
+
+    /// Sets the light level of the channel at every position.
+    pub fn fill_light(&mut self, channel: LightChannel, level: u8) {
+        match channel {
+            LightChannel::Block => self.block_light.fill(level),
+            LightChannel::Sky => self.sky_light.fill(level),
+        }
+        self.dirty_borders = [true; 6];
+    }
+
+    /// Returns true if all the voxels have been replaced since the last call,
+    /// so the light of the chunk has to be computed from scratch.
+    pub fn take_needs_relight(&mut self) -> bool {
+        mem::take(&mut self.needs_relight)
+    }
+
+    /// Returns the positions of the voxels that have changed since the last call,
+    /// the light around them has to be updated.
+    pub fn take_changed_voxels(&mut self) -> Vec<(usize, usize, usize)> {
+        mem::take(&mut self.changed_voxels)
+    }
+
+    /// Returns the light levels of the channel in the border layer on the side of the specified face direction.
+    pub fn get_light_border(&self, channel: LightChannel, face_dir: FaceDir) -> Box<LightBorder> {
+        let storage = self.get_light_storage(channel);
+        if let Some(level) = storage.get_uniform_value() {
+            return Box::new([[level; CHUNK_LENGTH]; CHUNK_LENGTH]);
+        }
+
+        let layer = chunk::border_layer(face_dir);
+        let mut border = Box::new([[0; CHUNK_LENGTH]; CHUNK_LENGTH]);
+        for (row, levels) in border.iter_mut().enumerate() {
+            for (bit, level) in levels.iter_mut().enumerate() {
+                let position = chunk::border_position(face_dir, layer, row, bit);
+                *level = storage.get(flatten_position(position));
+            }
+        }
+        border
+    }
+
+    /// Returns the stored fluid level at the specified position, see `fluid::FluidLevel`.
+    ///
+    /// ## Panics
+    /// If the position is out of bounds.
+    pub fn get_fluid_level<V3: Into<(usize, usize, usize)>>(&self, position: V3) -> u8 {
+        self.fluid_levels.get(flatten_position(position.into()))
+    }
+
+    /// Sets the voxel and its stored fluid level at the specified position.
+    ///
+    /// Unlike edits through `sample_mut`, this does not queue the voxel for the fluid simulation,
+    /// because the simulation is what sets it.
+    ///
+    /// ## Panics
+    /// If the position is out of bounds.
+    pub fn set_fluid<V3: Into<(usize, usize, usize)>>(
         &mut self,
         position: V3,
-    ) -> &mut Option<VoxelHandle> {
-        let position = position.into();
-        &mut self.voxels
-            [position.0 + position.1 * CHUNK_LENGTH + position.2 * CHUNK_LENGTH * CHUNK_LENGTH]
+        voxel: Option<VoxelHandle>,
+        level: u8,
+    ) {
+        let index = flatten_position(position.into());
+        if self.voxels.get(index) != voxel {
+            self.voxels.set(index, voxel);
+            mark_dirty_borders(&mut self.dirty_borders, index);
+            self.changed_voxels.push(unflatten_position(index));
+            self.unsaved = true;
+        }
+        if self.fluid_levels.get(index) != level {
+            self.fluid_levels.set(index, level);
+            self.unsaved = true;
+        }
+    }
+
+    /// Returns the stored fluid levels of the chunk.
+    pub fn get_fluid_levels(&self) -> &NibbleStorage {
+        &self.fluid_levels
+    }
+
+    /// Replaces the stored fluid levels of the chunk.
+    ///
+    /// ## Panics
+    /// If the length of `fluid_levels` is not `CHUNK_VOLUME`.
+    pub fn set_fluid_levels(&mut self, fluid_levels: NibbleStorage) {
+        assert_eq!(
+            fluid_levels.len(),
+            CHUNK_VOLUME,
+            "invalid chunk fluid level count"
+        );
+        self.fluid_levels = fluid_levels;
+        self.unsaved = true;
+    }
+
+    /// Returns true if all the voxels have been replaced since the last call,
+    /// so every fluid voxel of the chunk has to be simulated again.
+    pub fn take_needs_fluid_scan(&mut self) -> bool {
+        mem::take(&mut self.needs_fluid_scan)
+    }
+
+    /// Returns the positions of the voxels that have been edited since the last call,
+    /// the fluids around them have to be simulated again.
+    pub fn take_fluid_updates(&mut self) -> Vec<(usize, usize, usize)> {
+        mem::take(&mut self.fluid_updates)
+    }
+
+    /// Returns true if the voxels have changed since the chunk was generated, loaded or saved.
+    pub fn is_unsaved(&self) -> bool {
+        self.unsaved
+    }
+
+    /// Marks the current voxels of the chunk as saved.
+    pub fn mark_saved(&mut self) {
+        self.unsaved = false;
+    }
+
+    /// Returns which borders have had voxels changed since the last call, indexed by face direction.
+    ///
+    /// The neighbouring chunks on those sides have to be remeshed.
+    pub fn take_dirty_borders(&mut self) -> [bool; 6] {
+        mem::take(&mut self.dirty_borders)
+    }
+
+    /// Returns the binary voxel data of the opaque voxels in the border layer on the side of the specified face direction.
+    ///
+    /// ## Arguments
+    /// * `face_dir` - The side of the chunk the border layer is on.
+    /// * `registered_voxels` - The registered voxels to look up the voxel opacities in.
+    pub fn get_border(
+        &self,
+        face_dir: FaceDir,
+        registered_voxels: &HashMap<u32, Voxel>,
+    ) -> ChunkBorder {
+        match self.voxels.get_uniform_value() {
+            Some(voxel) if covers_face(registered_voxels, voxel, face_dir) => [!0; CHUNK_LENGTH],
+            Some(_) => [0; CHUNK_LENGTH],
+            None => {
+                let layer = chunk::border_layer(face_dir);
+                let mut border = [0; CHUNK_LENGTH];
+                for (row, bits) in border.iter_mut().enumerate() {
+                    for bit in 0..CHUNK_LENGTH {
+                        let position = chunk::border_position(face_dir, layer, row, bit);
+                        if covers_face(registered_voxels, self.sample(position), face_dir) {
+                            *bits |= 1 << bit;
+                        }
+                    }
+                }
+                border
+            }
+        }
+    }
+
+    /// Returns the voxel storage of the chunk.
+    pub fn get_voxels(&self) -> &VoxelStorage {
+        &self.voxels
+    }
+
+    /// Returns the approximate amount of memory used by the chunk in bytes.
+    pub fn memory_usage(&self) -> usize {
+        mem::size_of::<Self>()
+            - mem::size_of::<VoxelStorage>()
+            - 3 * mem::size_of::<NibbleStorage>()
+            + self.voxels.memory_usage()
+            + self.block_light.memory_usage()
+            + self.sky_light.memory_usage()
+            + self.fluid_levels.memory_usage()
     }
 
     /// Returns the index of the chunk.
@@ -96,48 +392,272 @@ impl Chunk {
         self.index
     }
 
-    /// Builds the mesh for the chunk.
+    /// Creates a snapshot of the voxels and the light of the chunk, which is all that is needed to mesh it.
+    pub fn create_mesh_snapshot(&self) -> ChunkMeshSnapshot {
+        ChunkMeshSnapshot {
+            voxels: self.voxels.clone(),
+            block_light: self.block_light.clone(),
+            sky_light: self.sky_light.clone(),
+            index: self.index,
+        }
+    }
+}
+
+/// The voxels and the light of a chunk at the time it was queued for meshing.
+///
+/// Meshing jobs work on these, so the chunk can keep changing while it is being meshed.
+pub struct ChunkMeshSnapshot {
+    voxels: VoxelStorage,
+    block_light: NibbleStorage,
+    sky_light: NibbleStorage,
+    index: Vector3<i32>,
+}
+
+impl ChunkMeshSnapshot {
+    /// Samples a voxel at the specified position.
+    ///
+    /// ## Panics
+    /// If the position is out of bounds.
+    fn sample(&self, position: (usize, usize, usize)) -> Option<VoxelHandle> {
+        self.voxels.get(flatten_position(position))
+    }
+
+    /// Tries to sample a voxel at the specified position.
+    ///
+    /// ## Returns
+    /// The outter option indicates whether the index is out of bounds or not.
+    /// The inner option indicates if the voxel is present or not.
+    fn try_sample(&self, position: (usize, usize, usize)) -> Option<Option<VoxelHandle>> {
+        let index = try_flatten_position(position)?;
+        Some(self.voxels.get(index))
+    }
+
+    /// Returns the block and the sky light level at the specified position.
+    ///
+    /// ## Panics
+    /// If the position is out of bounds.
+    fn get_light_levels(&self, position: (usize, usize, usize)) -> LightLevels {
+        let index = flatten_position(position);
+        LightLevels {
+            block: self.block_light.get(index),
+            sky: self.sky_light.get(index),
+        }
+    }
+
+    /// Builds the mesh data for the chunk.
+    ///
+    /// This does not touch the GPU, so it can be run on any thread.
+    ///
+    /// ## Arguments
+    /// * `registered_voxels` - The registered voxels to look up the voxel textures and opacities in.
+    /// * `neighbours` - The borders of the loaded neighbouring chunks, faces against opaque neighbour voxels are culled.
+    ///
+    /// ## Returns
+    /// `None` if the chunk does not have anything to render.
     pub fn build_mesh(
         &self,
-        render_context: &RenderContext,
-        voxel_registry: &VoxelRegistry,
-    ) -> Geometry {
+        registered_voxels: &HashMap<u32, Voxel>,
+        neighbours: &ChunkNeighbours,
+    ) -> Option<ChunkMesh> {
+        let (opaque, translucent) = match self.voxels.get_uniform_value() {
+            // Empty chunks never have any faces.
+            Some(None) => return None,
+            // Chunks filled with opaque cubes only have faces on their borders.
+            Some(Some(voxel))
+                if get_shape(registered_voxels, voxel) == (VoxelShape::Cube, ALL_FACES) =>
+            {
+                (
+                    Self::build_uniform_mesh_data(voxel, registered_voxels, neighbours),
+                    ChunkMeshData::default(),
+                )
+            }
+            _ => self.build_mesh_data(registered_voxels, neighbours),
+        };
+
+        if opaque.indices.is_empty() && translucent.indices.is_empty() {
+            return None;
+        }
+
+        Some(ChunkMesh {
+            opaque,
+            translucent,
+            chunk_index: self.index,
+        })
+    }
+
+    /// Computes which faces of the chunk can see each other, for culling the chunks hidden behind others.
+    ///
+    /// Only opaque cubes hide what is behind them.
+    ///
+    /// ## Arguments
+    /// * `registered_voxels` - The registered voxels to look up the voxel shapes and opacities in.
+    pub fn compute_connectivity(
+        &self,
+        registered_voxels: &HashMap<u32, Voxel>,
+    ) -> FaceConnectivity {
+        let is_occluder = |voxel: Option<VoxelHandle>| {
+            voxel.is_some_and(|voxel| {
+                get_shape(registered_voxels, voxel) == (VoxelShape::Cube, ALL_FACES)
+            })
+        };
+
+        // Most chunks are either empty, filled with rock or without any opaque cubes, those do not have to be flood filled.
+        match self.voxels.get_uniform_value() {
+            Some(voxel) if is_occluder(voxel) => FaceConnectivity::NONE,
+            _ if !self
+                .voxels
+                .get_palette()
+                .iter()
+                .any(|voxel| is_occluder(*voxel)) =>
+            {
+                FaceConnectivity::ALL
+            }
+            _ => FaceConnectivity::compute(|position| is_occluder(self.sample(position))),
+        }
+    }
+
+    /// Builds the mesh data for a chunk that is completely filled with the opaque cube `voxel`.
+    ///
+    /// Only the six outer faces are visible, so this does not have to walk the voxels.
+    fn build_uniform_mesh_data(
+        voxel: VoxelHandle,
+        registered_voxels: &HashMap<u32, Voxel>,
+        neighbours: &ChunkNeighbours,
+    ) -> ChunkMeshData {
+        let mut mesh_data = ChunkMeshData::default();
+        let texture_index = registered_voxels[&voxel.id].get_texture_index();
+        let occupancy = PaddedOccupancy::new(|_, _| !0, neighbours);
+
+        for axis in 0..6 {
+            let face_dir = FaceDir::from_axis(axis);
+            let layer = chunk::border_layer(face_dir);
+            let faces = match neighbours.get(face_dir) {
+                Some(border) => border.map(|bits| !bits),
+                None => [!0; CHUNK_LENGTH],
+            };
+
+            // Faces can only be merged with faces that have the same lighting.
+            let mut slices: HashMap<FaceLighting, [BinaryVoxelContainer; CHUNK_LENGTH]> =
+                HashMap::new();
+            for (row, mut bits) in faces.into_iter().enumerate() {
+                while bits != 0 {
+                    let bit = bits.trailing_zeros() as usize;
+                    bits &= bits - 1;
+
+                    let lighting = FaceLighting {
+                        ambient_occlusion: occupancy.face_ambient_occlusion(
+                            face_dir,
+                            chunk::border_position(face_dir, layer, row, bit),
+                        ),
+                        light: neighbours.get_light_levels(face_dir, row, bit),
+                    };
+                    slices
+                        .entry(lighting)
+                        .or_insert([BinaryVoxelContainer::default(); CHUNK_LENGTH])[row] |=
+                        1 << bit;
+                }
+            }
+
+            for (lighting, mut slice) in slices {
+                common::chunk::mesh_slice(&mut slice)
+                    .into_iter()
+                    .for_each(|q| {
+                        q.append_to_vertices(
+                            &mut mesh_data.vertices,
+                            &mut mesh_data.indices,
+                            texture_index,
+                            face_dir,
+                            layer as i32,
+                            lighting,
+                        )
+                    });
+            }
+        }
+
+        mesh_data
+    }
+
+    /// Builds the opaque and the translucent mesh data for the chunk by greedy meshing every voxel.
+    ///
+    /// Faces are only culled against opaque voxels and against voxels of the same translucent type.
+    fn build_mesh_data(
+        &self,
+        registered_voxels: &HashMap<u32, Voxel>,
+        neighbours: &ChunkNeighbours,
+    ) -> (ChunkMeshData, ChunkMeshData) {
         const ONE: BinaryVoxelContainer = 1;
 
         let mut axis_cols = [[[BinaryVoxelContainer::default(); CHUNK_LENGTH]; CHUNK_LENGTH]; 3];
+        // The voxels that cover each side of their cell, in the column layout of the axis of the side.
+        let mut cover_cols = [[[BinaryVoxelContainer::default(); CHUNK_LENGTH]; CHUNK_LENGTH]; 6];
+        // The voxels that cover every side of their cell, those are the only ones that occlude light.
+        let mut occluder_cols = [[BinaryVoxelContainer::default(); CHUNK_LENGTH]; CHUNK_LENGTH];
         let mut col_face_masks =
             [[[BinaryVoxelContainer::default(); CHUNK_LENGTH]; CHUNK_LENGTH]; 6];
-
-        let mut add_voxel_to_axis_cols = |x: usize, y: usize, z: usize| {
-            axis_cols[0][z][x] |= ONE << y as BinaryVoxelContainer;
-            axis_cols[1][y][z] |= ONE << x as BinaryVoxelContainer;
-            axis_cols[2][y][x] |= ONE << z as BinaryVoxelContainer;
-        };
+        // Voxels that are not cubes get meshed one by one after the greedy meshing.
+        let mut shaped_voxels = vec![];
 
         for z in 0..CHUNK_LENGTH {
             for y in 0..CHUNK_LENGTH {
                 for x in 0..CHUNK_LENGTH {
+                    let position = (x, y, z);
                     // Can sample this without bound checks because it can never exceed it.
-                    if self.sample((x, y, z)).is_some() {
-                        add_voxel_to_axis_cols(x, y, z);
+                    let voxel = match self.sample(position) {
+                        Some(voxel) => voxel,
+                        None => continue,
+                    };
+
+                    let (shape, covered_faces) = get_shape(registered_voxels, voxel);
+                    if shape.is_cube() {
+                        for (axis, cols) in axis_cols.iter_mut().enumerate() {
+                            add_to_axis_cols(cols, axis, position);
+                        }
+                    } else {
+                        shaped_voxels.push((position, voxel));
+                    }
+
+                    for (face, cols) in cover_cols.iter_mut().enumerate() {
+                        if covered_faces & (1 << face) != 0 {
+                            add_to_axis_cols(cols, face / 2, position);
+                        }
+                    }
+                    if covered_faces == ALL_FACES {
+                        add_to_axis_cols(&mut occluder_cols, 1, position);
                     }
                 }
             }
         }
 
+        let occupancy = PaddedOccupancy::new(|y, z| occluder_cols[y][z], neighbours);
+
         for axis in 0..3 {
             for z in 0..CHUNK_LENGTH {
                 for x in 0..CHUNK_LENGTH {
                     let col = axis_cols[axis][z][x];
 
-                    col_face_masks[2 * axis][z][x] = col & !(col << 1);
-                    col_face_masks[2 * axis + 1][z][x] = col & !(col >> 1);
+                    // A face is hidden if the neighbour covers the side of its cell that faces it.
+                    col_face_masks[2 * axis][z][x] = col & !(cover_cols[2 * axis + 1][z][x] << 1);
+                    col_face_masks[2 * axis + 1][z][x] = col & !(cover_cols[2 * axis][z][x] >> 1);
+                }
+            }
+        }
+
+        // Cull the faces on the chunk borders that are covered by the neighbouring chunks.
+        for (axis, face_masks) in col_face_masks.iter_mut().enumerate() {
+            let face_dir = FaceDir::from_axis(axis);
+            if let Some(border) = neighbours.get(face_dir) {
+                let layer = chunk::border_layer(face_dir);
+                for (z, masks) in face_masks.iter_mut().enumerate() {
+                    for (x, mask) in masks.iter_mut().enumerate() {
+                        *mask &= !(((border[x] >> z) & ONE) << layer);
+                    }
                 }
             }
         }
 
-        let mut data: [HashMap<VoxelHandle, [[BinaryVoxelContainer; CHUNK_LENGTH]; CHUNK_LENGTH]>;
-            6] = [
+        // The faces are grouped by voxel, lighting and axis position,
+        // so only faces that look the same get merged.
+        let mut data: [FaceSlices; 6] = [
             HashMap::new(),
             HashMap::new(),
             HashMap::new(),
@@ -147,6 +667,7 @@ impl Chunk {
         ];
 
         for axis in 0..6 {
+            let face_dir = FaceDir::from_axis(axis);
             for z in 0..CHUNK_LENGTH {
                 for x in 0..CHUNK_LENGTH {
                     let mut col = col_face_masks[axis][z][x];
@@ -163,49 +684,443 @@ impl Chunk {
                         };
 
                         // Can sample this without bound checks because it can never exceed it.
-                        if let Some(voxel) = self.sample(voxel_pos) {
-                            data[axis].entry(*voxel).or_insert(
-                                [[BinaryVoxelContainer::default(); CHUNK_LENGTH]; CHUNK_LENGTH],
-                            )[y][x] |= ONE << z;
+                        let voxel = match self.sample(voxel_pos) {
+                            Some(voxel) => voxel,
+                            None => continue,
+                        };
+
+                        // Translucent voxels of the same type merge into one volume.
+                        let opacity = registered_voxels[&voxel.id].opacity;
+                        if opacity == VoxelOpacity::Translucent
+                            && self.try_sample(offset_position(voxel_pos, face_dir))
+                                == Some(Some(voxel))
+                        {
+                            continue;
                         }
+
+                        let lighting = FaceLighting {
+                            ambient_occlusion: occupancy
+                                .face_ambient_occlusion(face_dir, voxel_pos),
+                            light: self.get_face_light(neighbours, voxel_pos, face_dir),
+                        };
+                        data[axis]
+                            .entry((voxel, lighting, y))
+                            .or_insert([BinaryVoxelContainer::default(); CHUNK_LENGTH])[x] |=
+                            ONE << z;
                     }
                 }
             }
         }
 
-        let mut vertices = vec![];
-        let mut indices = vec![];
-        for (axis, voxels) in data.into_iter().enumerate() {
+        let mut opaque = ChunkMeshData::default();
+        let mut translucent = ChunkMeshData::default();
+        for (axis, slices) in data.into_iter().enumerate() {
             let face_dir = FaceDir::from_axis(axis);
 
-            for (voxel, slices) in voxels.into_iter() {
-                for (axis_pos, mut slice) in slices.into_iter().enumerate() {
-                    common::chunk::mesh_slice(&mut slice)
-                        .into_iter()
-                        .for_each(|q| {
-                            q.append_to_vertices(
-                                &mut vertices,
-                                &mut indices,
-                                voxel_registry.voxels[&voxel.id].get_texture_index(),
-                                face_dir,
-                                axis_pos as i32,
-                            )
-                        });
+            for ((voxel, lighting, axis_pos), mut slice) in slices.into_iter() {
+                let registered_voxel = &registered_voxels[&voxel.id];
+                let mesh_data = match registered_voxel.opacity {
+                    VoxelOpacity::Opaque | VoxelOpacity::Cutout => &mut opaque,
+                    VoxelOpacity::Translucent => &mut translucent,
+                };
+                common::chunk::mesh_slice(&mut slice)
+                    .into_iter()
+                    .for_each(|q| {
+                        q.append_to_vertices(
+                            &mut mesh_data.vertices,
+                            &mut mesh_data.indices,
+                            registered_voxel.get_texture_index(),
+                            face_dir,
+                            axis_pos as i32,
+                            lighting,
+                        )
+                    });
+            }
+        }
+
+        for (position, voxel) in shaped_voxels {
+            let registered_voxel = &registered_voxels[&voxel.id];
+            let mesh_data = match registered_voxel.opacity {
+                VoxelOpacity::Opaque | VoxelOpacity::Cutout => &mut opaque,
+                VoxelOpacity::Translucent => &mut translucent,
+            };
+            let (x, y, z) = position;
+            registered_voxel.shape.append_to_vertices(
+                &mut mesh_data.vertices,
+                &mut mesh_data.indices,
+                Vector3::new(x as f32, y as f32, z as f32),
+                registered_voxel.get_texture_index(),
+                self.get_light_levels(position),
+                |face_dir| self.is_side_covered(registered_voxels, neighbours, position, face_dir),
+            );
+        }
+
+        (opaque, translucent)
+    }
+
+    /// Returns the light levels in front of the face of the voxel at the chunk local position.
+    ///
+    /// Faces on the border of the chunk are lit by the neighbouring chunk, see `ChunkNeighbours::get_light_levels`.
+    fn get_face_light(
+        &self,
+        neighbours: &ChunkNeighbours,
+        position: (usize, usize, usize),
+        face_dir: FaceDir,
+    ) -> LightLevels {
+        let front = offset_position(position, face_dir);
+        match try_flatten_position(front) {
+            Some(_) => self.get_light_levels(front),
+            None => {
+                let (row, bit) = chunk::border_row_bit(face_dir, position);
+                neighbours.get_light_levels(face_dir, row, bit)
+            }
+        }
+    }
+
+    /// Returns true if the neighbour of the voxel at the chunk local position covers the side of its cell
+    /// in the face direction.
+    ///
+    /// Neighbours in unloaded chunks never cover the side.
+    fn is_side_covered(
+        &self,
+        registered_voxels: &HashMap<u32, Voxel>,
+        neighbours: &ChunkNeighbours,
+        position: (usize, usize, usize),
+        face_dir: FaceDir,
+    ) -> bool {
+        match self.try_sample(offset_position(position, face_dir)) {
+            Some(neighbour) => {
+                covers_face(registered_voxels, neighbour, chunk::opposite_face(face_dir))
+            }
+            None => neighbours.get(face_dir).is_some_and(|border| {
+                let (row, bit) = chunk::border_row_bit(face_dir, position);
+                (border[row] >> bit) & 1 != 0
+            }),
+        }
+    }
+}
+
+/// The slices of faces with the same voxel and lighting, keyed by those and the axis position.
+type FaceSlices = HashMap<(VoxelHandle, FaceLighting, usize), [BinaryVoxelContainer; CHUNK_LENGTH]>;
+
+/// The side length of a chunk with one voxel of padding on each side.
+const PADDED_LENGTH: usize = CHUNK_LENGTH + 2;
+
+/// The solid voxels of a chunk and of the neighbouring border layers, used for ambient occlusion.
+///
+/// Only the neighbours that share a face with the chunk are known,
+/// so the edges and corners of the padding are always empty.
+struct PaddedOccupancy {
+    /// Indexed by z and y with one voxel of padding, the bits are x with one bit of padding.
+    rows: Box<[[u128; PADDED_LENGTH]; PADDED_LENGTH]>,
+}
+
+impl PaddedOccupancy {
+    /// Creates a new `PaddedOccupancy`.
+    ///
+    /// ## Arguments
+    /// * `rows` - Returns the solid voxels of the chunk at the specified y and z, the bits are x.
+    /// * `neighbours` - The borders of the loaded neighbouring chunks.
+    fn new(
+        rows: impl Fn(usize, usize) -> BinaryVoxelContainer,
+        neighbours: &ChunkNeighbours,
+    ) -> Self {
+        let mut occupancy = Self {
+            rows: Box::new([[0; PADDED_LENGTH]; PADDED_LENGTH]),
+        };
+
+        for z in 0..CHUNK_LENGTH {
+            for y in 0..CHUNK_LENGTH {
+                occupancy.rows[z + 1][y + 1] = (rows(y, z) as u128) << 1;
+            }
+        }
+
+        for axis in 0..6 {
+            let face_dir = FaceDir::from_axis(axis);
+            let border = match neighbours.get(face_dir) {
+                Some(border) => border,
+                None => continue,
+            };
+            let layer = chunk::border_layer(face_dir);
+            for (row, bits) in border.iter().enumerate() {
+                for bit in 0..CHUNK_LENGTH {
+                    if (bits >> bit) & 1 == 0 {
+                        continue;
+                    }
+                    let (x, y, z) = chunk::border_position(face_dir, layer, row, bit);
+                    let position = Vector3::new(x as i32, y as i32, z as i32)
+                        + chunk::neighbour_offset(face_dir);
+                    let padded = position.map(|c| (c + 1) as usize);
+                    occupancy.rows[padded.z][padded.y] |= 1 << padded.x;
                 }
             }
         }
 
-        Geometry::new_instanced(
-            &render_context.device,
-            &vertices,
-            &[Instance {
-                model_matrix: Matrix4::new_translation(
-                    &self.index.map(|c| c as f32 * chunk::CHUNK_LENGTH as f32),
-                )
-                .into(),
-            }],
-            &indices,
-            index::INDEX_FORMAT,
+        occupancy
+    }
+
+    /// Returns true if the voxel at the chunk local position is solid.
+    ///
+    /// Positions outside of the padding are never solid.
+    fn is_solid(&self, position: Vector3<i32>) -> bool {
+        let range = -1..=CHUNK_LENGTHI32;
+        if !range.contains(&position.x)
+            || !range.contains(&position.y)
+            || !range.contains(&position.z)
+        {
+            return false;
+        }
+        let padded = position.map(|c| (c + 1) as usize);
+        (self.rows[padded.z][padded.y] >> padded.x) & 1 != 0
+    }
+
+    /// Computes the ambient occlusion of a face of the voxel at the chunk local position.
+    fn face_ambient_occlusion(
+        &self,
+        face_dir: FaceDir,
+        (x, y, z): (usize, usize, usize),
+    ) -> [u8; 4] {
+        chunk::face_ambient_occlusion(
+            face_dir,
+            Vector3::new(x as i32, y as i32, z as i32),
+            |position| self.is_solid(position),
         )
     }
 }
+
+/// The CPU side mesh data of a chunk.
+pub struct ChunkMesh {
+    /// The faces of the opaque and cutout voxels, rendered into the `GBuffer`.
+    pub opaque: ChunkMeshData,
+    /// The faces of the translucent voxels, blended on top of the lit scene.
+    pub translucent: ChunkMeshData,
+    chunk_index: Vector3<i32>,
+}
+
+impl ChunkMesh {
+    /// Returns the instance that places the mesh at its chunk.
+    pub fn create_instance(&self) -> Instance {
+        Instance {
+            model_matrix: Matrix4::new_translation(
+                &self
+                    .chunk_index
+                    .map(|c| c as f32 * chunk::CHUNK_LENGTH as f32),
+            )
+            .into(),
+        }
+    }
+
+    /// Returns the box around the chunk in world space.
+    pub fn get_bounds(&self) -> Aabb {
+        Aabb::from_chunk_index(self.chunk_index)
+    }
+}
+
+/// The vertices and indices of a part of a chunk mesh.
+#[derive(Default)]
+pub struct ChunkMeshData {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<Index>,
+}
+
+impl ChunkMeshData {
+    /// Returns true if there are no faces to render.
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+}
+
+/// The borders of the loaded chunks around a chunk.
+#[derive(Default)]
+pub struct ChunkNeighbours {
+    /// The opaque voxels of the borders facing the chunk, indexed by the face direction the neighbour is in.
+    borders: [Option<ChunkBorder>; 6],
+    /// The block light levels of the borders facing the chunk, indexed the same way as `borders`.
+    block_light_borders: [Option<Box<LightBorder>>; 6],
+    /// The sky light levels of the borders facing the chunk, indexed the same way as `borders`.
+    sky_light_borders: [Option<Box<LightBorder>>; 6],
+}
+
+impl ChunkNeighbours {
+    /// Returns the border of the neighbour in the specified face direction, if it is loaded.
+    pub fn get(&self, face_dir: FaceDir) -> Option<&ChunkBorder> {
+        self.borders[face_dir as usize].as_ref()
+    }
+
+    /// Returns the light levels in the border of the neighbour in the specified face direction.
+    ///
+    /// The row and the bit are laid out the same way as in the `ChunkBorder`.
+    /// Unloaded neighbours have no block light and are open to the sky.
+    pub fn get_light_levels(&self, face_dir: FaceDir, row: usize, bit: usize) -> LightLevels {
+        let get_level = |borders: &[Option<Box<LightBorder>>; 6], default| {
+            borders[face_dir as usize]
+                .as_ref()
+                .map_or(default, |border| border[row][bit])
+        };
+        LightLevels {
+            block: get_level(&self.block_light_borders, 0),
+            sky: get_level(&self.sky_light_borders, light::MAX_LIGHT_LEVEL),
+        }
+    }
+
+    /// Sets the border of the neighbour in the specified face direction.
+    ///
+    /// ## Arguments
+    /// * `face_dir` - The direction the neighbour is in.
+    /// * `neighbour` - The neighbouring chunk.
+    /// * `registered_voxels` - The registered voxels to look up the voxel opacities in.
+    pub fn set(
+        &mut self,
+        face_dir: FaceDir,
+        neighbour: &Chunk,
+        registered_voxels: &HashMap<u32, Voxel>,
+    ) {
+        let neighbour_face = chunk::opposite_face(face_dir);
+        self.borders[face_dir as usize] =
+            Some(neighbour.get_border(neighbour_face, registered_voxels));
+        self.block_light_borders[face_dir as usize] =
+            Some(neighbour.get_light_border(LightChannel::Block, neighbour_face));
+        self.sky_light_borders[face_dir as usize] =
+            Some(neighbour.get_light_border(LightChannel::Sky, neighbour_face));
+    }
+}
+
+/// A mutable reference to a single voxel of a chunk.
+///
+/// Because the voxels are bit-packed, the value is written back into the chunk on drop.
+pub struct VoxelMut<'a> {
+    chunk: &'a mut Chunk,
+    index: usize,
+    value: Option<VoxelHandle>,
+}
+
+impl<'a> VoxelMut<'a> {
+    fn new(chunk: &'a mut Chunk, index: usize) -> Self {
+        let value = chunk.voxels.get(index);
+        Self {
+            chunk,
+            index,
+            value,
+        }
+    }
+}
+
+impl Deref for VoxelMut<'_> {
+    type Target = Option<VoxelHandle>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.value
+    }
+}
+
+impl DerefMut for VoxelMut<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.value
+    }
+}
+
+impl Drop for VoxelMut<'_> {
+    fn drop(&mut self) {
+        if self.chunk.voxels.get(self.index) != self.value {
+            self.chunk.voxels.set(self.index, self.value);
+            mark_dirty_borders(&mut self.chunk.dirty_borders, self.index);
+            self.chunk.unsaved = true;
+            self.chunk
+                .changed_voxels
+                .push(unflatten_position(self.index));
+            // Edited fluid voxels become sources.
+            self.chunk.fluid_levels.set(self.index, 0);
+            self.chunk
+                .fluid_updates
+                .push(unflatten_position(self.index));
+        }
+    }
+}
+
+/// A bitmask of every face direction.
+const ALL_FACES: u8 = 0b11_1111;
+
+/// Returns the shape of a voxel and a bitmask of the sides of its cell that it covers, indexed by face direction.
+///
+/// Only opaque voxels cover the sides of their cell.
+/// Voxels that are missing from the registry are treated as opaque cubes.
+fn get_shape(registered_voxels: &HashMap<u32, Voxel>, voxel: VoxelHandle) -> (VoxelShape, u8) {
+    match registered_voxels.get(&voxel.id) {
+        Some(registered_voxel) if registered_voxel.opacity.is_opaque() => {
+            let shape = registered_voxel.shape;
+            let covered_faces = (0..6)
+                .filter(|axis| shape.covers_face(FaceDir::from_axis(*axis)))
+                .fold(0, |mask, axis| mask | 1 << axis);
+            (shape, covered_faces)
+        }
+        Some(registered_voxel) => (registered_voxel.shape, 0),
+        None => (VoxelShape::Cube, ALL_FACES),
+    }
+}
+
+/// Returns true if the voxel is present and covers the side of its cell in the face direction,
+/// which hides the face of the neighbouring voxel on that side.
+fn covers_face(
+    registered_voxels: &HashMap<u32, Voxel>,
+    voxel: Option<VoxelHandle>,
+    face_dir: FaceDir,
+) -> bool {
+    voxel.is_some_and(|voxel| get_shape(registered_voxels, voxel).1 & (1 << face_dir as u8) != 0)
+}
+
+/// Sets the bit of the voxel at the chunk local position in the binary columns along an axis.
+///
+/// The columns along y are indexed by z and x, along x by y and z and along z by y and x.
+fn add_to_axis_cols(
+    cols: &mut [[BinaryVoxelContainer; CHUNK_LENGTH]; CHUNK_LENGTH],
+    axis: usize,
+    (x, y, z): (usize, usize, usize),
+) {
+    const ONE: BinaryVoxelContainer = 1;
+    match axis {
+        0 => cols[z][x] |= ONE << y,
+        1 => cols[y][z] |= ONE << x,
+        _ => cols[y][x] |= ONE << z,
+    }
+}
+
+/// Returns the chunk local position next to the specified one in the face direction.
+///
+/// Positions outside of the chunk wrap around to out of bounds values.
+fn offset_position(position: (usize, usize, usize), face_dir: FaceDir) -> (usize, usize, usize) {
+    let offset = chunk::neighbour_offset(face_dir);
+    (
+        position.0.wrapping_add_signed(offset.x as isize),
+        position.1.wrapping_add_signed(offset.y as isize),
+        position.2.wrapping_add_signed(offset.z as isize),
+    )
+}
+
+/// Converts a local voxel position to an index into the voxel storage.
+fn flatten_position((x, y, z): (usize, usize, usize)) -> usize {
+    x + y * CHUNK_LENGTH + z * CHUNK_LENGTH * CHUNK_LENGTH
+}
+
+/// Converts an index into the voxel storage to a local voxel position.
+fn unflatten_position(index: usize) -> (usize, usize, usize) {
+    (
+        index % CHUNK_LENGTH,
+        index / CHUNK_LENGTH % CHUNK_LENGTH,
+        index / (CHUNK_LENGTH * CHUNK_LENGTH),
+    )
+}
+
+/// Marks the borders that the voxel at the specified storage index lies on as dirty.
+fn mark_dirty_borders(dirty_borders: &mut [bool; 6], index: usize) {
+    let (x, y, z) = unflatten_position(index);
+    for (axis, c) in [y, x, z].into_iter().enumerate() {
+        dirty_borders[2 * axis] |= c == 0;
+        dirty_borders[2 * axis + 1] |= c == CHUNK_LENGTH - 1;
+    }
+}
+
+/// Converts a local voxel position to an index into the voxel storage, if it is in bounds.
+fn try_flatten_position(position: (usize, usize, usize)) -> Option<usize> {
+    let (x, y, z) = position;
+    (x < CHUNK_LENGTH && y < CHUNK_LENGTH && z < CHUNK_LENGTH).then(|| flatten_position(position))
+}
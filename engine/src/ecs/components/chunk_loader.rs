@@ -0,0 +1,60 @@
+use bevy_ecs::component::Component;
+use nalgebra::{Point3, Vector3};
+
+use crate::common::chunk;
+
+/// Keeps the chunks around its position loaded.
+#[derive(Component, Clone, Debug)]
+pub struct ChunkLoader {
+    /// The position of the loader in world space.
+    pub position: Point3<f32>,
+    /// How many chunks are loaded around the loader on the x and z axes.
+    pub horizontal_render_distance: u32,
+    /// How many chunks are loaded above and below the loader.
+    pub vertical_render_distance: u32,
+}
+
+impl Default for ChunkLoader {
+    fn default() -> Self {
+        Self {
+            position: Point3::origin(),
+            horizontal_render_distance: 8,
+            vertical_render_distance: 4,
+        }
+    }
+}
+
+impl ChunkLoader {
+    /// Returns the index of the chunk the loader is in.
+    pub fn get_chunk_index(&self) -> Vector3<i32> {
+        chunk::world_to_chunk_index(self.position.coords.map(|c| c.floor() as i32))
+    }
+
+    /// Returns true if the chunk with the specified index is within the render distance.
+    ///
+    /// ## Arguments
+    /// * `index` - The index of the chunk.
+    /// * `margin` - Extra chunks added to both render distances.
+    pub fn is_in_range(&self, index: Vector3<i32>, margin: u32) -> bool {
+        let offset = index - self.get_chunk_index();
+        let horizontal = (self.horizontal_render_distance + margin) as i32;
+        let vertical = (self.vertical_render_distance + margin) as i32;
+        offset.x * offset.x + offset.z * offset.z <= horizontal * horizontal
+            && offset.y.abs() <= vertical
+    }
+
+    /// Returns the indices of all the chunks within the render distance.
+    pub fn get_chunks_in_range(&self) -> impl Iterator<Item = Vector3<i32>> + '_ {
+        let center = self.get_chunk_index();
+        let horizontal = self.horizontal_render_distance as i32;
+        let vertical = self.vertical_render_distance as i32;
+        (-horizontal..=horizontal)
+            .flat_map(move |x| {
+                (-vertical..=vertical).flat_map(move |y| {
+                    (-horizontal..=horizontal).map(move |z| Vector3::new(x, y, z))
+                })
+            })
+            .map(move |offset| center + offset)
+            .filter(|index| self.is_in_range(*index, 0))
+    }
+}
@@ -0,0 +1,12 @@
+use bevy_ecs::component::Component;
+
+/// The lifecycle state of a chunk.
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ChunkState {
+    /// The chunk is waiting for its voxels to be generated.
+    Queued,
+    /// The voxels of the chunk have been generated, but it has not been meshed yet.
+    Generated,
+    /// The chunk has been meshed at least once.
+    Meshed,
+}
@@ -2,5 +2,11 @@ mod geometry;
 pub use geometry::Geometry;
 mod render_descriptor;
 pub use render_descriptor::RenderDescriptor;
+mod render_bounds;
+pub use render_bounds::RenderBounds;
 mod chunk;
-pub use chunk::Chunk;
+pub use chunk::{Chunk, ChunkMesh, ChunkMeshData, ChunkMeshSnapshot, ChunkNeighbours, VoxelMut};
+mod chunk_loader;
+pub use chunk_loader::ChunkLoader;
+mod chunk_state;
+pub use chunk_state::ChunkState;
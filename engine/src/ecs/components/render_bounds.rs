@@ -0,0 +1,10 @@
+use bevy_ecs::component::Component;
+
+use crate::common::aabb::Aabb;
+
+/// The box around all of the instances of an entity's geometry in world space.
+///
+/// Geometry with bounds is not rendered while the box is outside of the view of the camera,
+/// geometry without bounds is always rendered.
+#[derive(Component, Clone, Debug, PartialEq)]
+pub struct RenderBounds(pub Aabb);
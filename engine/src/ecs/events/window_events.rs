@@ -1,9 +1,9 @@
 use bevy_ecs::{event::Event, world::World};
 use nalgebra::{vector, Vector2};
-pub use winit::event::{MouseScrollDelta, WindowEvent as WinitWindowEvent};
+pub use winit::event::{ElementState, MouseScrollDelta, WindowEvent as WinitWindowEvent};
 use winit::{
     dpi::{PhysicalPosition, PhysicalSize},
-    event::{ElementState, KeyEvent, MouseButton},
+    event::{KeyEvent, MouseButton},
     keyboard::PhysicalKey,
 };
 
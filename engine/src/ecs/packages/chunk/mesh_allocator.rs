@@ -0,0 +1,514 @@
+use std::{collections::HashMap, mem, ops::Range};
+
+use bevy_ecs::{entity::Entity, system::Resource};
+use wgpu::{
+    util::DrawIndexedIndirectArgs, Buffer, BufferAddress, BufferDescriptor, BufferUsages,
+    CommandEncoderDescriptor, Device, Features, Queue, RenderPass, COPY_BUFFER_ALIGNMENT,
+};
+
+use crate::{
+    common::aabb::Aabb,
+    ecs::{
+        components::{ChunkMesh, ChunkMeshData},
+        resources::Camera,
+    },
+    rendering::{
+        free_list::FreeList,
+        index::{self, Index},
+        instance::Instance,
+        vertex::Vertex,
+    },
+};
+
+/// The amount of vertices the shared vertex buffer can hold before it has to grow.
+const INITIAL_VERTEX_CAPACITY: u32 = 1 << 20;
+/// The amount of indices the shared index buffer can hold before it has to grow.
+const INITIAL_INDEX_CAPACITY: u32 = 3 << 19;
+/// The amount of chunks the shared instance buffer can hold before it has to grow.
+const INITIAL_INSTANCE_CAPACITY: u32 = 1 << 10;
+/// The amount of draws the indirect buffer can hold before it has to grow.
+const INITIAL_DRAW_CAPACITY: usize = 1 << 10;
+/// The size of a single draw in the indirect buffer.
+const DRAW_SIZE: BufferAddress = mem::size_of::<DrawIndexedIndirectArgs>() as BufferAddress;
+
+/// Stores the meshes of all the chunks inside of a few large GPU buffers.
+///
+/// Every chunk gets a range of the shared vertex and index buffers for each of its meshes and
+/// a slot in the shared instance buffer. The visible chunks are then drawn with a single indirect
+/// draw per pass if the GPU supports it, instead of binding the buffers of every chunk separately.
+#[derive(Resource)]
+pub struct ChunkMeshAllocator {
+    vertices: SharedBuffer,
+    indices: SharedBuffer,
+    instances: SharedBuffer,
+    chunks: HashMap<Entity, ChunkAllocation>,
+    /// The draws of the current frame, the opaque draws followed by the translucent draws.
+    draws: Vec<DrawIndexedIndirectArgs>,
+    opaque_draw_count: usize,
+    /// The amount of chunks with a mesh that are hidden or outside of the view of the camera in the current frame.
+    culled_count: usize,
+    indirect_buffer: Buffer,
+    indirect_capacity: usize,
+    draw_mode: DrawMode,
+}
+
+impl ChunkMeshAllocator {
+    /// Creates a new `ChunkMeshAllocator`, the draw mode is picked based on the features of the device.
+    pub fn new(device: &Device) -> Self {
+        let features = device.features();
+        let draw_mode = if !features.contains(Features::INDIRECT_FIRST_INSTANCE) {
+            DrawMode::Direct
+        } else if features.contains(Features::MULTI_DRAW_INDIRECT) {
+            DrawMode::MultiIndirect
+        } else {
+            DrawMode::Indirect
+        };
+
+        Self {
+            vertices: SharedBuffer::new(
+                device,
+                "buffer_vertex_chunks",
+                BufferUsages::VERTEX,
+                mem::size_of::<Vertex>() as BufferAddress,
+                INITIAL_VERTEX_CAPACITY,
+            ),
+            indices: SharedBuffer::new(
+                device,
+                "buffer_index_chunks",
+                BufferUsages::INDEX,
+                mem::size_of::<Index>() as BufferAddress,
+                INITIAL_INDEX_CAPACITY,
+            ),
+            instances: SharedBuffer::new(
+                device,
+                "buffer_instance_chunks",
+                BufferUsages::VERTEX,
+                mem::size_of::<Instance>() as BufferAddress,
+                INITIAL_INSTANCE_CAPACITY,
+            ),
+            chunks: HashMap::new(),
+            draws: vec![],
+            opaque_draw_count: 0,
+            culled_count: 0,
+            indirect_buffer: create_indirect_buffer(device, INITIAL_DRAW_CAPACITY),
+            indirect_capacity: INITIAL_DRAW_CAPACITY,
+            draw_mode,
+        }
+    }
+
+    /// Uploads the mesh of a chunk, replacing the previous mesh of the chunk.
+    ///
+    /// Meshes that do not fit into the buffers anymore are logged and not drawn.
+    ///
+    /// ## Arguments
+    /// * `device` - The device to grow the buffers with.
+    /// * `queue` - The queue to upload the mesh with.
+    /// * `entity` - The chunk entity.
+    /// * `mesh` - The mesh of the chunk.
+    pub fn insert(&mut self, device: &Device, queue: &Queue, entity: Entity, mesh: &ChunkMesh) {
+        self.remove(entity);
+        if mesh.opaque.is_empty() && mesh.translucent.is_empty() {
+            return;
+        }
+
+        let instance = match self.instances.allocate(device, queue, 1) {
+            Some(instance) => instance,
+            None => return,
+        };
+        self.instances
+            .write(queue, instance, bytemuck::bytes_of(&mesh.create_instance()));
+
+        let opaque = self.allocate_mesh(device, queue, &mesh.opaque);
+        let translucent = self.allocate_mesh(device, queue, &mesh.translucent);
+        self.chunks.insert(
+            entity,
+            ChunkAllocation {
+                instance,
+                opaque,
+                translucent,
+                bounds: mesh.get_bounds(),
+            },
+        );
+    }
+
+    /// Frees the mesh of a chunk, if it has one.
+    pub fn remove(&mut self, entity: Entity) {
+        let chunk = match self.chunks.remove(&entity) {
+            Some(chunk) => chunk,
+            None => return,
+        };
+
+        self.instances.free(chunk.instance, 1);
+        for mesh in [chunk.opaque, chunk.translucent].into_iter().flatten() {
+            self.vertices.free(mesh.vertex_offset, mesh.vertex_count);
+            self.indices
+                .free(mesh.index_offset, padded_index_count(mesh.index_count));
+        }
+    }
+
+    /// Builds the draws of the chunks that the camera can see and uploads them to the indirect buffer.
+    ///
+    /// The opaque meshes are drawn nearest first, so the depth test rejects more of the hidden fragments.
+    /// The translucent meshes are drawn farthest first, because blending only works if the farthest geometry gets drawn first.
+    ///
+    /// ## Arguments
+    /// * `device` - The device to grow the indirect buffer with.
+    /// * `queue` - The queue to upload the draws with.
+    /// * `camera` - The camera to cull the chunks with and to sort them by.
+    /// * `is_visible` - Returns false for the chunks that are hidden behind other chunks.
+    pub fn prepare_draws(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        camera: &Camera,
+        is_visible: impl Fn(Entity) -> bool,
+    ) {
+        let camera_position = camera.get_position();
+        let distance =
+            |chunk: &ChunkAllocation| (chunk.bounds.get_center() - camera_position).norm_squared();
+        let visible = self
+            .chunks
+            .iter()
+            .filter(|(entity, chunk)| {
+                is_visible(**entity) && camera.get_frustum().intersects_aabb(&chunk.bounds)
+            })
+            .map(|(_, chunk)| chunk)
+            .collect::<Vec<_>>();
+        self.culled_count = self.chunks.len() - visible.len();
+
+        let mut opaque = visible
+            .iter()
+            .filter_map(|chunk| Some((distance(chunk), chunk.opaque?.get_draw(chunk.instance))))
+            .collect::<Vec<_>>();
+        opaque.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+        let mut translucent = visible
+            .iter()
+            .filter_map(|chunk| {
+                Some((distance(chunk), chunk.translucent?.get_draw(chunk.instance)))
+            })
+            .collect::<Vec<_>>();
+        translucent.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+
+        self.opaque_draw_count = opaque.len();
+        self.draws.clear();
+        self.draws
+            .extend(opaque.into_iter().chain(translucent).map(|(_, draw)| draw));
+
+        if self.draw_mode == DrawMode::Direct || self.draws.is_empty() {
+            return;
+        }
+        if self.draws.len() > self.indirect_capacity {
+            self.indirect_capacity = self.draws.len().next_power_of_two();
+            self.indirect_buffer = create_indirect_buffer(device, self.indirect_capacity);
+        }
+        let bytes = self
+            .draws
+            .iter()
+            .flat_map(|draw| draw.as_bytes())
+            .copied()
+            .collect::<Vec<_>>();
+        queue.write_buffer(&self.indirect_buffer, 0, &bytes);
+    }
+
+    /// Draws the opaque meshes that were prepared for the current frame.
+    ///
+    /// Note: This function assumes that the pipeline and the bind groups are already set.
+    pub fn render_opaque<'rp, 's: 'rp>(&'s self, render_pass: &mut RenderPass<'rp>) {
+        self.render_draws(render_pass, 0..self.opaque_draw_count);
+    }
+
+    /// Draws the translucent meshes that were prepared for the current frame.
+    ///
+    /// Note: This function assumes that the pipeline and the bind groups are already set.
+    pub fn render_translucent<'rp, 's: 'rp>(&'s self, render_pass: &mut RenderPass<'rp>) {
+        self.render_draws(render_pass, self.opaque_draw_count..self.draws.len());
+    }
+
+    /// Returns the amount of draws that were prepared for the current frame.
+    pub fn draw_count(&self) -> usize {
+        self.draws.len()
+    }
+
+    /// Returns the amount of chunks with a mesh that were culled in the current frame.
+    pub fn culled_count(&self) -> usize {
+        self.culled_count
+    }
+
+    /// Returns the name of the way the chunks are drawn.
+    pub fn draw_mode_name(&self) -> &'static str {
+        match self.draw_mode {
+            DrawMode::MultiIndirect => "multi draw indirect",
+            DrawMode::Indirect => "draw indirect",
+            DrawMode::Direct => "direct",
+        }
+    }
+
+    /// Returns the amount of bytes that are used by meshes and the total size of the shared buffers.
+    pub fn memory_usage(&self) -> (BufferAddress, BufferAddress) {
+        [&self.vertices, &self.indices, &self.instances]
+            .into_iter()
+            .fold((0, 0), |(used, size), buffer| {
+                (used + buffer.used_size(), size + buffer.buffer.size())
+            })
+    }
+
+    /// Allocates and uploads a part of a chunk mesh.
+    ///
+    /// ## Returns
+    /// `None` if the mesh is empty or does not fit into the buffers.
+    fn allocate_mesh(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        mesh: &ChunkMeshData,
+    ) -> Option<MeshAllocation> {
+        if mesh.is_empty() {
+            return None;
+        }
+
+        let vertex_count = mesh.vertices.len() as u32;
+        let index_count = mesh.indices.len() as u32;
+        let vertex_offset = self.vertices.allocate(device, queue, vertex_count)?;
+        let index_offset =
+            match self
+                .indices
+                .allocate(device, queue, padded_index_count(index_count))
+            {
+                Some(index_offset) => index_offset,
+                None => {
+                    self.vertices.free(vertex_offset, vertex_count);
+                    return None;
+                }
+            };
+
+        self.vertices
+            .write(queue, vertex_offset, bytemuck::cast_slice(&mesh.vertices));
+        if index_count == padded_index_count(index_count) {
+            self.indices
+                .write(queue, index_offset, bytemuck::cast_slice(&mesh.indices));
+        } else {
+            let mut indices = mesh.indices.clone();
+            indices.resize(padded_index_count(index_count) as usize, 0);
+            self.indices
+                .write(queue, index_offset, bytemuck::cast_slice(&indices));
+        }
+
+        Some(MeshAllocation {
+            vertex_offset,
+            vertex_count,
+            index_offset,
+            index_count,
+        })
+    }
+
+    /// Draws a range of the prepared draws.
+    fn render_draws<'rp, 's: 'rp>(
+        &'s self,
+        render_pass: &mut RenderPass<'rp>,
+        draws: Range<usize>,
+    ) {
+        if draws.is_empty() {
+            return;
+        }
+
+        render_pass.set_vertex_buffer(0, self.vertices.buffer.slice(..));
+        render_pass.set_vertex_buffer(1, self.instances.buffer.slice(..));
+        render_pass.set_index_buffer(self.indices.buffer.slice(..), index::INDEX_FORMAT);
+
+        match self.draw_mode {
+            DrawMode::MultiIndirect => render_pass.multi_draw_indexed_indirect(
+                &self.indirect_buffer,
+                draws.start as BufferAddress * DRAW_SIZE,
+                draws.len() as u32,
+            ),
+            DrawMode::Indirect => {
+                for draw in draws {
+                    render_pass.draw_indexed_indirect(
+                        &self.indirect_buffer,
+                        draw as BufferAddress * DRAW_SIZE,
+                    );
+                }
+            }
+            DrawMode::Direct => {
+                for draw in &self.draws[draws] {
+                    render_pass.draw_indexed(
+                        draw.first_index..draw.first_index + draw.index_count,
+                        draw.base_vertex,
+                        draw.first_instance..draw.first_instance + 1,
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// How the chunks are drawn.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum DrawMode {
+    /// All the chunks of a pass are drawn with a single `multi_draw_indexed_indirect`.
+    MultiIndirect,
+    /// Every chunk is drawn with a `draw_indexed_indirect`.
+    Indirect,
+    /// Every chunk is drawn with a `draw_indexed`, for devices where indirect draws can not pick the instance.
+    Direct,
+}
+
+/// The ranges of the shared buffers that belong to a chunk.
+struct ChunkAllocation {
+    instance: u32,
+    opaque: Option<MeshAllocation>,
+    translucent: Option<MeshAllocation>,
+    bounds: Aabb,
+}
+
+/// The ranges of the shared buffers that belong to a part of a chunk mesh.
+#[derive(Clone, Copy)]
+struct MeshAllocation {
+    vertex_offset: u32,
+    vertex_count: u32,
+    index_offset: u32,
+    index_count: u32,
+}
+
+impl MeshAllocation {
+    /// Returns the draw of the mesh with the instance of its chunk.
+    fn get_draw(&self, instance: u32) -> DrawIndexedIndirectArgs {
+        DrawIndexedIndirectArgs {
+            index_count: self.index_count,
+            instance_count: 1,
+            first_index: self.index_offset,
+            base_vertex: self.vertex_offset as i32,
+            first_instance: instance,
+        }
+    }
+}
+
+/// A GPU buffer whose elements are handed out by a `FreeList`.
+///
+/// The buffer doubles in size when it runs out of space, up to the largest buffer the device supports.
+struct SharedBuffer {
+    label: &'static str,
+    usage: BufferUsages,
+    element_size: BufferAddress,
+    buffer: Buffer,
+    free_list: FreeList,
+}
+
+impl SharedBuffer {
+    /// Creates a new `SharedBuffer` that can hold `capacity` elements.
+    fn new(
+        device: &Device,
+        label: &'static str,
+        usage: BufferUsages,
+        element_size: BufferAddress,
+        capacity: u32,
+    ) -> Self {
+        let usage = usage | BufferUsages::COPY_DST | BufferUsages::COPY_SRC;
+        Self {
+            label,
+            usage,
+            element_size,
+            buffer: create_buffer(
+                device,
+                label,
+                usage,
+                element_size * capacity as BufferAddress,
+            ),
+            free_list: FreeList::new(capacity),
+        }
+    }
+
+    /// Allocates `len` elements, growing the buffer if there is no free range that is large enough.
+    ///
+    /// ## Returns
+    /// The offset of the elements, or `None` if the buffer can not grow any further.
+    fn allocate(&mut self, device: &Device, queue: &Queue, len: u32) -> Option<u32> {
+        let max_capacity = (device.limits().max_buffer_size / self.element_size)
+            .min(u32::MAX as BufferAddress) as u32;
+
+        loop {
+            if let Some(offset) = self.free_list.allocate(len) {
+                return Some(offset);
+            }
+
+            let capacity = self
+                .free_list
+                .capacity()
+                .saturating_mul(2)
+                .max(len)
+                .min(max_capacity);
+            if capacity <= self.free_list.capacity() {
+                log::error!("Failed to allocate {len} elements in {}", self.label);
+                return None;
+            }
+            self.grow(device, queue, capacity);
+        }
+    }
+
+    /// Frees elements that have been returned by `allocate`.
+    fn free(&mut self, offset: u32, len: u32) {
+        self.free_list.free(offset, len);
+    }
+
+    /// Writes the data to the elements starting at the offset, its size has to be a multiple of 4 bytes.
+    fn write(&self, queue: &Queue, offset: u32, data: &[u8]) {
+        queue.write_buffer(
+            &self.buffer,
+            offset as BufferAddress * self.element_size,
+            data,
+        );
+    }
+
+    /// Returns the amount of bytes that are allocated.
+    fn used_size(&self) -> BufferAddress {
+        self.free_list.used() as BufferAddress * self.element_size
+    }
+
+    /// Replaces the buffer with a larger one and copies the old elements into it.
+    fn grow(&mut self, device: &Device, queue: &Queue, capacity: u32) {
+        let buffer = create_buffer(
+            device,
+            self.label,
+            self.usage,
+            self.element_size * capacity as BufferAddress,
+        );
+
+        // Writes to the old buffer are applied before the copy, because they get submitted first.
+        let mut command_encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("command_encoder_grow_buffer"),
+        });
+        command_encoder.copy_buffer_to_buffer(&self.buffer, 0, &buffer, 0, self.buffer.size());
+        queue.submit(Some(command_encoder.finish()));
+
+        self.buffer = buffer;
+        self.free_list.grow(capacity);
+    }
+}
+
+/// Returns the amount of indices that are allocated for a mesh.
+///
+/// Writes to buffers have to be aligned to `wgpu::COPY_BUFFER_ALIGNMENT` bytes, so the indices are padded up to it.
+fn padded_index_count(index_count: u32) -> u32 {
+    let indices_per_alignment = (COPY_BUFFER_ALIGNMENT as usize / mem::size_of::<Index>()).max(1);
+    index_count.next_multiple_of(indices_per_alignment as u32)
+}
+
+/// Creates a buffer of the specified size in bytes.
+fn create_buffer(device: &Device, label: &str, usage: BufferUsages, size: BufferAddress) -> Buffer {
+    device.create_buffer(&BufferDescriptor {
+        label: Some(label),
+        size,
+        usage,
+        mapped_at_creation: false,
+    })
+}
+
+/// Creates an indirect buffer that can hold `capacity` draws.
+fn create_indirect_buffer(device: &Device, capacity: usize) -> Buffer {
+    device.create_buffer(&BufferDescriptor {
+        label: Some("buffer_indirect_chunks"),
+        size: capacity as BufferAddress * DRAW_SIZE,
+        usage: BufferUsages::INDIRECT | BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
@@ -1,64 +1,502 @@
-use std::time::Instant;
+use std::sync::Arc;
 
 use bevy_ecs::{
+    change_detection::{DetectChanges as _, DetectChangesMut as _},
     entity::Entity,
-    query::Changed,
-    // schedule::IntoSystemConfigs as _,
-    system::{ParallelCommands, Query, Res},
+    query::{Added, Changed, With},
+    removal_detection::RemovedComponents,
+    schedule::{common_conditions::resource_exists, IntoSystemConfigs as _},
+    system::{Commands, NonSend, Query, Res, ResMut, Resource},
 };
-use nalgebra::Vector3;
+use imgui::TreeNodeFlags;
+use rayon::iter::{IntoParallelIterator as _, ParallelIterator as _};
 
-use crate::ecs::{
-    components::{Chunk, RenderDescriptor},
-    schedules::Update,
+use crate::{
+    common::{chunk::CHUNK_VOLUME, face_dir::FaceDir, palette::VoxelStorage, VoxelHandle},
+    ecs::{
+        components::{Chunk, ChunkLoader, ChunkState},
+        resources::Camera,
+        schedules::{EarlyUpdate, Render, Update},
+        systems,
+    },
 };
 
-use super::{render_init::RenderContext, voxel_registry::VoxelRegistry, Package};
+use super::{
+    debug_gui::{self, DebugCompositor},
+    render_init::RenderContext,
+    voxel_registry::VoxelRegistry,
+    world_storage::WorldStorage,
+    Package,
+};
+
+mod mesh_allocator;
+mod resource;
+mod visibility;
+pub use mesh_allocator::ChunkMeshAllocator;
+pub use resource::{ChunkGenerationQueue, ChunkMap, ChunkMeshQueue, ChunkStreamingOptions};
+pub use visibility::ChunkVisibility;
 
-/// Package for initializing chunks.
+/// Package for streaming, generating and meshing chunks.
+///
+/// Chunks are loaded around the entities with a `ChunkLoader` component.
+/// They are only generated if a `ChunkGenerationQueue` resource is inserted.
+/// The meshes are stored in the `ChunkMeshAllocator` and drawn by the `render_system`.
 pub struct ChunkPackage;
 
 impl Package for ChunkPackage {
     fn initialize(&mut self, app: &mut crate::application::Application) {
-        for x in 0..2 {
-            for y in 0..2 {
-                for z in 0..2 {
-                    app.spawn(Chunk::new(Vector3::new(x, y, z)));
-                }
+        let mesh_queue = match ChunkMeshQueue::new(64) {
+            Ok(queue) => queue,
+            Err(e) => {
+                log::error!("Failed to create the chunk meshing thread pool: {e}");
+                return;
             }
-        }
+        };
+        let mesh_allocator = match app.get_resource::<RenderContext>() {
+            Some(render_context) => ChunkMeshAllocator::new(&render_context.device),
+            None => {
+                log::error!("Failed to get render context");
+                return;
+            }
+        };
 
+        app.insert_resource(ChunkMap::default());
+        app.insert_resource(ChunkStreamingOptions::default());
+        app.insert_resource(mesh_queue);
+        app.insert_resource(mesh_allocator);
+        app.insert_resource(ChunkVisibility::default());
+        app.insert_resource(ChunkDebugGuiState::default());
+        app.add_systems(
+            EarlyUpdate,
+            (
+                (chunk_map_remove_system, chunk_map_insert_system).chain(),
+                chunk_job_cancel_system,
+                chunk_mesh_free_system,
+            ),
+        );
         app.add_systems(
             Update,
-            chunk_mesher_system,
+            (
+                chunk_streaming_system,
+                (
+                    chunk_generation_apply_system,
+                    chunk_generation_dispatch_system,
+                )
+                    .chain()
+                    .run_if(resource_exists::<ChunkGenerationQueue>)
+                    .before(chunk_border_system),
+                (
+                    chunk_border_system,
+                    chunk_mesher_system,
+                    chunk_mesh_upload_system,
+                )
+                    .chain(),
+            ),
         );
+        app.add_systems(
+            Render,
+            (
+                chunk_draw_system.before(systems::render_system),
+                chunk_debug_gui
+                    .after(debug_gui::start_gui_frame)
+                    .before(systems::render_system),
+            ),
+        );
+    }
+}
+
+/// Adds the newly spawned chunks to the `ChunkMap`.
+pub fn chunk_map_insert_system(
+    chunks: Query<(Entity, &Chunk), Added<Chunk>>,
+    mut chunk_map: ResMut<ChunkMap>,
+) {
+    for (entity, chunk) in chunks.iter() {
+        chunk_map.insert(chunk.get_index(), entity);
     }
 }
 
-/// Meshes the chunks that have been changed.
+/// Removes the despawned chunks from the `ChunkMap` and remeshes their neighbours.
+pub fn chunk_map_remove_system(
+    mut removed: RemovedComponents<Chunk>,
+    mut chunk_map: ResMut<ChunkMap>,
+    mut chunks: Query<&mut Chunk>,
+) {
+    for entity in removed.read() {
+        if let Some(index) = chunk_map.remove(entity) {
+            chunk_map.mark_neighbours_changed(&mut chunks, index, (0..6).map(FaceDir::from_axis));
+        }
+    }
+}
+
+/// Spawns the missing chunks around the `ChunkLoader`s nearest-first and despawns the chunks out of their range.
+pub fn chunk_streaming_system(
+    mut commands: Commands,
+    loaders: Query<&ChunkLoader>,
+    chunks: Query<&Chunk>,
+    chunk_map: Res<ChunkMap>,
+    options: Res<ChunkStreamingOptions>,
+    world_storage: Option<Res<WorldStorage>>,
+) {
+    for (index, entity) in chunk_map.iter() {
+        let in_range = loaders
+            .iter()
+            .any(|loader| loader.is_in_range(index, options.unload_margin));
+        if in_range {
+            continue;
+        }
+        let chunk = match chunks.get(entity) {
+            Ok(chunk) => chunk,
+            Err(_) => continue,
+        };
+
+        // The changes are queued right away, the chunk is gone by the next save.
+        if let Some(world_storage) = &world_storage {
+            if chunk.is_unsaved() {
+                world_storage.get_region_storage().queue_save(chunk);
+            }
+        }
+        commands.entity(entity).despawn();
+    }
+
+    let mut missing = vec![];
+    for loader in loaders.iter() {
+        let center = loader.get_chunk_index();
+        for index in loader.get_chunks_in_range() {
+            if !chunk_map.contains(index) {
+                missing.push(((index - center).cast::<f32>().norm_squared(), index));
+            }
+        }
+    }
+
+    // Chunks in range of multiple loaders get loaded based on their nearest loader.
+    missing.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+    let mut loaded = vec![];
+    for (_, index) in missing {
+        if loaded.len() >= options.max_loads_per_frame {
+            break;
+        }
+        if !loaded.contains(&index) {
+            commands.spawn((Chunk::new(index), ChunkState::Queued));
+            loaded.push(index);
+        }
+    }
+}
+
+/// Hands the queued chunks nearest to the `ChunkLoader`s to the `ChunkGenerationQueue`.
+pub fn chunk_generation_dispatch_system(
+    chunks: Query<(Entity, &Chunk, &ChunkState)>,
+    loaders: Query<&ChunkLoader>,
+    mut generation_queue: ResMut<ChunkGenerationQueue>,
+    world_storage: Option<Res<WorldStorage>>,
+) {
+    let free_jobs = generation_queue.get_free_job_count();
+    if free_jobs == 0 {
+        return;
+    }
+
+    let loader_indices = loaders
+        .iter()
+        .map(ChunkLoader::get_chunk_index)
+        .collect::<Vec<_>>();
+    let mut queued = chunks
+        .iter()
+        .filter(|(entity, _, state)| {
+            **state == ChunkState::Queued && !generation_queue.is_in_flight(*entity)
+        })
+        .map(|(entity, chunk, _)| {
+            let index = chunk.get_index();
+            let distance = loader_indices
+                .iter()
+                .map(|loader| (index - loader).cast::<f32>().norm_squared())
+                .fold(f32::INFINITY, f32::min);
+            (distance, entity, index)
+        })
+        .collect::<Vec<_>>();
+
+    queued.sort_by(|(a, ..), (b, ..)| a.total_cmp(b));
+    for (_, entity, index) in queued.into_iter().take(free_jobs) {
+        let storage = world_storage
+            .as_ref()
+            .map(|storage| storage.get_region_storage().clone());
+        generation_queue.generate(entity, index, storage);
+    }
+}
+
+/// Replaces the queued chunks with the chunks that have finished generating.
+pub fn chunk_generation_apply_system(
+    mut chunks: Query<(&mut Chunk, &mut ChunkState)>,
+    mut generation_queue: ResMut<ChunkGenerationQueue>,
+) {
+    for (entity, generated) in generation_queue.take_finished() {
+        if let Ok((mut chunk, mut state)) = chunks.get_mut(entity) {
+            if *state == ChunkState::Queued {
+                *chunk = generated;
+                *state = ChunkState::Generated;
+            }
+        }
+    }
+}
+
+/// Marks the neighbours of the chunks whose border voxels have changed, so they get remeshed.
+pub fn chunk_border_system(mut chunks: Query<&mut Chunk>, chunk_map: Res<ChunkMap>) {
+    let mut dirty_chunks = vec![];
+    for mut chunk in chunks.iter_mut() {
+        if chunk.is_changed() {
+            let dirty_borders = chunk.bypass_change_detection().take_dirty_borders();
+            if dirty_borders.contains(&true) {
+                dirty_chunks.push((chunk.get_index(), dirty_borders));
+            }
+        }
+    }
+
+    for (index, dirty_borders) in dirty_chunks {
+        let face_dirs = (0..6)
+            .filter(|axis| dirty_borders[*axis])
+            .map(FaceDir::from_axis);
+        chunk_map.mark_neighbours_changed(&mut chunks, index, face_dirs);
+    }
+}
+
+/// Queues the chunks that have been changed to be meshed in the background.
+///
+/// Chunks that are still waiting to be generated are skipped.
 pub fn chunk_mesher_system(
-    commands: ParallelCommands,
-    chunks: Query<(Entity, &Chunk), Changed<Chunk>>,
-    render_context: Res<RenderContext>,
+    chunks: Query<(Entity, &Chunk, Option<&ChunkState>), Changed<Chunk>>,
+    all_chunks: Query<&Chunk>,
+    chunk_map: Res<ChunkMap>,
     voxel_registry: Res<VoxelRegistry>,
+    mut mesh_queue: ResMut<ChunkMeshQueue>,
 ) {
     if chunks.is_empty() {
         return;
     }
 
-    let voxel_render_descriptor = RenderDescriptor {
-        pipeline_name: "voxel".to_owned(),
-    };
-    let start = Instant::now();
+    // The jobs work on snapshots, so the chunks can keep changing while they are being meshed.
+    let snapshots = chunks
+        .iter()
+        .filter(|(.., state)| *state != Some(&ChunkState::Queued))
+        .map(|(entity, chunk, _)| (entity, chunk))
+        .collect::<Vec<_>>()
+        .into_par_iter()
+        .map(|(entity, chunk)| {
+            let neighbours =
+                chunk_map.get_neighbours(&all_chunks, chunk.get_index(), &voxel_registry.voxels);
+            (entity, chunk.create_mesh_snapshot(), neighbours)
+        })
+        .collect::<Vec<_>>();
+
+    for (entity, chunk, neighbours) in snapshots {
+        mesh_queue.queue(
+            entity,
+            chunk,
+            neighbours,
+            Arc::clone(&voxel_registry.voxels),
+        );
+    }
+}
+
+/// Uploads the finished chunk meshes to the `ChunkMeshAllocator`, up to the per-frame budget of the `ChunkMeshQueue`.
+pub fn chunk_mesh_upload_system(
+    mut mesh_queue: ResMut<ChunkMeshQueue>,
+    mut mesh_allocator: ResMut<ChunkMeshAllocator>,
+    mut visibility: ResMut<ChunkVisibility>,
+    mut chunks: Query<Option<&mut ChunkState>, With<Chunk>>,
+    render_context: Res<RenderContext>,
+) {
+    for (entity, mesh, connectivity) in mesh_queue.take_finished() {
+        match chunks.get_mut(entity) {
+            Ok(Some(mut state)) => *state = ChunkState::Meshed,
+            Ok(None) => {}
+            Err(_) => continue,
+        }
+
+        match mesh {
+            Some(mesh) => {
+                mesh_allocator.insert(&render_context.device, &render_context.queue, entity, &mesh)
+            }
+            None => mesh_allocator.remove(entity),
+        }
+        visibility.insert(entity, connectivity);
+    }
+}
+
+/// Frees the meshes and forgets the face connectivity of the despawned chunks.
+pub fn chunk_mesh_free_system(
+    mut removed: RemovedComponents<Chunk>,
+    mut mesh_allocator: ResMut<ChunkMeshAllocator>,
+    mut visibility: ResMut<ChunkVisibility>,
+) {
+    for entity in removed.read() {
+        mesh_allocator.remove(entity);
+        visibility.remove(entity);
+    }
+}
+
+/// Prepares the draws of the chunk meshes that the camera can see in the current frame.
+pub fn chunk_draw_system(
+    mut mesh_allocator: ResMut<ChunkMeshAllocator>,
+    mut visibility: ResMut<ChunkVisibility>,
+    chunk_map: Res<ChunkMap>,
+    render_context: Res<RenderContext>,
+    camera: Res<Camera>,
+) {
+    visibility.update(&chunk_map, &camera);
+    mesh_allocator.prepare_draws(
+        &render_context.device,
+        &render_context.queue,
+        &camera,
+        |entity| visibility.is_visible(entity),
+    );
+}
+
+/// Cancels the generation and meshing jobs of the despawned chunks.
+pub fn chunk_job_cancel_system(
+    mut removed: RemovedComponents<Chunk>,
+    mut mesh_queue: ResMut<ChunkMeshQueue>,
+    mut generation_queue: Option<ResMut<ChunkGenerationQueue>>,
+) {
+    for entity in removed.read() {
+        mesh_queue.cancel(entity);
+        if let Some(generation_queue) = &mut generation_queue {
+            generation_queue.cancel(entity);
+        }
+    }
+}
+
+/// Builds a ui for inspecting the loaded chunks.
+#[allow(clippy::too_many_arguments)]
+fn chunk_debug_gui(
+    debug_compositor: Option<NonSend<DebugCompositor>>,
+    mut state: ResMut<ChunkDebugGuiState>,
+    mut streaming_options: ResMut<ChunkStreamingOptions>,
+    mut mesh_queue: ResMut<ChunkMeshQueue>,
+    mesh_allocator: Res<ChunkMeshAllocator>,
+    mut visibility: ResMut<ChunkVisibility>,
+    generation_queue: Option<Res<ChunkGenerationQueue>>,
+    chunks: Query<&Chunk>,
+    states: Query<&ChunkState>,
+) {
+    /// The amount of memory a chunk would take up without palette compression.
+    const UNCOMPRESSED_CHUNK_SIZE: usize =
+        CHUNK_VOLUME * std::mem::size_of::<Option<VoxelHandle>>();
+    /// Bytes in a kibibyte.
+    const KIB: f32 = 1024.0;
+
+    if let Some(debug_compositor) = debug_compositor {
+        let ui = debug_compositor.get_frame_ui();
 
-    chunks.par_iter().for_each(|(entity, chunk)| {
-        let geometry = chunk.build_mesh(&render_context, &voxel_registry);
-        commands.command_scope(|mut commands| {
-            commands
-                .entity(entity)
-                .insert((voxel_render_descriptor.clone(), geometry));
+        ui.main_menu_bar(|| {
+            ui.menu("Windows", || {
+                if ui.menu_item("Chunks") {
+                    state.open = true;
+                }
+            })
         });
-    });
 
-    log::info!("Chunk meshing took {} ms", start.elapsed().as_millis());
+        if state.open {
+            let mut open = state.open;
+            ui.window("Chunks").opened(&mut open).build(|| {
+                let chunk_count = chunks.iter().len();
+                let total_memory = chunks.iter().map(Chunk::memory_usage).sum::<usize>();
+                let uncompressed_memory = chunk_count * UNCOMPRESSED_CHUNK_SIZE;
+
+                ui.text(format!("Loaded chunks: {chunk_count}"));
+                ui.text(format!(
+                    "Voxel memory: {:.1} KiB (uncompressed: {:.1} KiB)",
+                    total_memory as f32 / KIB,
+                    uncompressed_memory as f32 / KIB,
+                ));
+                if chunk_count > 0 {
+                    ui.text(format!(
+                        "Average per chunk: {:.1} KiB",
+                        total_memory as f32 / chunk_count as f32 / KIB,
+                    ));
+                }
+
+                let count_state = |state| states.iter().filter(|s| **s == state).count();
+                ui.text(format!(
+                    "Queued: {}, generated: {}, meshed: {}",
+                    count_state(ChunkState::Queued),
+                    count_state(ChunkState::Generated),
+                    count_state(ChunkState::Meshed),
+                ));
+                if let Some(generation_queue) = &generation_queue {
+                    ui.text(format!(
+                        "Generation jobs in flight: {}",
+                        generation_queue.in_flight_count()
+                    ));
+                }
+                ui.text(format!("Pending mesh jobs: {}", mesh_queue.pending_count()));
+                ui.text(format!(
+                    "Meshes waiting for upload: {}",
+                    mesh_queue.finished_count()
+                ));
+                let (used_mesh_memory, mesh_buffer_size) = mesh_allocator.memory_usage();
+                ui.text(format!(
+                    "Mesh memory: {:.1} KiB of {:.1} KiB",
+                    used_mesh_memory as f32 / KIB,
+                    mesh_buffer_size as f32 / KIB,
+                ));
+                ui.text(format!(
+                    "Chunk draws: {} ({}), culled chunks: {}",
+                    mesh_allocator.draw_count(),
+                    mesh_allocator.draw_mode_name(),
+                    mesh_allocator.culled_count(),
+                ));
+
+                ui.separator();
+                let mut max_loads_per_frame = streaming_options.max_loads_per_frame as u32;
+                if ui.slider("Max loads per frame", 1, 256, &mut max_loads_per_frame) {
+                    streaming_options.max_loads_per_frame = max_loads_per_frame as usize;
+                }
+                let mut max_uploads_per_frame = mesh_queue.max_uploads_per_frame as u32;
+                if ui.slider(
+                    "Max mesh uploads per frame",
+                    1,
+                    256,
+                    &mut max_uploads_per_frame,
+                ) {
+                    mesh_queue.max_uploads_per_frame = max_uploads_per_frame as usize;
+                }
+                ui.checkbox("Cull hidden chunks", &mut visibility.enabled);
+                let mut unload_margin = streaming_options.unload_margin;
+                if ui.slider("Unload margin", 0, 8, &mut unload_margin) {
+                    streaming_options.unload_margin = unload_margin;
+                }
+
+                ui.separator();
+                if !ui.collapsing_header("Chunk storage", TreeNodeFlags::empty()) {
+                    return;
+                }
+                for chunk in chunks.iter() {
+                    let index = chunk.get_index();
+                    let storage = match chunk.get_voxels() {
+                        VoxelStorage::Uniform { value: None, .. } => "uniform, empty".to_owned(),
+                        VoxelStorage::Uniform {
+                            value: Some(voxel), ..
+                        } => format!("uniform, voxel {}", voxel.id),
+                        VoxelStorage::Paletted(voxels) => format!(
+                            "{} palette entries, {} bits per voxel",
+                            voxels.get_palette().len(),
+                            voxels.get_bits_per_index(),
+                        ),
+                    };
+                    ui.text(format!(
+                        "[{}, {}, {}] {:.1} KiB, {storage}",
+                        index.x,
+                        index.y,
+                        index.z,
+                        chunk.memory_usage() as f32 / KIB,
+                    ));
+                }
+            });
+            state.open = open;
+        }
+    }
+}
+
+/// Singleton state for the chunks window.
+#[derive(Resource, Default)]
+struct ChunkDebugGuiState {
+    open: bool,
 }
@@ -0,0 +1,532 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex,
+    },
+};
+
+use bevy_ecs::{
+    change_detection::DetectChangesMut as _,
+    entity::Entity,
+    query::QueryFilter,
+    system::{Query, Resource},
+    world::Mut,
+};
+use nalgebra::Vector3;
+use rayon::{ThreadPool, ThreadPoolBuildError, ThreadPoolBuilder};
+
+use crate::{
+    common::{
+        aabb::Aabb,
+        chunk,
+        chunk_visibility::FaceConnectivity,
+        collision::{self, CollisionResult},
+        face_dir::FaceDir,
+        raycast::{Raycast, RaycastHit},
+        region::RegionStorage,
+        voxel::Voxel,
+        world_generator::WorldGenerator,
+        VoxelHandle,
+    },
+    ecs::components::{Chunk, ChunkMesh, ChunkMeshSnapshot, ChunkNeighbours},
+};
+
+/// Maps chunk indices to the entities of the loaded chunks and provides world space voxel access.
+///
+/// The map is kept in sync with the spawned and despawned `Chunk` entities by the `ChunkPackage`.
+#[derive(Resource, Default)]
+pub struct ChunkMap {
+    entities: HashMap<Vector3<i32>, Entity>,
+    indices: HashMap<Entity, Vector3<i32>>,
+}
+
+impl ChunkMap {
+    /// Returns the entity of the chunk with the specified chunk index.
+    pub fn get_entity(&self, index: Vector3<i32>) -> Option<Entity> {
+        self.entities.get(&index).copied()
+    }
+
+    /// Returns the chunk index of the specified chunk entity.
+    pub fn get_chunk_index(&self, entity: Entity) -> Option<Vector3<i32>> {
+        self.indices.get(&entity).copied()
+    }
+
+    /// Returns true if a chunk with the specified chunk index is loaded.
+    pub fn contains(&self, index: Vector3<i32>) -> bool {
+        self.entities.contains_key(&index)
+    }
+
+    /// Returns the amount of loaded chunks.
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    /// Returns true if there are no loaded chunks.
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    /// Returns an iterator over the chunk indices and entities of all the loaded chunks.
+    pub fn iter(&self) -> impl Iterator<Item = (Vector3<i32>, Entity)> + '_ {
+        self.entities
+            .iter()
+            .map(|(index, entity)| (*index, *entity))
+    }
+
+    /// Returns the chunk that contains the specified world voxel position.
+    pub fn get_chunk<'a, F: QueryFilter>(
+        &self,
+        chunks: &'a Query<'_, '_, &Chunk, F>,
+        position: Vector3<i32>,
+    ) -> Option<&'a Chunk> {
+        let entity = self.get_entity(chunk::world_to_chunk_index(position))?;
+        chunks.get(entity).ok()
+    }
+
+    /// Returns the mutable chunk that contains the specified world voxel position.
+    pub fn get_chunk_mut<'a, F: QueryFilter>(
+        &self,
+        chunks: &'a mut Query<'_, '_, &mut Chunk, F>,
+        position: Vector3<i32>,
+    ) -> Option<Mut<'a, Chunk>> {
+        let entity = self.get_entity(chunk::world_to_chunk_index(position))?;
+        chunks.get_mut(entity).ok()
+    }
+
+    /// Collects the borders of the loaded chunks around the chunk with the specified chunk index.
+    ///
+    /// ## Arguments
+    /// * `chunks` - The query to get the neighbouring chunks from.
+    /// * `index` - The chunk index of the chunk whose neighbours are collected.
+    /// * `registered_voxels` - The registered voxels to look up the voxel opacities in.
+    pub fn get_neighbours<F: QueryFilter>(
+        &self,
+        chunks: &Query<'_, '_, &Chunk, F>,
+        index: Vector3<i32>,
+        registered_voxels: &HashMap<u32, Voxel>,
+    ) -> ChunkNeighbours {
+        let mut neighbours = ChunkNeighbours::default();
+        for axis in 0..6 {
+            let face_dir = FaceDir::from_axis(axis);
+            let neighbour = self
+                .get_entity(index + chunk::neighbour_offset(face_dir))
+                .and_then(|entity| chunks.get(entity).ok());
+            if let Some(neighbour) = neighbour {
+                neighbours.set(face_dir, neighbour, registered_voxels);
+            }
+        }
+        neighbours
+    }
+
+    /// Marks the loaded neighbours of a chunk in the specified face directions as changed, so they get remeshed.
+    ///
+    /// ## Arguments
+    /// * `chunks` - The query to get the neighbouring chunks from.
+    /// * `index` - The chunk index of the chunk whose neighbours are marked.
+    /// * `face_dirs` - The directions of the neighbours to mark.
+    pub fn mark_neighbours_changed<F: QueryFilter>(
+        &self,
+        chunks: &mut Query<'_, '_, &mut Chunk, F>,
+        index: Vector3<i32>,
+        face_dirs: impl IntoIterator<Item = FaceDir>,
+    ) {
+        for face_dir in face_dirs {
+            let entity = self.get_entity(index + chunk::neighbour_offset(face_dir));
+            if let Some(mut neighbour) = entity.and_then(|entity| chunks.get_mut(entity).ok()) {
+                neighbour.set_changed();
+            }
+        }
+    }
+
+    /// Returns the voxel at the specified world voxel position.
+    ///
+    /// ## Returns
+    /// The outter option indicates whether the chunk containing the position is loaded or not.
+    /// The inner option indicates if the voxel is present or not.
+    pub fn get_voxel<F: QueryFilter>(
+        &self,
+        chunks: &Query<'_, '_, &Chunk, F>,
+        position: Vector3<i32>,
+    ) -> Option<Option<VoxelHandle>> {
+        self.get_chunk(chunks, position)
+            .map(|chunk| chunk.sample(chunk::world_to_local(position)))
+    }
+
+    /// Sets the voxel at the specified world voxel position.
+    ///
+    /// The chunk is only marked as changed if the voxel actually changes.
+    ///
+    /// ## Returns
+    /// False if the chunk containing the position is not loaded.
+    pub fn set_voxel<F: QueryFilter>(
+        &self,
+        chunks: &mut Query<'_, '_, &mut Chunk, F>,
+        position: Vector3<i32>,
+        voxel: Option<VoxelHandle>,
+    ) -> bool {
+        let mut chunk = match self.get_chunk_mut(chunks, position) {
+            Some(chunk) => chunk,
+            None => return false,
+        };
+
+        let local = chunk::world_to_local(position);
+        if chunk.sample(local) != voxel {
+            *chunk.sample_mut(local) = voxel;
+        }
+        true
+    }
+
+    /// Casts a ray through the loaded chunks.
+    ///
+    /// ## Returns
+    /// The first hit voxel, or `None` if nothing was hit or the ray reached an unloaded chunk.
+    pub fn raycast<F: QueryFilter>(
+        &self,
+        chunks: &Query<'_, '_, &Chunk, F>,
+        raycast: &Raycast,
+    ) -> Option<RaycastHit> {
+        raycast.cast(|position| self.get_voxel(chunks, position))
+    }
+
+    /// Moves a box through the loaded chunks and stops it at collidable voxels.
+    ///
+    /// Unloaded chunks are treated as solid, so nothing falls through the world while it loads.
+    ///
+    /// ## Arguments
+    /// * `chunks` - The query to get the chunks from.
+    /// * `registered_voxels` - The registered voxels, used to check if a voxel is collidable.
+    /// * `aabb` - The box to move.
+    /// * `movement` - The movement to apply to the box.
+    pub fn move_aabb<F: QueryFilter>(
+        &self,
+        chunks: &Query<'_, '_, &Chunk, F>,
+        registered_voxels: &HashMap<u32, Voxel>,
+        aabb: &Aabb,
+        movement: Vector3<f32>,
+    ) -> CollisionResult {
+        collision::move_aabb(aabb, movement, |position| {
+            match self.get_voxel(chunks, position) {
+                Some(voxel) => collision::is_collidable(registered_voxels, voxel),
+                None => true,
+            }
+        })
+    }
+
+    /// Adds a chunk entity to the map.
+    pub(super) fn insert(&mut self, index: Vector3<i32>, entity: Entity) {
+        if let Some(previous) = self.entities.insert(index, entity) {
+            if previous != entity {
+                log::warn!("Chunk {index:?} was loaded more than once");
+                self.indices.remove(&previous);
+            }
+        }
+        self.indices.insert(entity, index);
+    }
+
+    /// Removes a chunk entity from the map and returns its chunk index.
+    pub(super) fn remove(&mut self, entity: Entity) -> Option<Vector3<i32>> {
+        let index = self.indices.remove(&entity)?;
+        if self.entities.get(&index) == Some(&entity) {
+            self.entities.remove(&index);
+        }
+        Some(index)
+    }
+}
+
+/// Options for streaming chunks in and out around the `ChunkLoader`s.
+#[derive(Resource, Clone, Debug)]
+pub struct ChunkStreamingOptions {
+    /// The maximum amount of chunks spawned in a single frame.
+    pub max_loads_per_frame: usize,
+    /// How many chunks beyond the render distance are kept loaded, so moving back and forth
+    /// over a chunk border does not keep reloading the same chunks.
+    pub unload_margin: u32,
+}
+
+impl Default for ChunkStreamingOptions {
+    fn default() -> Self {
+        Self {
+            max_loads_per_frame: 16,
+            unload_margin: 1,
+        }
+    }
+}
+
+/// A chunk mesh that was built in the background.
+struct FinishedChunkMesh {
+    entity: Entity,
+    job_id: u64,
+    mesh: Option<ChunkMesh>,
+    connectivity: FaceConnectivity,
+}
+
+/// A meshing job whose mesh has not been uploaded yet.
+struct PendingChunkMesh {
+    job_id: u64,
+    cancelled: Arc<AtomicBool>,
+}
+
+/// Builds chunk meshes on background threads, so meshing never blocks a frame.
+///
+/// Only the newest job of every chunk gets its mesh uploaded, older jobs are cancelled.
+#[derive(Resource)]
+pub struct ChunkMeshQueue {
+    thread_pool: ThreadPool,
+    sender: Sender<FinishedChunkMesh>,
+    receiver: Mutex<Receiver<FinishedChunkMesh>>,
+    pending: HashMap<Entity, PendingChunkMesh>,
+    finished: VecDeque<FinishedChunkMesh>,
+    next_job_id: u64,
+    /// The maximum amount of finished meshes uploaded to the GPU in a single frame.
+    pub max_uploads_per_frame: usize,
+}
+
+impl ChunkMeshQueue {
+    /// Creates a new queue with its own thread pool.
+    pub fn new(max_uploads_per_frame: usize) -> Result<Self, ThreadPoolBuildError> {
+        let thread_pool = ThreadPoolBuilder::new()
+            .thread_name(|index| format!("chunk-mesher-{index}"))
+            .build()?;
+        let (sender, receiver) = mpsc::channel();
+
+        Ok(Self {
+            thread_pool,
+            sender,
+            receiver: Mutex::new(receiver),
+            pending: HashMap::new(),
+            finished: VecDeque::new(),
+            next_job_id: 0,
+            max_uploads_per_frame,
+        })
+    }
+
+    /// Queues a chunk to be meshed in the background.
+    ///
+    /// A job that is still pending for the same entity gets cancelled.
+    ///
+    /// ## Arguments
+    /// * `entity` - The entity of the chunk.
+    /// * `chunk` - A snapshot of the chunk to mesh.
+    /// * `neighbours` - The borders of the neighbouring chunks at the time of the snapshot.
+    /// * `voxels` - The registered voxels, shared with the other jobs.
+    pub fn queue(
+        &mut self,
+        entity: Entity,
+        chunk: ChunkMeshSnapshot,
+        neighbours: ChunkNeighbours,
+        voxels: Arc<HashMap<u32, Voxel>>,
+    ) {
+        let job_id = self.next_job_id;
+        self.next_job_id += 1;
+
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let pending = PendingChunkMesh {
+            job_id,
+            cancelled: cancelled.clone(),
+        };
+        if let Some(previous) = self.pending.insert(entity, pending) {
+            previous.cancelled.store(true, Ordering::Relaxed);
+        }
+
+        let sender = self.sender.clone();
+        self.thread_pool.spawn(move || {
+            if cancelled.load(Ordering::Relaxed) {
+                return;
+            }
+            let mesh = chunk.build_mesh(&voxels, &neighbours);
+            let connectivity = chunk.compute_connectivity(&voxels);
+            if cancelled.load(Ordering::Relaxed) {
+                return;
+            }
+            // The receiver only gets dropped with the queue, then nobody needs the mesh anymore.
+            let _ = sender.send(FinishedChunkMesh {
+                entity,
+                job_id,
+                mesh,
+                connectivity,
+            });
+        });
+    }
+
+    /// Cancels the pending job of the specified entity.
+    pub fn cancel(&mut self, entity: Entity) {
+        if let Some(pending) = self.pending.remove(&entity) {
+            pending.cancelled.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Returns the amount of chunks whose meshes have not been uploaded yet.
+    pub fn pending_count(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Returns the amount of finished meshes that are waiting to be uploaded.
+    pub fn finished_count(&self) -> usize {
+        self.finished.len()
+    }
+
+    /// Takes the finished meshes of the newest jobs.
+    ///
+    /// At most `max_uploads_per_frame` meshes are returned, the rest stay queued for later.
+    /// A `None` mesh means that the chunk does not have anything to render.
+    /// Every mesh comes with the face connectivity of the chunk.
+    pub fn take_finished(&mut self) -> Vec<(Entity, Option<ChunkMesh>, FaceConnectivity)> {
+        let receiver = self.receiver.get_mut().unwrap_or_else(|e| e.into_inner());
+        self.finished.extend(receiver.try_iter());
+
+        let mut output = vec![];
+        let mut uploads = 0;
+        while uploads < self.max_uploads_per_frame {
+            let finished = match self.finished.pop_front() {
+                Some(finished) => finished,
+                None => break,
+            };
+
+            // Stale meshes of chunks that changed again or got despawned are thrown away.
+            let is_current = self
+                .pending
+                .get(&finished.entity)
+                .is_some_and(|pending| pending.job_id == finished.job_id);
+            if !is_current {
+                continue;
+            }
+
+            self.pending.remove(&finished.entity);
+            if finished.mesh.is_some() {
+                uploads += 1;
+            }
+            output.push((finished.entity, finished.mesh, finished.connectivity));
+        }
+        output
+    }
+}
+
+/// A chunk that was generated in the background.
+struct GeneratedChunk {
+    entity: Entity,
+    chunk: Chunk,
+}
+
+/// Generates the voxels of queued chunks on background threads with a `WorldGenerator`.
+#[derive(Resource)]
+pub struct ChunkGenerationQueue {
+    generator: Arc<dyn WorldGenerator>,
+    thread_pool: ThreadPool,
+    sender: Sender<GeneratedChunk>,
+    receiver: Mutex<Receiver<GeneratedChunk>>,
+    in_flight: HashMap<Entity, Arc<AtomicBool>>,
+    /// The maximum amount of chunks that are generated at the same time.
+    ///
+    /// Chunks only get handed to the thread pool when there is room, so the queued chunks
+    /// can be reprioritized while the camera moves.
+    pub max_jobs_in_flight: usize,
+}
+
+impl ChunkGenerationQueue {
+    /// Creates a new queue with its own thread pool.
+    pub fn new(
+        generator: Arc<dyn WorldGenerator>,
+        max_jobs_in_flight: usize,
+    ) -> Result<Self, ThreadPoolBuildError> {
+        let thread_pool = ThreadPoolBuilder::new()
+            .thread_name(|index| format!("chunk-generator-{index}"))
+            .build()?;
+        let (sender, receiver) = mpsc::channel();
+
+        Ok(Self {
+            generator,
+            thread_pool,
+            sender,
+            receiver: Mutex::new(receiver),
+            in_flight: HashMap::new(),
+            max_jobs_in_flight,
+        })
+    }
+
+    /// Returns the generator the chunks are generated with.
+    pub fn get_generator(&self) -> &dyn WorldGenerator {
+        self.generator.as_ref()
+    }
+
+    /// Starts generating the chunk with the specified index in the background.
+    ///
+    /// ## Arguments
+    /// * `entity` - The entity of the chunk.
+    /// * `index` - The index of the chunk.
+    /// * `storage` - The storage to load the chunk from, the generator is only used if the chunk has not been saved.
+    pub fn generate(
+        &mut self,
+        entity: Entity,
+        index: Vector3<i32>,
+        storage: Option<Arc<RegionStorage>>,
+    ) {
+        let cancelled = Arc::new(AtomicBool::new(false));
+        if let Some(previous) = self.in_flight.insert(entity, cancelled.clone()) {
+            previous.store(true, Ordering::Relaxed);
+        }
+
+        let generator = self.generator.clone();
+        let sender = self.sender.clone();
+        self.thread_pool.spawn(move || {
+            if cancelled.load(Ordering::Relaxed) {
+                return;
+            }
+            let loaded = storage.and_then(|storage| match storage.load_chunk(index) {
+                Ok(chunk) => chunk,
+                Err(e) => {
+                    log::error!("Failed to load chunk {index:?}, generating it instead: {e}");
+                    None
+                }
+            });
+            let mut chunk = loaded.unwrap_or_else(|| {
+                let mut chunk = Chunk::new(index);
+                generator.generate_chunk(&mut chunk);
+                chunk
+            });
+            // Generated chunks can always be generated again, so they only need saving once they change.
+            chunk.mark_saved();
+            if cancelled.load(Ordering::Relaxed) {
+                return;
+            }
+            // The receiver only gets dropped with the queue, then nobody needs the chunk anymore.
+            let _ = sender.send(GeneratedChunk { entity, chunk });
+        });
+    }
+
+    /// Cancels the generation of the specified entity.
+    pub fn cancel(&mut self, entity: Entity) {
+        if let Some(cancelled) = self.in_flight.remove(&entity) {
+            cancelled.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Returns true if the specified entity is being generated.
+    pub fn is_in_flight(&self, entity: Entity) -> bool {
+        self.in_flight.contains_key(&entity)
+    }
+
+    /// Returns the amount of chunks that are being generated.
+    pub fn in_flight_count(&self) -> usize {
+        self.in_flight.len()
+    }
+
+    /// Returns how many more chunks can be handed to the thread pool.
+    pub fn get_free_job_count(&self) -> usize {
+        self.max_jobs_in_flight.saturating_sub(self.in_flight.len())
+    }
+
+    /// Takes the chunks that have finished generating.
+    pub fn take_finished(&mut self) -> Vec<(Entity, Chunk)> {
+        let receiver = self.receiver.get_mut().unwrap_or_else(|e| e.into_inner());
+        let mut output = vec![];
+        for generated in receiver.try_iter() {
+            // Chunks that got despawned in the meantime are thrown away.
+            if self.in_flight.remove(&generated.entity).is_some() {
+                output.push((generated.entity, generated.chunk));
+            }
+        }
+        output
+    }
+}
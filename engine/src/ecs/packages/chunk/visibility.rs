@@ -0,0 +1,94 @@
+use std::collections::{HashMap, HashSet};
+
+use bevy_ecs::{entity::Entity, system::Resource};
+
+use crate::{
+    common::{
+        aabb::Aabb,
+        chunk,
+        chunk_visibility::{self, FaceConnectivity},
+    },
+    ecs::resources::Camera,
+};
+
+use super::ChunkMap;
+
+/// Culls the chunks that are hidden behind other chunks, like caves behind solid rock.
+///
+/// Every meshed chunk knows which of its faces can see each other, the chunks that can be seen
+/// are found by walking through the connected faces from the chunk the camera is in.
+#[derive(Resource)]
+pub struct ChunkVisibility {
+    /// Whether the hidden chunks are culled.
+    pub enabled: bool,
+    /// The face connectivity of the meshed chunks.
+    connectivity: HashMap<Entity, FaceConnectivity>,
+    /// The chunks that can be seen in the current frame, `None` if nothing could be culled.
+    visible: Option<HashSet<Entity>>,
+}
+
+impl Default for ChunkVisibility {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            connectivity: HashMap::new(),
+            visible: None,
+        }
+    }
+}
+
+impl ChunkVisibility {
+    /// Sets the face connectivity of a chunk that has been meshed.
+    pub fn insert(&mut self, entity: Entity, connectivity: FaceConnectivity) {
+        self.connectivity.insert(entity, connectivity);
+    }
+
+    /// Forgets the face connectivity of a despawned chunk.
+    pub fn remove(&mut self, entity: Entity) {
+        self.connectivity.remove(&entity);
+    }
+
+    /// Finds the chunks that can be seen by the camera in the current frame.
+    ///
+    /// Chunks that have not been meshed yet are treated as if every face could see every other face,
+    /// so nothing behind them gets culled before they are.
+    pub fn update(&mut self, chunk_map: &ChunkMap, camera: &Camera) {
+        if !self.enabled {
+            self.visible = None;
+            return;
+        }
+
+        let start =
+            chunk::world_to_chunk_index(camera.get_position().coords.map(|c| c.floor() as i32));
+        let visible = chunk_visibility::find_visible_chunks(
+            start,
+            |index| {
+                chunk_map.get_entity(index).map(|entity| {
+                    self.connectivity
+                        .get(&entity)
+                        .copied()
+                        .unwrap_or(FaceConnectivity::ALL)
+                })
+            },
+            |index| {
+                camera
+                    .get_frustum()
+                    .intersects_aabb(&Aabb::from_chunk_index(index))
+            },
+        );
+
+        self.visible = visible.map(|visible| {
+            visible
+                .into_iter()
+                .filter_map(|index| chunk_map.get_entity(index))
+                .collect()
+        });
+    }
+
+    /// Returns true if the chunk may be seen in the current frame.
+    pub fn is_visible(&self, entity: Entity) -> bool {
+        self.visible
+            .as_ref()
+            .is_none_or(|visible| visible.contains(&entity))
+    }
+}
@@ -2,6 +2,7 @@ use crate::{
     application::Application,
     ecs::{
         events::window_events::WindowEvent,
+        resources::RenderStatistics,
         schedules::{Render, Update},
         systems,
     },
@@ -15,7 +16,7 @@ mod resource;
 use bevy_ecs::{
     event::EventReader,
     schedule::IntoSystemConfigs as _,
-    system::{NonSendMut, Res},
+    system::{NonSend, NonSendMut, Res, ResMut, Resource},
 };
 pub use resource::DebugCompositor;
 
@@ -40,8 +41,17 @@ impl Package for DebugCompositorPackage {
         };
 
         app.insert_non_send_resource(DebugCompositor::new(&window, &render_context));
+        app.insert_resource(RenderStatisticsDebugGuiState::default());
         app.add_systems(Update, update_gui);
-        app.add_systems(Render, start_gui_frame.before(systems::render_system));
+        app.add_systems(
+            Render,
+            (
+                start_gui_frame.before(systems::render_system),
+                render_statistics_debug_gui
+                    .after(start_gui_frame)
+                    .before(systems::render_system),
+            ),
+        );
     }
 
     fn intialization_stage(&self) -> InitializationStage {
@@ -67,3 +77,46 @@ pub fn update_gui(
 pub fn start_gui_frame(mut debug_compositor: NonSendMut<DebugCompositor>) {
     debug_compositor.start_frame();
 }
+
+/// Builds a ui for the statistics of the last rendered frame.
+fn render_statistics_debug_gui(
+    debug_compositor: NonSend<DebugCompositor>,
+    mut state: ResMut<RenderStatisticsDebugGuiState>,
+    render_statistics: Option<Res<RenderStatistics>>,
+) {
+    let ui = debug_compositor.get_frame_ui();
+
+    ui.main_menu_bar(|| {
+        ui.menu("Windows", || {
+            if ui.menu_item("Render Statistics") {
+                state.open = true;
+            }
+        })
+    });
+
+    if state.open {
+        let mut open = state.open;
+        ui.window("Render Statistics")
+            .opened(&mut open)
+            .build(|| match &render_statistics {
+                Some(render_statistics) => {
+                    ui.text(format!(
+                        "Drawn objects: {}",
+                        render_statistics.drawn_objects
+                    ));
+                    ui.text(format!(
+                        "Culled objects: {}",
+                        render_statistics.culled_objects
+                    ));
+                }
+                None => ui.text("Nothing has been rendered"),
+            });
+        state.open = open;
+    }
+}
+
+/// Singleton state for the render statistics window.
+#[derive(Resource, Default)]
+struct RenderStatisticsDebugGuiState {
+    open: bool,
+}
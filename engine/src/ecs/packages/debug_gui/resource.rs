@@ -100,6 +100,11 @@ impl DebugCompositor {
         unsafe { &*self.ui.unwrap() }
     }
 
+    /// Returns true if the debug gui is using the mouse, so the mouse input should not affect the game.
+    pub fn wants_mouse(&self) -> bool {
+        self.context.io().want_capture_mouse
+    }
+
     /// Updates the delta time.
     pub fn update_delta_time<D: Into<Duration>>(&mut self, delta_time: D) {
         let delta_time = delta_time.into();
@@ -0,0 +1,208 @@
+use std::{cell::Cell, collections::HashMap, time::Duration};
+
+use bevy_ecs::{
+    change_detection::{DetectChanges as _, DetectChangesMut as _},
+    entity::Entity,
+    schedule::IntoSystemConfigs as _,
+    system::{Local, Query, Res},
+};
+use nalgebra::Vector3;
+
+use crate::{
+    application::Application,
+    common::{
+        chunk::{self, CHUNK_LENGTH},
+        face_dir::FaceDir,
+        fluid::{self, FluidSimulation, FluidVolume, FLUID_TICKS_PER_SECOND},
+        voxel::Voxel,
+        VoxelHandle,
+    },
+    ecs::{
+        components::{Chunk, ChunkState},
+        schedules::Update,
+    },
+};
+
+use super::{
+    chunk::{self as chunk_package, ChunkMap},
+    light as light_package,
+    time::Time,
+    voxel_registry::VoxelRegistry,
+    Package,
+};
+
+/// The most fluid ticks that are simulated in a single frame, the rest are skipped so slow frames do not pile up.
+const MAX_TICKS_PER_FRAME: u32 = 4;
+
+/// Package that simulates the fluid voxels of the loaded chunks.
+///
+/// The fluids are simulated before the light is updated, so the light reaches the voxels the fluid has left.
+pub struct FluidPackage;
+
+impl Package for FluidPackage {
+    fn initialize(&mut self, app: &mut Application) {
+        app.add_systems(
+            Update,
+            fluid_system
+                .after(chunk_package::chunk_generation_apply_system)
+                .before(light_package::light_system),
+        );
+    }
+}
+
+/// Queues the edited and newly generated fluids and simulates `FLUID_TICKS_PER_SECOND` fluid ticks per second.
+///
+/// Only the chunks whose voxels the fluid changes are marked as changed.
+pub fn fluid_system(
+    mut chunks: ChunkQuery,
+    chunk_map: Res<ChunkMap>,
+    voxel_registry: Res<VoxelRegistry>,
+    time: Res<Time>,
+    mut simulation: Local<FluidSimulation>,
+    mut elapsed: Local<Duration>,
+) {
+    let mut scanned_chunks = vec![];
+    for (mut chunk, state) in chunks.iter_mut() {
+        if *state == ChunkState::Queued || !chunk.is_changed() {
+            continue;
+        }
+
+        let chunk = chunk.bypass_change_detection();
+        let index = chunk.get_index();
+        for position in chunk.take_fluid_updates() {
+            simulation.queue_around(chunk::local_to_world(index, position));
+        }
+        if chunk.take_needs_fluid_scan() {
+            scanned_chunks.push(index);
+        }
+    }
+
+    let mut volume = ChunkFluidVolume {
+        chunks: &mut chunks,
+        chunk_map: &chunk_map,
+        registered_voxels: &voxel_registry.voxels,
+        last_entity: Cell::new(None),
+    };
+    for index in scanned_chunks {
+        volume.queue_chunk(&mut simulation, index);
+    }
+
+    let tick_duration = Duration::from_secs(1) / FLUID_TICKS_PER_SECOND;
+    *elapsed += Duration::from(time.get_delta_time());
+    let mut ticks = 0;
+    while *elapsed >= tick_duration {
+        *elapsed -= tick_duration;
+        if ticks < MAX_TICKS_PER_FRAME && !simulation.is_idle() {
+            simulation.tick(&mut volume, &voxel_registry.voxels);
+            ticks += 1;
+        }
+    }
+}
+
+/// The chunks and their states.
+type ChunkQuery<'w, 's> = Query<'w, 's, (&'static mut Chunk, &'static ChunkState)>;
+
+/// The voxels and the fluid levels of the loaded chunks.
+///
+/// Chunks that are still waiting to be generated are treated as unloaded.
+struct ChunkFluidVolume<'a, 'w, 's> {
+    chunks: &'a mut ChunkQuery<'w, 's>,
+    chunk_map: &'a ChunkMap,
+    registered_voxels: &'a HashMap<u32, Voxel>,
+    /// The last looked up chunk index and its entity, neighbouring fluid voxels are mostly inside of the same chunk.
+    last_entity: Cell<Option<(Vector3<i32>, Option<Entity>)>>,
+}
+
+impl ChunkFluidVolume<'_, '_, '_> {
+    /// Returns the entity of the chunk with the specified chunk index.
+    fn get_entity(&self, index: Vector3<i32>) -> Option<Entity> {
+        match self.last_entity.get() {
+            Some((last_index, entity)) if last_index == index => entity,
+            _ => {
+                let entity = self.chunk_map.get_entity(index);
+                self.last_entity.set(Some((index, entity)));
+                entity
+            }
+        }
+    }
+
+    /// Returns the generated chunk with the specified chunk index.
+    fn get_chunk(&self, index: Vector3<i32>) -> Option<&Chunk> {
+        let entity = self.get_entity(index)?;
+        match self.chunks.get(entity) {
+            Ok((chunk, state)) if *state != ChunkState::Queued => Some(chunk),
+            _ => None,
+        }
+    }
+
+    /// Returns true if the palette of the chunk contains a fluid.
+    fn contains_fluid(&self, chunk: &Chunk) -> bool {
+        chunk
+            .get_voxels()
+            .get_palette()
+            .iter()
+            .any(|voxel| fluid::is_fluid(self.registered_voxels, *voxel))
+    }
+
+    /// Queues the fluid voxels of a newly generated or loaded chunk and the voxels around them.
+    ///
+    /// The border voxels are also queued if a neighbouring chunk contains a fluid, which may flow into this one.
+    fn queue_chunk(&self, simulation: &mut FluidSimulation, index: Vector3<i32>) {
+        let chunk = match self.get_chunk(index) {
+            Some(chunk) => chunk,
+            None => return,
+        };
+
+        if self.contains_fluid(chunk) {
+            for z in 0..CHUNK_LENGTH {
+                for y in 0..CHUNK_LENGTH {
+                    for x in 0..CHUNK_LENGTH {
+                        if fluid::is_fluid(self.registered_voxels, chunk.sample((x, y, z))) {
+                            simulation.queue_around(chunk::local_to_world(index, (x, y, z)));
+                        }
+                    }
+                }
+            }
+        }
+
+        for axis in 0..6 {
+            let face_dir = FaceDir::from_axis(axis);
+            let neighbour_has_fluid = self
+                .get_chunk(index + chunk::neighbour_offset(face_dir))
+                .is_some_and(|neighbour| self.contains_fluid(neighbour));
+            if !neighbour_has_fluid {
+                continue;
+            }
+
+            let layer = chunk::border_layer(face_dir);
+            for row in 0..CHUNK_LENGTH {
+                for bit in 0..CHUNK_LENGTH {
+                    let position = chunk::border_position(face_dir, layer, row, bit);
+                    simulation.queue(chunk::local_to_world(index, position));
+                }
+            }
+        }
+    }
+}
+
+impl FluidVolume for ChunkFluidVolume<'_, '_, '_> {
+    fn get_voxel(&self, position: Vector3<i32>) -> Option<(Option<VoxelHandle>, u8)> {
+        self.get_chunk(chunk::world_to_chunk_index(position))
+            .map(|chunk| {
+                let local = chunk::world_to_local(position);
+                (chunk.sample(local), chunk.get_fluid_level(local))
+            })
+    }
+
+    fn set_voxel(&mut self, position: Vector3<i32>, voxel: Option<VoxelHandle>, level: u8) {
+        let entity = match self.get_entity(chunk::world_to_chunk_index(position)) {
+            Some(entity) => entity,
+            None => return,
+        };
+        if let Ok((mut chunk, state)) = self.chunks.get_mut(entity) {
+            if *state != ChunkState::Queued {
+                chunk.set_fluid(chunk::world_to_local(position), voxel, level);
+            }
+        }
+    }
+}
@@ -16,7 +16,7 @@ use bevy_ecs::{
     system::{NonSend, Res, ResMut, Resource},
 };
 use nalgebra::UnitVector3;
-pub use resource::GameWorld;
+pub use resource::{GameWorld, WORLD_BIND_GROUP_LAYOUT_DESCRIPTOR};
 
 /// Package for the `GameWorld` resource.
 pub struct GameWorldPackage;
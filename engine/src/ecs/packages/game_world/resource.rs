@@ -5,8 +5,9 @@ use bytemuck::{Pod, Zeroable};
 use nalgebra::{vector, UnitVector3};
 use wgpu::{
     util::{BufferInitDescriptor, DeviceExt},
-    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, Buffer, BufferUsages, Device,
-    Queue, RenderPass,
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingType, Buffer, BufferBindingType, BufferUsages, Device, Queue,
+    RenderPass, ShaderStages,
 };
 
 /// Global world state.
@@ -78,3 +79,19 @@ struct WorldUniform {
     sun_position: [f32; 3],
     ambient_light: f32,
 }
+
+/// The game world bind group layout descriptor.
+pub const WORLD_BIND_GROUP_LAYOUT_DESCRIPTOR: BindGroupLayoutDescriptor =
+    BindGroupLayoutDescriptor {
+        label: Some("bind_group_layout_world"),
+        entries: &[BindGroupLayoutEntry {
+            binding: 0,
+            visibility: ShaderStages::FRAGMENT,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        }],
+    };
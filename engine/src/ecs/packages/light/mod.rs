@@ -0,0 +1,360 @@
+use std::{cell::Cell, collections::HashMap};
+
+use bevy_ecs::{
+    change_detection::{DetectChanges as _, DetectChangesMut as _},
+    entity::Entity,
+    schedule::IntoSystemConfigs as _,
+    system::{Local, Query, Res},
+};
+use nalgebra::Vector3;
+
+use crate::{
+    application::Application,
+    common::{
+        chunk::{self, CHUNK_LENGTH},
+        face_dir::FaceDir,
+        light::{self, LightChannel, LightPropagator, LightVolume, MAX_LIGHT_LEVEL},
+        voxel::Voxel,
+    },
+    ecs::{
+        components::{Chunk, ChunkState},
+        schedules::Update,
+    },
+};
+
+use super::{
+    chunk::{self as chunk_package, ChunkMap},
+    voxel_registry::VoxelRegistry,
+    Package,
+};
+
+/// Package that spreads the block light of emissive voxels and the sky light through the loaded chunks.
+///
+/// The light is updated before the chunks are meshed, so the meshes always have the current light baked in.
+pub struct LightPackage;
+
+impl Package for LightPackage {
+    fn initialize(&mut self, app: &mut Application) {
+        app.add_systems(
+            Update,
+            light_system
+                .after(chunk_package::chunk_generation_apply_system)
+                .before(chunk_package::chunk_border_system),
+        );
+    }
+}
+
+/// Updates the light around the changed voxels and lights the newly generated chunks.
+///
+/// Generated chunks get lit by their own emitters, by the sky and by the light at the borders of their neighbours.
+pub fn light_system(
+    mut chunks: ChunkQuery,
+    chunk_map: Res<ChunkMap>,
+    voxel_registry: Res<VoxelRegistry>,
+    mut propagator: Local<LightPropagator>,
+) {
+    let mut relit_chunks = vec![];
+    let mut changed_voxels = vec![];
+    for (mut chunk, state) in chunks.iter_mut() {
+        if *state == ChunkState::Queued || !chunk.is_changed() {
+            continue;
+        }
+
+        let chunk = chunk.bypass_change_detection();
+        let index = chunk.get_index();
+        let changed = chunk.take_changed_voxels();
+        if chunk.take_needs_relight() {
+            relit_chunks.push(index);
+        } else {
+            changed_voxels.extend(
+                changed
+                    .into_iter()
+                    .map(|position| chunk::local_to_world(index, position)),
+            );
+        }
+    }
+
+    if relit_chunks.is_empty() && changed_voxels.is_empty() {
+        return;
+    }
+
+    for channel in [LightChannel::Block, LightChannel::Sky] {
+        let mut volume = ChunkLightVolume {
+            chunks: &mut chunks,
+            chunk_map: &chunk_map,
+            registered_voxels: &voxel_registry.voxels,
+            channel,
+            last_entity: Cell::new(None),
+        };
+        for index in &relit_chunks {
+            match channel {
+                LightChannel::Block => volume.queue_emitters(&mut propagator, *index),
+                LightChannel::Sky => volume.queue_sky(&mut propagator, *index),
+            }
+            volume.queue_borders(&mut propagator, *index);
+        }
+        for position in &changed_voxels {
+            propagator.update_voxel(&mut volume, *position);
+        }
+        propagator.propagate(&mut volume);
+    }
+}
+
+/// The chunks and their states.
+type ChunkQuery<'w, 's> = Query<'w, 's, (&'static mut Chunk, &'static ChunkState)>;
+
+/// A single light channel of the loaded chunks.
+///
+/// Chunks that are still waiting to be generated are treated as unloaded.
+struct ChunkLightVolume<'a, 'w, 's> {
+    chunks: &'a mut ChunkQuery<'w, 's>,
+    chunk_map: &'a ChunkMap,
+    registered_voxels: &'a HashMap<u32, Voxel>,
+    channel: LightChannel,
+    /// The last looked up chunk index and its entity, the flood fills mostly stay inside of the same chunk.
+    last_entity: Cell<Option<(Vector3<i32>, Option<Entity>)>>,
+}
+
+impl ChunkLightVolume<'_, '_, '_> {
+    /// Returns the entity of the chunk with the specified chunk index.
+    fn get_entity(&self, index: Vector3<i32>) -> Option<Entity> {
+        match self.last_entity.get() {
+            Some((last_index, entity)) if last_index == index => entity,
+            _ => {
+                let entity = self.chunk_map.get_entity(index);
+                self.last_entity.set(Some((index, entity)));
+                entity
+            }
+        }
+    }
+
+    /// Returns the generated chunk with the specified chunk index.
+    fn get_chunk(&self, index: Vector3<i32>) -> Option<&Chunk> {
+        let entity = self.get_entity(index)?;
+        match self.chunks.get(entity) {
+            Ok((chunk, state)) if *state != ChunkState::Queued => Some(chunk),
+            _ => None,
+        }
+    }
+
+    /// Returns true if the voxel at the world voxel position is lit by the open sky above it.
+    ///
+    /// The height of the world is not known, so voxels in the top layer of a chunk are open to the sky
+    /// if the chunk above them is not generated.
+    fn is_open_to_sky(&self, position: Vector3<i32>) -> bool {
+        let (_, y, _) = chunk::world_to_local(position);
+        let index = chunk::world_to_chunk_index(position);
+        y == CHUNK_LENGTH - 1
+            && self.get_chunk(index + Vector3::y()).is_none()
+            && self.is_transparent(position)
+    }
+
+    /// Lights the emitters of a chunk and queues them to spread their light.
+    fn queue_emitters(&mut self, propagator: &mut LightPropagator, index: Vector3<i32>) {
+        let chunk = match self.get_chunk(index) {
+            Some(chunk) => chunk,
+            None => return,
+        };
+
+        // Most chunks do not contain any emitters, which the palette tells without walking the voxels.
+        let mut emitters = vec![];
+        let has_emitters = chunk
+            .get_voxels()
+            .get_palette()
+            .iter()
+            .any(|voxel| light::get_emission(self.registered_voxels, *voxel) > 0);
+        if has_emitters {
+            for z in 0..CHUNK_LENGTH {
+                for y in 0..CHUNK_LENGTH {
+                    for x in 0..CHUNK_LENGTH {
+                        let emission =
+                            light::get_emission(self.registered_voxels, chunk.sample((x, y, z)));
+                        if emission > 0 {
+                            emitters.push((chunk::local_to_world(index, (x, y, z)), emission));
+                        }
+                    }
+                }
+            }
+        }
+
+        for (position, emission) in emitters {
+            self.set_light(position, emission);
+            propagator.queue_add(position);
+        }
+    }
+
+    /// Lights the columns of a chunk that the sky reaches straight from above
+    /// and queues the voxels that spread the sky light sideways.
+    ///
+    /// The light that the chunk blocks is removed from the chunk below it.
+    fn queue_sky(&mut self, propagator: &mut LightPropagator, index: Vector3<i32>) {
+        let entity = match self.get_entity(index) {
+            Some(entity) => entity,
+            None => return,
+        };
+
+        // The columns that get the full sky light from above, indexed by z and x.
+        let mut open_columns = [[true; CHUNK_LENGTH]; CHUNK_LENGTH];
+        if let Some(above) = self.get_chunk(index + Vector3::y()) {
+            for (z, columns) in open_columns.iter_mut().enumerate() {
+                for (x, open) in columns.iter_mut().enumerate() {
+                    *open = above.get_light(LightChannel::Sky, (x, 0, z)) == MAX_LIGHT_LEVEL;
+                }
+            }
+        }
+
+        let mut chunk = match self.chunks.get_mut(entity) {
+            Ok((chunk, state)) if *state != ChunkState::Queued => chunk,
+            _ => return,
+        };
+
+        let all_open = open_columns.iter().flatten().all(|open| *open);
+        let uniform_transparent = chunk
+            .get_voxels()
+            .get_uniform_value()
+            .is_some_and(|voxel| light::is_transparent(self.registered_voxels, voxel));
+        let mut blocked_columns = vec![];
+        if all_open && uniform_transparent {
+            // Chunks of air under the open sky are completely lit, so nothing inside of them has to spread.
+            chunk.fill_light(LightChannel::Sky, MAX_LIGHT_LEVEL);
+        } else {
+            // The lowest lit y of every column, indexed by z and x, the sky falls down until it hits a voxel.
+            let mut lit_from = [[CHUNK_LENGTH; CHUNK_LENGTH]; CHUNK_LENGTH];
+            for (z, columns) in open_columns.iter().enumerate() {
+                for (x, _) in columns.iter().enumerate().filter(|(_, open)| **open) {
+                    for y in (0..CHUNK_LENGTH).rev() {
+                        if !light::is_transparent(self.registered_voxels, chunk.sample((x, y, z))) {
+                            break;
+                        }
+                        chunk.set_light(LightChannel::Sky, (x, y, z), MAX_LIGHT_LEVEL);
+                        lit_from[z][x] = y;
+                    }
+                }
+            }
+
+            // Only the lit voxels next to a column that is darker at their height spread sideways,
+            // the chunk borders are handled by `queue_borders`.
+            let is_darker = |x: usize, z: usize, y: usize| {
+                x < CHUNK_LENGTH && z < CHUNK_LENGTH && lit_from[z][x] > y
+            };
+            for (z, columns) in lit_from.iter().enumerate() {
+                for (x, lit_from_y) in columns.iter().enumerate() {
+                    for y in *lit_from_y..CHUNK_LENGTH {
+                        if is_darker(x + 1, z, y)
+                            || is_darker(x.wrapping_sub(1), z, y)
+                            || is_darker(x, z + 1, y)
+                            || is_darker(x, z.wrapping_sub(1), y)
+                        {
+                            propagator.queue_add(chunk::local_to_world(index, (x, y, z)));
+                        }
+                    }
+
+                    if *lit_from_y != 0 {
+                        blocked_columns.push((x, z));
+                    }
+                }
+            }
+        }
+
+        // The chunk below assumed the sky was open while this chunk was not generated,
+        // so the sky light that falls into it through the blocked columns has to be removed.
+        let below = index - Vector3::y();
+        for (x, z) in blocked_columns {
+            let position = chunk::local_to_world(below, (x, CHUNK_LENGTH - 1, z));
+            if self.get_light(position) == Some(MAX_LIGHT_LEVEL) {
+                propagator.update_voxel(self, position);
+            }
+        }
+    }
+
+    /// Queues the voxels on both sides of the borders of a chunk whose light can spread over the border.
+    fn queue_borders(&mut self, propagator: &mut LightPropagator, index: Vector3<i32>) {
+        let chunk = match self.get_chunk(index) {
+            Some(chunk) => chunk,
+            None => return,
+        };
+
+        let mut queued = vec![];
+        for axis in 0..6 {
+            let face_dir = FaceDir::from_axis(axis);
+            let neighbour_index = index + chunk::neighbour_offset(face_dir);
+            let neighbour_face = chunk::opposite_face(face_dir);
+            let neighbour = match self.get_chunk(neighbour_index) {
+                Some(neighbour) => neighbour,
+                None => continue,
+            };
+
+            let border = chunk.get_light_border(self.channel, face_dir);
+            let neighbour_border = neighbour.get_light_border(self.channel, neighbour_face);
+            let layer = chunk::border_layer(face_dir);
+            let neighbour_layer = chunk::border_layer(neighbour_face);
+            for row in 0..CHUNK_LENGTH {
+                for bit in 0..CHUNK_LENGTH {
+                    let level = border[row][bit];
+                    let neighbour_level = neighbour_border[row][bit];
+                    if self.channel.get_spread_level(level, face_dir) > neighbour_level {
+                        let position = chunk::border_position(face_dir, layer, row, bit);
+                        queued.push(chunk::local_to_world(index, position));
+                    }
+                    if self
+                        .channel
+                        .get_spread_level(neighbour_level, neighbour_face)
+                        > level
+                    {
+                        let position =
+                            chunk::border_position(neighbour_face, neighbour_layer, row, bit);
+                        queued.push(chunk::local_to_world(neighbour_index, position));
+                    }
+                }
+            }
+        }
+
+        for position in queued {
+            propagator.queue_add(position);
+        }
+    }
+}
+
+impl LightVolume for ChunkLightVolume<'_, '_, '_> {
+    fn get_light(&self, position: Vector3<i32>) -> Option<u8> {
+        self.get_chunk(chunk::world_to_chunk_index(position))
+            .map(|chunk| chunk.get_light(self.channel, chunk::world_to_local(position)))
+    }
+
+    fn set_light(&mut self, position: Vector3<i32>, level: u8) {
+        let entity = match self.get_entity(chunk::world_to_chunk_index(position)) {
+            Some(entity) => entity,
+            None => return,
+        };
+        if let Ok((mut chunk, state)) = self.chunks.get_mut(entity) {
+            if *state != ChunkState::Queued {
+                chunk.set_light(self.channel, chunk::world_to_local(position), level);
+            }
+        }
+    }
+
+    fn is_transparent(&self, position: Vector3<i32>) -> bool {
+        self.get_chunk(chunk::world_to_chunk_index(position))
+            .is_some_and(|chunk| {
+                let voxel = chunk.sample(chunk::world_to_local(position));
+                light::is_transparent(self.registered_voxels, voxel)
+            })
+    }
+
+    fn get_emission(&self, position: Vector3<i32>) -> u8 {
+        match self.channel {
+            LightChannel::Block => self
+                .get_chunk(chunk::world_to_chunk_index(position))
+                .map_or(0, |chunk| {
+                    let voxel = chunk.sample(chunk::world_to_local(position));
+                    light::get_emission(self.registered_voxels, voxel)
+                }),
+            LightChannel::Sky if self.is_open_to_sky(position) => MAX_LIGHT_LEVEL,
+            LightChannel::Sky => 0,
+        }
+    }
+
+    fn get_spread_level(&self, level: u8, face_dir: FaceDir) -> u8 {
+        self.channel.get_spread_level(level, face_dir)
+    }
+}
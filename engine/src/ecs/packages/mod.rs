@@ -4,16 +4,21 @@ use crate::application::Application;
 pub mod chunk;
 // pub mod config;
 pub mod debug_gui;
+pub mod fluid;
 pub mod game_world;
 pub mod gbuffer;
 // pub mod generator;
 pub mod input_provider;
+pub mod light;
 pub mod logging_init;
+pub mod outline;
 pub mod pipeline_server;
 pub mod render_init;
 pub mod time;
 pub mod voxel_registry;
 pub mod window_surface;
+pub mod world_generator;
+pub mod world_storage;
 
 /// The initialization stage of a package.
 pub enum InitializationStage {
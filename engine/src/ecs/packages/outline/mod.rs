@@ -0,0 +1,42 @@
+mod resource;
+use bevy_ecs::{
+    schedule::IntoSystemConfigs as _,
+    system::{Res, ResMut},
+};
+pub use resource::OutlineRenderer;
+
+use crate::ecs::{schedules::Render, systems};
+
+use super::{render_init::RenderContext, Package};
+
+/// Package for `OutlineRenderer`.
+///
+/// Other packages outline voxel aligned boxes by calling `OutlineRenderer::draw_box` before the
+/// `Render` schedule or before `outline_prepare_system`.
+pub struct OutlinePackage;
+
+impl Package for OutlinePackage {
+    fn initialize(&mut self, app: &mut crate::application::Application) {
+        let outline_renderer = match app.get_resource::<RenderContext>() {
+            Some(render_context) => OutlineRenderer::new(&render_context.device),
+            None => {
+                log::error!("Failed to get render context");
+                return;
+            }
+        };
+
+        app.insert_resource(outline_renderer);
+        app.add_systems(
+            Render,
+            outline_prepare_system.before(systems::render_system),
+        );
+    }
+}
+
+/// Uploads the boxes that were drawn this frame.
+pub fn outline_prepare_system(
+    mut outline_renderer: ResMut<OutlineRenderer>,
+    render_context: Res<RenderContext>,
+) {
+    outline_renderer.prepare(&render_context.device, &render_context.queue);
+}
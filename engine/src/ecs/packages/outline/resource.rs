@@ -0,0 +1,85 @@
+use std::mem;
+
+use bevy_ecs::system::Resource;
+use wgpu::{Buffer, BufferAddress, BufferDescriptor, BufferUsages, Device, Queue, RenderPass};
+
+use crate::{
+    common::aabb::Aabb,
+    rendering::outline_instance::{self, OutlineInstance},
+};
+
+/// The amount of boxes the instance buffer can hold before it has to grow.
+const INITIAL_CAPACITY: usize = 16;
+
+/// Collects the boxes that get outlined in the current frame.
+///
+/// Boxes have to be drawn again every frame, the list is emptied when it gets uploaded.
+#[derive(Resource)]
+pub struct OutlineRenderer {
+    queued: Vec<OutlineInstance>,
+    instance_buffer: Buffer,
+    capacity: usize,
+    instance_count: u32,
+}
+
+impl OutlineRenderer {
+    /// Creates a new `OutlineRenderer`.
+    pub fn new(device: &Device) -> Self {
+        Self {
+            queued: Vec::new(),
+            instance_buffer: create_instance_buffer(device, INITIAL_CAPACITY),
+            capacity: INITIAL_CAPACITY,
+            instance_count: 0,
+        }
+    }
+
+    /// Outlines the box in the current frame.
+    ///
+    /// ## Arguments
+    /// * `aabb` - The box to outline, voxel aligned boxes should be slightly inflated so the
+    ///   lines do not fight with the voxel faces.
+    /// * `color` - The color of the outline, the alpha is used for blending.
+    pub fn draw_box(&mut self, aabb: &Aabb, color: [f32; 4]) {
+        self.queued.push(OutlineInstance::new(aabb, color));
+    }
+
+    /// Uploads the boxes drawn since the last upload to the gpu and empties the list.
+    pub fn prepare(&mut self, device: &Device, queue: &Queue) {
+        if self.queued.len() > self.capacity {
+            self.capacity = self.queued.len().next_power_of_two();
+            self.instance_buffer = create_instance_buffer(device, self.capacity);
+        }
+        if !self.queued.is_empty() {
+            queue.write_buffer(&self.instance_buffer, 0, bytemuck::cast_slice(&self.queued));
+        }
+        self.instance_count = self.queued.len() as u32;
+        self.queued.clear();
+    }
+
+    /// Returns true if there is nothing to render.
+    pub fn is_empty(&self) -> bool {
+        self.instance_count == 0
+    }
+
+    /// Draws the uploaded boxes, the outline pipeline and the camera must already be bound.
+    pub fn render_to_render_pass<'rp, 's: 'rp>(&'s self, render_pass: &mut RenderPass<'rp>) {
+        if self.is_empty() {
+            return;
+        }
+        render_pass.set_vertex_buffer(0, self.instance_buffer.slice(..));
+        render_pass.draw(
+            0..outline_instance::OUTLINE_VERTEX_COUNT,
+            0..self.instance_count,
+        );
+    }
+}
+
+/// Creates an instance buffer that can hold `capacity` boxes.
+fn create_instance_buffer(device: &Device, capacity: usize) -> Buffer {
+    device.create_buffer(&BufferDescriptor {
+        label: Some("buffer_instance_outline"),
+        size: (capacity * mem::size_of::<OutlineInstance>()) as BufferAddress,
+        usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
@@ -5,7 +5,9 @@ pub use resource::PipelineServer;
 use wgpu::Device;
 
 use crate::{
-    rendering::pipelines::{lighting_pipeline::LightingPipeline, Pipeline},
+    rendering::pipelines::{
+        lighting_pipeline::LightingPipeline, outline_pipeline::OutlinePipeline, Pipeline,
+    },
     utils::file_system,
 };
 
@@ -19,16 +21,28 @@ impl Package for PipelineServerPackage {
         let mut server = PipelineServer::default();
 
         match app.get_resource::<RenderContext>() {
-            Some(render_context) => match get_lighting_pipeline(&render_context.device) {
-                Ok(pipeline) => {
-                    server.add_pipeline("lighting".to_owned(), pipeline);
+            Some(render_context) => {
+                match get_lighting_pipeline(&render_context.device) {
+                    Ok(pipeline) => {
+                        server.add_pipeline("lighting".to_owned(), pipeline);
+                    }
+                    Err(e) => {
+                        log::error!("Failed to compile lighting pipeline: {e}");
+                    }
                 }
-                Err(e) => {
-                    log::error!("Failed to compile lighting pipeline: {e}");
+                match get_outline_pipeline(&render_context.device) {
+                    Ok(pipeline) => {
+                        server.add_pipeline("outline".to_owned(), pipeline);
+                    }
+                    Err(e) => {
+                        log::error!("Failed to compile outline pipeline: {e}");
+                    }
                 }
-            },
+            }
             None => {
-                log::error!("Failed to get render context, cannot add lighting pipeline");
+                log::error!(
+                    "Failed to get render context, cannot add lighting and outline pipelines"
+                );
             }
         };
 
@@ -41,3 +55,8 @@ fn get_lighting_pipeline(device: &Device) -> io::Result<Pipeline> {
     let fragment_src = file_system::read_wgsl_shader("lighting")?;
     Ok(LightingPipeline::new(device, &vertex_src, &fragment_src).into())
 }
+
+fn get_outline_pipeline(device: &Device) -> io::Result<Pipeline> {
+    let src = file_system::read_wgsl_shader("outline")?;
+    Ok(OutlinePipeline::new(device, &src).into())
+}
@@ -3,6 +3,12 @@ use wgpu::{Device, DeviceDescriptor, Features, Limits, Queue, RequestDeviceError
 
 use super::gpu_instance::GpuInstance;
 
+/// The features that are enabled if the adapter supports them.
+///
+/// Chunks are drawn through an indirect draw buffer with them, otherwise they fall back to direct draw calls.
+const OPTIONAL_FEATURES: Features =
+    Features::INDIRECT_FIRST_INSTANCE.union(Features::MULTI_DRAW_INDIRECT);
+
 /// This reprents and open connection to the GPU.
 #[derive(Resource)]
 pub struct RenderContext {
@@ -13,13 +19,14 @@ pub struct RenderContext {
 impl RenderContext {
     /// Creates a new `RenderContext`.
     pub async fn new(instance: &GpuInstance) -> Result<Self, RequestDeviceError> {
-        let (device, queue) = instance
-            .get_adapter()
+        let adapter = instance.get_adapter();
+        let (device, queue) = adapter
             .request_device(
                 &DeviceDescriptor {
                     label: Some("device"),
                     required_features: Features::TEXTURE_BINDING_ARRAY
-                        | Features::POLYGON_MODE_LINE,
+                        | Features::POLYGON_MODE_LINE
+                        | (adapter.features() & OPTIONAL_FEATURES),
                     required_limits: Limits {
                         ..Default::default()
                     },
@@ -1,10 +1,11 @@
-use std::{fs::File, io::BufReader, num::NonZeroU32, path::Path};
+use std::{fs::File, io::BufReader, num::NonZeroU32, path::Path, sync::Arc};
 
 use crate::{
     common::voxel::{Voxel, VoxelTexture},
     rendering::{
-        pipelines::{Pipeline, VoxelPipeline},
+        pipelines::{translucent_pipeline::TranslucentPipeline, Pipeline, VoxelPipeline},
         texture_array::{TextureArray, TextureArrayCreationDescriptor},
+        vertex,
     },
     utils::file_system,
 };
@@ -94,6 +95,15 @@ impl Package for VoxelRegistryPackage {
             };
         }
 
+        if images.len() > vertex::MAX_TEXTURE_LAYERS as usize {
+            log::error!(
+                "Too many voxel textures, the limit is {}, got: {}",
+                vertex::MAX_TEXTURE_LAYERS,
+                images.len()
+            );
+            return;
+        }
+
         let dimensions = match images.first() {
             Some((dim, _)) => *dim,
             None => {
@@ -183,7 +193,7 @@ impl Package for VoxelRegistryPackage {
             });
 
         let voxel_registry = VoxelRegistry {
-            voxels: voxels.into_iter().map(|voxel| (voxel.id, voxel)).collect(),
+            voxels: Arc::new(voxels.into_iter().map(|voxel| (voxel.id, voxel)).collect()),
             textures,
             bind_group,
         };
@@ -195,6 +205,18 @@ impl Package for VoxelRegistryPackage {
                 return;
             }
         };
+        let translucent_shader = match file_system::read_wgsl_shader("voxel_translucent_pass") {
+            Ok(shader) => shader,
+            Err(e) => {
+                log::error!("{e}");
+                return;
+            }
+        };
+        let translucent_pipeline = TranslucentPipeline::new(
+            &render_context.device,
+            &voxel_texture_bind_group_layout,
+            &translucent_shader,
+        );
         let pipeline = VoxelPipeline::new(
             &render_context.device,
             voxel_texture_bind_group_layout,
@@ -211,6 +233,10 @@ impl Package for VoxelRegistryPackage {
             }
         };
         pipeline_server.add_pipeline("voxel".to_owned(), Pipeline::Voxel(pipeline));
+        pipeline_server.add_pipeline(
+            "voxel_translucent".to_owned(),
+            Pipeline::Translucent(translucent_pipeline),
+        );
     }
 }
 
@@ -1,4 +1,7 @@
-use std::collections::HashMap;
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
 
 use bevy_ecs::system::Resource;
 use wgpu::{BindGroup, RenderPass};
@@ -8,13 +11,22 @@ use crate::{common::voxel::Voxel, rendering::texture_array::TextureArray};
 /// Contains the data for all the registered voxels and their textures.
 #[derive(Resource)]
 pub struct VoxelRegistry {
-    pub voxels: HashMap<u32, Voxel>,
+    /// The registered voxels, shared with background tasks like chunk meshing.
+    pub voxels: Arc<HashMap<u32, Voxel>>,
     #[allow(unused)]
     pub(super) textures: TextureArray,
     pub(super) bind_group: BindGroup,
 }
 
 impl VoxelRegistry {
+    /// Returns the ids of the registered voxels by their names.
+    pub fn get_voxel_ids(&self) -> BTreeMap<String, u32> {
+        self.voxels
+            .values()
+            .map(|voxel| (voxel.name.clone(), voxel.id))
+            .collect()
+    }
+
     /// Binds the voxel texture array to the render pass;
     pub fn bind_to_render_pass<'rp, 's: 'rp>(&'s self, render_pass: &mut RenderPass<'rp>) {
         render_pass.set_bind_group(1, &self.bind_group, &[]);
@@ -16,7 +16,7 @@ use crate::{
             pipeline_server::PipelineServer,
             render_init::{GpuInstance, RenderContext},
         },
-        resources::{Camera, ScreenQuad},
+        resources::{Camera, RenderStatistics, ScreenQuad},
         schedules::{SentWindowEvent, WindowInit},
     },
     rendering::pipelines::Pipeline,
@@ -94,6 +94,7 @@ impl Package for WindowSurfacePackage {
         let screen_quad = ScreenQuad::new(&render_context.device);
         app.insert_resource(camera);
         app.insert_resource(screen_quad);
+        app.insert_resource(RenderStatistics::default());
         app.insert_resource(window);
         app.insert_resource(surface);
 
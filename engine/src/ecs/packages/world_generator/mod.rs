@@ -0,0 +1,46 @@
+use std::sync::Arc;
+
+use crate::{application::Application, common::world_generator::WorldGenerator};
+
+use super::{chunk::ChunkGenerationQueue, Package};
+
+/// Package that generates the chunks streamed in by the `ChunkPackage` with a `WorldGenerator`.
+pub struct WorldGeneratorPackage<G: WorldGenerator> {
+    generator: Option<G>,
+    max_jobs_in_flight: usize,
+}
+
+impl<G: WorldGenerator> WorldGeneratorPackage<G> {
+    /// Creates a new package for the specified generator.
+    pub fn new(generator: G) -> Self {
+        Self {
+            generator: Some(generator),
+            // Keeps the generator threads busy between frames without committing too far ahead of the camera.
+            max_jobs_in_flight: rayon::current_num_threads() * 2,
+        }
+    }
+
+    /// Sets the maximum amount of chunks that are generated at the same time.
+    pub fn with_max_jobs_in_flight(mut self, max_jobs_in_flight: usize) -> Self {
+        self.max_jobs_in_flight = max_jobs_in_flight;
+        self
+    }
+}
+
+impl<G: WorldGenerator> Package for WorldGeneratorPackage<G> {
+    fn initialize(&mut self, app: &mut Application) {
+        let generator = match self.generator.take() {
+            Some(generator) => generator,
+            None => {
+                log::error!("World generator package was initialized more than once");
+                return;
+            }
+        };
+
+        log::info!("Generating world with seed {}", generator.get_seed());
+        match ChunkGenerationQueue::new(Arc::new(generator), self.max_jobs_in_flight) {
+            Ok(queue) => app.insert_resource(queue),
+            Err(e) => log::error!("Failed to create the chunk generation queue: {e}"),
+        }
+    }
+}
@@ -0,0 +1,116 @@
+use std::{path::PathBuf, time::Duration};
+
+use bevy_ecs::{
+    change_detection::DetectChangesMut as _,
+    query::Changed,
+    schedule::IntoSystemConfigs as _,
+    system::{Query, Res, ResMut},
+};
+
+use crate::{
+    application::Application,
+    common::region::VoxelIdMap,
+    ecs::{
+        components::Chunk,
+        schedules::{Exit, Update},
+    },
+};
+
+use super::{chunk, Package};
+
+mod resource;
+pub use resource::WorldStorage;
+
+/// Package for `WorldStorage`.
+///
+/// Chunks are loaded from the region files instead of being generated if they have been saved before.
+/// The changed chunks are saved periodically in the background and when the application exits.
+pub struct WorldStoragePackage {
+    regions_dir: PathBuf,
+    voxel_ids: VoxelIdMap,
+    save_interval: Duration,
+}
+
+impl WorldStoragePackage {
+    /// Creates a new package that stores the region files in the specified directory.
+    pub fn new<P: Into<PathBuf>>(regions_dir: P) -> Self {
+        Self {
+            regions_dir: regions_dir.into(),
+            voxel_ids: VoxelIdMap::default(),
+            save_interval: Duration::from_secs(30),
+        }
+    }
+
+    /// Sets how the voxel ids stored in the region files translate to the ids of the registered voxels.
+    ///
+    /// By default the ids are stored as they are.
+    pub fn with_voxel_ids(mut self, voxel_ids: VoxelIdMap) -> Self {
+        self.voxel_ids = voxel_ids;
+        self
+    }
+
+    /// Sets how often the changed chunks get written to disk in the background.
+    pub fn with_save_interval(mut self, save_interval: Duration) -> Self {
+        self.save_interval = save_interval;
+        self
+    }
+}
+
+impl Package for WorldStoragePackage {
+    fn initialize(&mut self, app: &mut Application) {
+        let world_storage = match WorldStorage::new(
+            &self.regions_dir,
+            self.voxel_ids.clone(),
+            self.save_interval,
+        ) {
+            Ok(storage) => storage,
+            Err(e) => {
+                log::error!(
+                    "Failed to open the world storage {:?}: {e}",
+                    self.regions_dir
+                );
+                return;
+            }
+        };
+
+        app.insert_resource(world_storage);
+        app.add_systems(
+            Update,
+            (collect_unsaved_chunks_system, periodic_save_system)
+                .chain()
+                .run_if(is_save_due)
+                .after(chunk::chunk_streaming_system),
+        );
+        app.add_systems(Exit, (collect_unsaved_chunks_system, save_system).chain());
+    }
+}
+
+/// Returns true if the save interval has passed and no background save is running.
+fn is_save_due(world_storage: Res<WorldStorage>) -> bool {
+    world_storage.is_save_due() && !world_storage.is_saving()
+}
+
+/// Queues the changed chunks to be saved.
+///
+/// This only runs right before a save, chunks that get unloaded in the meantime are queued by the `ChunkPackage`.
+pub fn collect_unsaved_chunks_system(
+    mut chunks: Query<&mut Chunk, Changed<Chunk>>,
+    world_storage: Res<WorldStorage>,
+) {
+    for mut chunk in chunks.iter_mut() {
+        if chunk.is_unsaved() {
+            world_storage.get_region_storage().queue_save(&chunk);
+            chunk.bypass_change_detection().mark_saved();
+        }
+    }
+}
+
+/// Saves the changed chunks in the background.
+fn periodic_save_system(mut world_storage: ResMut<WorldStorage>) {
+    world_storage.save_in_background();
+}
+
+/// Saves the changed chunks and waits until they are written.
+fn save_system(mut world_storage: ResMut<WorldStorage>) {
+    world_storage.save();
+}
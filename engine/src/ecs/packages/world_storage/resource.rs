@@ -0,0 +1,96 @@
+use std::{
+    io,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use bevy_ecs::system::Resource;
+
+use crate::common::region::{RegionStorage, VoxelIdMap};
+
+/// Persists the changed chunks of a world to its region files.
+#[derive(Resource)]
+pub struct WorldStorage {
+    storage: Arc<RegionStorage>,
+    /// How often the changed chunks get written to disk in the background.
+    pub save_interval: Duration,
+    last_save: Instant,
+    saving: Arc<AtomicBool>,
+}
+
+impl WorldStorage {
+    /// Creates a new storage for the region files in the specified directory.
+    ///
+    /// ## Arguments
+    /// * `regions_dir` - The directory of the region files.
+    /// * `voxel_ids` - Translates between the stored voxel ids and the ids of the registered voxels.
+    /// * `save_interval` - How often the changed chunks get written to disk in the background.
+    pub fn new<P: AsRef<Path>>(
+        regions_dir: P,
+        voxel_ids: VoxelIdMap,
+        save_interval: Duration,
+    ) -> io::Result<Self> {
+        Ok(Self {
+            storage: Arc::new(RegionStorage::new(regions_dir, voxel_ids)?),
+            save_interval,
+            last_save: Instant::now(),
+            saving: Arc::new(AtomicBool::new(false)),
+        })
+    }
+
+    /// Returns the region storage, it can be shared with background threads.
+    pub fn get_region_storage(&self) -> &Arc<RegionStorage> {
+        &self.storage
+    }
+
+    /// Returns true if a background save is running.
+    pub fn is_saving(&self) -> bool {
+        self.saving.load(Ordering::Acquire)
+    }
+
+    /// Returns true if the save interval has passed since the last save.
+    pub fn is_save_due(&self) -> bool {
+        self.last_save.elapsed() >= self.save_interval
+    }
+
+    /// Starts writing the changed chunks on a background thread.
+    ///
+    /// Does nothing if a background save is already running.
+    pub fn save_in_background(&mut self) {
+        if self.saving.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        self.last_save = Instant::now();
+
+        let storage = self.storage.clone();
+        let saving = self.saving.clone();
+        let spawned = thread::Builder::new()
+            .name("world-saver".to_owned())
+            .spawn(move || {
+                match storage.flush() {
+                    Ok(0) => {}
+                    Ok(count) => log::info!("Saved {count} chunks"),
+                    Err(e) => log::error!("Failed to save chunks: {e}"),
+                }
+                saving.store(false, Ordering::Release);
+            });
+        if let Err(e) = spawned {
+            log::error!("Failed to spawn the world saving thread: {e}");
+            self.saving.store(false, Ordering::Release);
+        }
+    }
+
+    /// Writes the changed chunks and blocks until they are written.
+    pub fn save(&mut self) {
+        self.last_save = Instant::now();
+        match self.storage.flush() {
+            Ok(count) => log::info!("Saved {count} chunks"),
+            Err(e) => log::error!("Failed to save chunks: {e}"),
+        }
+    }
+}
@@ -1,6 +1,6 @@
 use bevy_ecs::system::Resource;
 use bytemuck::{Pod, Zeroable};
-use nalgebra::Matrix4;
+use nalgebra::{Matrix4, Point3};
 use wgpu::{
     util::{BufferInitDescriptor, DeviceExt},
     BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
@@ -8,9 +8,15 @@ use wgpu::{
     Device, Queue, RenderPass, ShaderStages,
 };
 
+use crate::common::frustum::Frustum;
+
 /// Camera resource this is used to render from the perspective of the user.
 #[derive(Resource)]
 pub struct Camera {
+    /// The position of the camera in world space, the same as in the uniform buffer.
+    position: Point3<f32>,
+    /// The volume the camera can see in world space, taken from the view projection matrix in the uniform buffer.
+    frustum: Frustum,
     uniform_buffer: Buffer,
     bind_group: BindGroup,
 }
@@ -39,6 +45,8 @@ impl Camera {
         });
 
         Self {
+            position: position_of(&camera_uniform),
+            frustum: frustum_of(&camera_uniform),
             uniform_buffer,
             bind_group,
         }
@@ -49,7 +57,9 @@ impl Camera {
     /// ## Arguments
     /// * `queue` - The queue to use for writing the provided data to the uniform buffer.
     /// * `camera_uniform` - The camera uniform to write to the uniform buffer.
-    pub fn update_camera(&self, queue: &Queue, camera_uniform: CameraUniform) {
+    pub fn update_camera(&mut self, queue: &Queue, camera_uniform: CameraUniform) {
+        self.position = position_of(&camera_uniform);
+        self.frustum = frustum_of(&camera_uniform);
         queue.write_buffer(
             &self.uniform_buffer,
             0,
@@ -57,12 +67,33 @@ impl Camera {
         );
     }
 
+    /// Returns the position of the camera in world space.
+    pub fn get_position(&self) -> Point3<f32> {
+        self.position
+    }
+
+    /// Returns the volume the camera can see in world space.
+    pub fn get_frustum(&self) -> &Frustum {
+        &self.frustum
+    }
+
     /// Binds the camera to the render pass.
     pub fn bind_to_render_pass<'rp, 's: 'rp>(&'s self, render_pass: &mut RenderPass<'rp>) {
         render_pass.set_bind_group(0, &self.bind_group, &[]);
     }
 }
 
+/// Returns the world space position stored in a camera uniform.
+fn position_of(camera_uniform: &CameraUniform) -> Point3<f32> {
+    let [x, y, z, _] = camera_uniform.position;
+    Point3::new(x, y, z)
+}
+
+/// Returns the frustum of the view projection matrix stored in a camera uniform.
+fn frustum_of(camera_uniform: &CameraUniform) -> Frustum {
+    Frustum::from_view_proj(&Matrix4::from(camera_uniform.view_proj))
+}
+
 /// The raw camera uniform to send to the GPU.
 #[repr(C)]
 #[derive(Clone, Copy, PartialEq, Pod, Zeroable)]
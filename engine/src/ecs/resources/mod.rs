@@ -1,4 +1,6 @@
 pub mod camera;
 pub use camera::Camera;
+mod render_statistics;
+pub use render_statistics::RenderStatistics;
 mod screen_quad;
 pub use screen_quad::ScreenQuad;
@@ -0,0 +1,10 @@
+use bevy_ecs::system::Resource;
+
+/// Counts of what has been rendered in the last frame, for debugging.
+#[derive(Resource, Default, Clone, Copy, Debug)]
+pub struct RenderStatistics {
+    /// The amount of geometries and chunk meshes that were drawn.
+    pub drawn_objects: usize,
+    /// The amount of geometries and chunks that were skipped because the camera can not see them.
+    pub culled_objects: usize,
+}
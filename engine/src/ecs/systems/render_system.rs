@@ -2,7 +2,7 @@
 
 use std::{fs::File, io::BufWriter};
 
-use bevy_ecs::system::{NonSendMut, Query, Res};
+use bevy_ecs::system::{NonSendMut, Query, Res, ResMut};
 use wgpu::{
     BufferDescriptor, BufferUsages, Color, CommandEncoderDescriptor, Extent3d, ImageCopyBuffer,
     ImageCopyBufferBase, ImageCopyTexture, ImageCopyTextureBase, ImageDataLayout, LoadOp,
@@ -12,24 +12,26 @@ use wgpu::{
 
 use crate::{
     ecs::{
-        components::{Geometry, RenderDescriptor},
+        components::{Geometry, RenderBounds, RenderDescriptor},
         packages::{
+            chunk::ChunkMeshAllocator,
             debug_gui::DebugCompositor,
             game_world::GameWorld,
             gbuffer::GBuffer,
+            outline::OutlineRenderer,
             pipeline_server::PipelineServer,
             render_init::RenderContext,
             voxel_registry::VoxelRegistry,
             window_surface::{Window, WindowRenderSurface},
         },
-        resources::{Camera, ScreenQuad},
+        resources::{Camera, RenderStatistics, ScreenQuad},
     },
     rendering::pipelines::PipelineTrait as _,
 };
 
 #[allow(clippy::too_many_arguments)]
 pub fn render_system(
-    render_query: Query<(&RenderDescriptor, &Geometry)>,
+    render_query: Query<(&RenderDescriptor, &Geometry, Option<&RenderBounds>)>,
     render_surface: Res<WindowRenderSurface>,
     pipeline_server: Res<PipelineServer>,
     context: Res<RenderContext>,
@@ -40,11 +42,20 @@ pub fn render_system(
     render_context: Res<RenderContext>,
     screen_quad: Res<ScreenQuad>,
     gbuffer: Res<GBuffer>,
+    outline_renderer: Option<Res<OutlineRenderer>>,
+    chunk_mesh_allocator: Option<Res<ChunkMeshAllocator>>,
+    render_statistics: Option<ResMut<RenderStatistics>>,
     mut debug_compositor: Option<NonSendMut<DebugCompositor>>,
 ) {
     let output = render_surface.get_texture().unwrap();
     let output_view = output.texture.create_view(&Default::default());
 
+    let mut statistics = RenderStatistics::default();
+    if let Some(chunk_mesh_allocator) = &chunk_mesh_allocator {
+        statistics.drawn_objects += chunk_mesh_allocator.draw_count();
+        statistics.culled_objects += chunk_mesh_allocator.culled_count();
+    }
+
     let mut command_encoder = context
         .device
         .create_command_encoder(&CommandEncoderDescriptor {
@@ -93,7 +104,13 @@ pub fn render_system(
             occlusion_query_set: None,
         });
 
-        for (desc, geometry) in render_query.iter() {
+        let frustum = camera.get_frustum();
+        for (desc, geometry, bounds) in render_query.iter() {
+            if bounds.is_some_and(|RenderBounds(bounds)| !frustum.intersects_aabb(bounds)) {
+                statistics.culled_objects += 1;
+                continue;
+            }
+
             let pipeline = match pipeline_server.get_pipeline(&desc.pipeline_name) {
                 Some(pipeline) => pipeline,
                 None => {
@@ -108,6 +125,21 @@ pub fn render_system(
             voxel_textures.bind_to_render_pass(&mut render_pass);
 
             geometry.render_to_render_pass(&mut render_pass);
+            statistics.drawn_objects += 1;
+        }
+
+        if let Some(chunk_mesh_allocator) = &chunk_mesh_allocator {
+            match pipeline_server.get_pipeline("voxel") {
+                Some(pipeline) => {
+                    pipeline.bind_to_render_pass(&mut render_pass);
+                    camera.bind_to_render_pass(&mut render_pass);
+                    voxel_textures.bind_to_render_pass(&mut render_pass);
+                    chunk_mesh_allocator.render_opaque(&mut render_pass);
+                }
+                None => {
+                    log::error!("Could not find voxel pipeline");
+                }
+            }
         }
     }
 
@@ -144,6 +176,83 @@ pub fn render_system(
         }
     }
 
+    // Translucent pass
+    {
+        let mut render_pass = command_encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("render_pass_translucent"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: &output_view,
+                resolve_target: None,
+                ops: Operations {
+                    load: LoadOp::Load,
+                    store: StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                view: &gbuffer.depth_texture.view,
+                depth_ops: Some(Operations {
+                    load: LoadOp::Load,
+                    store: StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+
+        // The chunk meshes are already sorted by the `ChunkMeshAllocator`.
+        if let Some(chunk_mesh_allocator) = &chunk_mesh_allocator {
+            match pipeline_server.get_pipeline("voxel_translucent") {
+                Some(pipeline) => {
+                    pipeline.bind_to_render_pass(&mut render_pass);
+                    camera.bind_to_render_pass(&mut render_pass);
+                    voxel_textures.bind_to_render_pass(&mut render_pass);
+                    game_world.bind_to_render_pass(&mut render_pass);
+                    chunk_mesh_allocator.render_translucent(&mut render_pass);
+                }
+                None => {
+                    log::error!("Could not find voxel translucent pipeline");
+                }
+            }
+        }
+    }
+
+    // Outline pass
+    if let Some(outline_renderer) = outline_renderer.filter(|renderer| !renderer.is_empty()) {
+        let mut render_pass = command_encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("render_pass_outline"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: &output_view,
+                resolve_target: None,
+                ops: Operations {
+                    load: LoadOp::Load,
+                    store: StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                view: &gbuffer.depth_texture.view,
+                depth_ops: Some(Operations {
+                    load: LoadOp::Load,
+                    store: StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+
+        match pipeline_server.get_pipeline("outline") {
+            Some(pipeline) => {
+                pipeline.bind_to_render_pass(&mut render_pass);
+                camera.bind_to_render_pass(&mut render_pass);
+                outline_renderer.render_to_render_pass(&mut render_pass);
+            }
+            None => {
+                log::error!("Could not find outline pass pipeline");
+            }
+        }
+    }
+
     // Debug compositor pass
     if let Some(debug_compositor) = debug_compositor.as_mut() {
         let render_pass = command_encoder.begin_render_pass(&RenderPassDescriptor {
@@ -166,5 +275,9 @@ pub fn render_system(
 
     context.queue.submit(Some(command_encoder.finish()));
 
+    if let Some(mut render_statistics) = render_statistics {
+        *render_statistics = statistics;
+    }
+
     output.present();
 }
@@ -0,0 +1,103 @@
+/// Hands out ranges of elements inside a buffer of a fixed capacity.
+///
+/// Freed ranges are merged with their free neighbours, so the buffer does not fall apart into
+/// ranges that are too small to be reused.
+#[derive(Clone, Debug)]
+pub struct FreeList {
+    /// The free ranges as offset and length, sorted by offset and never touching each other.
+    free_ranges: Vec<(u32, u32)>,
+    capacity: u32,
+}
+
+impl FreeList {
+    /// Creates a new `FreeList` where all of the `capacity` elements are free.
+    pub fn new(capacity: u32) -> Self {
+        Self {
+            free_ranges: if capacity > 0 {
+                vec![(0, capacity)]
+            } else {
+                vec![]
+            },
+            capacity,
+        }
+    }
+
+    /// Returns the amount of elements the buffer can hold.
+    pub fn capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    /// Returns the amount of elements that are allocated.
+    pub fn used(&self) -> u32 {
+        self.capacity - self.free_ranges.iter().map(|(_, len)| len).sum::<u32>()
+    }
+
+    /// Allocates a range of elements inside of the first free range that is large enough.
+    ///
+    /// ## Returns
+    /// The offset of the range, or `None` if there is no free range that is large enough.
+    pub fn allocate(&mut self, len: u32) -> Option<u32> {
+        let index = self
+            .free_ranges
+            .iter()
+            .position(|(_, free_len)| *free_len >= len)?;
+        let (offset, free_len) = self.free_ranges[index];
+        if free_len == len {
+            self.free_ranges.remove(index);
+        } else {
+            self.free_ranges[index] = (offset + len, free_len - len);
+        }
+        Some(offset)
+    }
+
+    /// Frees a range that has been returned by `allocate`.
+    ///
+    /// ## Panics
+    /// If the range is out of bounds.
+    pub fn free(&mut self, offset: u32, len: u32) {
+        assert!(
+            offset + len <= self.capacity,
+            "range {offset}..{} out of bounds",
+            offset + len
+        );
+        if len == 0 {
+            return;
+        }
+
+        let index = self
+            .free_ranges
+            .partition_point(|(free_offset, _)| *free_offset < offset);
+        let merges_previous = index > 0 && {
+            let (previous_offset, previous_len) = self.free_ranges[index - 1];
+            previous_offset + previous_len == offset
+        };
+        let merges_next = self
+            .free_ranges
+            .get(index)
+            .is_some_and(|(next_offset, _)| offset + len == *next_offset);
+
+        match (merges_previous, merges_next) {
+            (true, true) => {
+                let (_, next_len) = self.free_ranges.remove(index);
+                self.free_ranges[index - 1].1 += len + next_len;
+            }
+            (true, false) => self.free_ranges[index - 1].1 += len,
+            (false, true) => {
+                let next = &mut self.free_ranges[index];
+                *next = (offset, next.1 + len);
+            }
+            (false, false) => self.free_ranges.insert(index, (offset, len)),
+        }
+    }
+
+    /// Grows the buffer to a larger capacity, the new elements are free.
+    pub fn grow(&mut self, capacity: u32) {
+        if capacity <= self.capacity {
+            return;
+        }
+
+        let old_capacity = self.capacity;
+        self.capacity = capacity;
+        self.free(old_capacity, capacity - old_capacity);
+    }
+}
@@ -1,6 +1,8 @@
 use wgpu::IndexFormat;
 
 /// The index type used for the index buffer.
-pub type Index = u16;
+///
+/// Chunk meshes can have more than `u16::MAX` vertices once faces with different lighting stop merging.
+pub type Index = u32;
 /// The `wgpu::IndexFormat` used for the index buffer.
-pub const INDEX_FORMAT: IndexFormat = IndexFormat::Uint16;
+pub const INDEX_FORMAT: IndexFormat = IndexFormat::Uint32;
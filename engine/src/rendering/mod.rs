@@ -1,8 +1,10 @@
 use wgpu::TextureFormat;
 
 pub mod depth_texture;
+pub mod free_list;
 pub mod index;
 pub mod instance;
+pub mod outline_instance;
 pub mod pipelines;
 pub mod simple_vertex;
 pub mod texture;
@@ -0,0 +1,48 @@
+use std::mem;
+
+use bytemuck::{Pod, Zeroable};
+use wgpu::{BufferAddress, VertexBufferLayout, VertexStepMode};
+
+use crate::common::aabb::Aabb;
+
+/// A single outlined box to be passed into the outline vertex shader.
+///
+/// The outline pipeline has no vertex buffer, the edges of the box are built from the vertex index.
+#[repr(C)]
+#[derive(Default, Clone, Copy, Debug, PartialEq, Pod, Zeroable)]
+pub struct OutlineInstance {
+    /// The corner of the box with the smallest coordinates.
+    pub min: [f32; 3],
+    /// The corner of the box with the largest coordinates.
+    pub max: [f32; 3],
+    /// The color of the outline, the alpha is used for blending.
+    pub color: [f32; 4],
+}
+
+impl OutlineInstance {
+    /// Creates a new instance that outlines the box with the specified color.
+    pub fn new(aabb: &Aabb, color: [f32; 4]) -> Self {
+        Self {
+            min: aabb.min.into(),
+            max: aabb.max.into(),
+            color,
+        }
+    }
+
+    /// Returns the instance buffer layout.
+    pub fn buffer_layout() -> VertexBufferLayout<'static> {
+        VertexBufferLayout {
+            array_stride: mem::size_of::<OutlineInstance>() as BufferAddress,
+            step_mode: VertexStepMode::Instance,
+            attributes: &OUTLINE_INSTANCE_ATTRIBUTES,
+        }
+    }
+}
+
+/// The amount of vertices used to draw the edges of a single box as a line list.
+pub const OUTLINE_VERTEX_COUNT: u32 = 24;
+/// The number of outline instance attributes.
+pub const OUTLINE_INSTANCE_ATTRIBUTE_COUNT: usize = 3;
+/// The outline instance attributes.
+pub const OUTLINE_INSTANCE_ATTRIBUTES: [wgpu::VertexAttribute; OUTLINE_INSTANCE_ATTRIBUTE_COUNT] =
+    wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3, 2 => Float32x4];
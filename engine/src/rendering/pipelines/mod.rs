@@ -1,10 +1,15 @@
 pub mod lighting_pipeline;
+pub mod outline_pipeline;
+pub mod translucent_pipeline;
 pub mod voxel_pipeline;
 use enum_dispatch::enum_dispatch;
 pub use voxel_pipeline::VoxelPipeline;
 use wgpu::RenderPass;
 
-use self::lighting_pipeline::LightingPipeline;
+use self::{
+    lighting_pipeline::LightingPipeline, outline_pipeline::OutlinePipeline,
+    translucent_pipeline::TranslucentPipeline,
+};
 
 #[enum_dispatch]
 pub trait PipelineTrait {
@@ -17,4 +22,6 @@ pub trait PipelineTrait {
 pub enum Pipeline {
     Voxel(VoxelPipeline),
     Lighting(LightingPipeline),
+    Outline(OutlinePipeline),
+    Translucent(TranslucentPipeline),
 }
@@ -0,0 +1,96 @@
+use wgpu::{
+    BindGroupLayout, BlendState, ColorTargetState, ColorWrites, CompareFunction, DepthStencilState,
+    Device, FragmentState, FrontFace, MultisampleState, PipelineLayoutDescriptor, PolygonMode,
+    PrimitiveState, PrimitiveTopology, RenderPass, RenderPipeline, RenderPipelineDescriptor,
+    ShaderModuleDescriptor, ShaderSource, VertexState,
+};
+
+use crate::{
+    ecs::resources::camera,
+    rendering::{self, depth_texture, outline_instance::OutlineInstance},
+};
+
+/// A pipeline for drawing the edges of boxes on top of the lit scene.
+///
+/// The lines are depth tested against the `GBuffer` depth, so geometry in front of a box hides it.
+pub struct OutlinePipeline {
+    pipeline: RenderPipeline,
+    pub camera_bind_group_layout: BindGroupLayout,
+}
+
+impl super::PipelineTrait for OutlinePipeline {
+    fn bind_to_render_pass<'rp, 's: 'rp>(&'s self, render_pass: &mut RenderPass<'rp>) {
+        render_pass.set_pipeline(&self.pipeline);
+    }
+}
+
+impl OutlinePipeline {
+    /// Creates a new `OutlinePipeline`.
+    ///
+    /// ## Arguments
+    /// * `device` - The `wgpu::Device` to use for compiling.
+    /// * `src` - The shader source code.
+    pub fn new(device: &Device, src: &str) -> Self {
+        let module = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("shader_module_outline"),
+            source: ShaderSource::Wgsl(src.into()),
+        });
+
+        let camera_bind_group_layout =
+            device.create_bind_group_layout(&camera::CAMERA_BIND_GROUP_LAYOUT_DESCRIPTOR);
+
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("pipeline_layout_outline"),
+            bind_group_layouts: &[&camera_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("pipeline_outline"),
+            layout: Some(&pipeline_layout),
+            vertex: VertexState {
+                module: &module,
+                entry_point: "outline_vertex",
+                compilation_options: Default::default(),
+                buffers: &[OutlineInstance::buffer_layout()],
+            },
+            primitive: PrimitiveState {
+                topology: PrimitiveTopology::LineList,
+                strip_index_format: None,
+                front_face: FrontFace::Ccw,
+                cull_mode: None,
+                unclipped_depth: false,
+                polygon_mode: PolygonMode::Fill,
+                conservative: false,
+            },
+            depth_stencil: Some(DepthStencilState {
+                format: depth_texture::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: CompareFunction::LessEqual,
+                stencil: Default::default(),
+                bias: Default::default(),
+            }),
+            multisample: MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            fragment: Some(FragmentState {
+                module: &module,
+                entry_point: "outline_fragment",
+                compilation_options: Default::default(),
+                targets: &[Some(ColorTargetState {
+                    format: rendering::OUTPUT_TEXTURE_FORMAT,
+                    blend: Some(BlendState::ALPHA_BLENDING),
+                    write_mask: ColorWrites::all(),
+                })],
+            }),
+            multiview: None,
+        });
+
+        Self {
+            pipeline,
+            camera_bind_group_layout,
+        }
+    }
+}
@@ -0,0 +1,110 @@
+use wgpu::{
+    BindGroupLayout, BlendState, ColorTargetState, ColorWrites, DepthStencilState, Device, Face,
+    FragmentState, FrontFace, MultisampleState, PipelineLayoutDescriptor, PolygonMode,
+    PrimitiveState, PrimitiveTopology, RenderPass, RenderPipeline, RenderPipelineDescriptor,
+    ShaderModuleDescriptor, ShaderSource, VertexState,
+};
+
+use crate::{
+    ecs::{packages::game_world, resources::camera},
+    rendering::{self, depth_texture, instance::Instance, vertex::Vertex},
+};
+
+/// A pipeline for rendering translucent voxels on top of the lit scene.
+///
+/// The voxels are lit in the fragment shader and blended with the output,
+/// they are depth tested against the `GBuffer` depth without writing to it.
+pub struct TranslucentPipeline {
+    pipeline: RenderPipeline,
+    pub camera_bind_group_layout: BindGroupLayout,
+    pub world_bind_group_layout: BindGroupLayout,
+}
+
+impl super::PipelineTrait for TranslucentPipeline {
+    fn bind_to_render_pass<'rp, 's: 'rp>(&'s self, render_pass: &mut RenderPass<'rp>) {
+        render_pass.set_pipeline(&self.pipeline);
+    }
+}
+
+impl TranslucentPipeline {
+    /// Creates a new `TranslucentPipeline`.
+    ///
+    /// ## Arguments
+    /// * `device` - The `wgpu::Device` to use for compiling.
+    /// * `voxel_texture_bind_group_layout` - The layout of the voxel texture bind group.
+    /// * `src` - The shader source code.
+    pub fn new(
+        device: &Device,
+        voxel_texture_bind_group_layout: &BindGroupLayout,
+        src: &str,
+    ) -> Self {
+        let module = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("shader_module_voxel_translucent"),
+            source: ShaderSource::Wgsl(src.into()),
+        });
+
+        let camera_bind_group_layout =
+            device.create_bind_group_layout(&camera::CAMERA_BIND_GROUP_LAYOUT_DESCRIPTOR);
+        let world_bind_group_layout =
+            device.create_bind_group_layout(&game_world::WORLD_BIND_GROUP_LAYOUT_DESCRIPTOR);
+
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("pipeline_layout_voxel_translucent"),
+            bind_group_layouts: &[
+                &camera_bind_group_layout,
+                voxel_texture_bind_group_layout,
+                &world_bind_group_layout,
+            ],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("pipeline_voxel_translucent"),
+            layout: Some(&pipeline_layout),
+            vertex: VertexState {
+                module: &module,
+                entry_point: "voxel_vertex",
+                compilation_options: Default::default(),
+                buffers: &[Vertex::buffer_layout(), Instance::buffer_layout()],
+            },
+            primitive: PrimitiveState {
+                topology: PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: FrontFace::Ccw,
+                cull_mode: Some(Face::Back),
+                unclipped_depth: false,
+                polygon_mode: PolygonMode::Fill,
+                conservative: false,
+            },
+            depth_stencil: Some(DepthStencilState {
+                format: depth_texture::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: depth_texture::DEPTH_COMPARE,
+                stencil: Default::default(),
+                bias: Default::default(),
+            }),
+            multisample: MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            fragment: Some(FragmentState {
+                module: &module,
+                entry_point: "voxel_fragment",
+                compilation_options: Default::default(),
+                targets: &[Some(ColorTargetState {
+                    format: rendering::OUTPUT_TEXTURE_FORMAT,
+                    blend: Some(BlendState::ALPHA_BLENDING),
+                    write_mask: ColorWrites::all(),
+                })],
+            }),
+            multiview: None,
+        });
+
+        Self {
+            pipeline,
+            camera_bind_group_layout,
+            world_bind_group_layout,
+        }
+    }
+}
@@ -1,24 +1,68 @@
 use std::mem;
 
 use bytemuck::{Pod, Zeroable};
+use nalgebra::Vector3;
 use serde::{Deserialize, Serialize};
 use wgpu::{BufferAddress, VertexBufferLayout, VertexStepMode};
 
-/// 3D Vertex type.
+use crate::common::{chunk, light::LightLevels};
+
+/// The amount of steps per voxel that the positions and the texture coordinates are stored in.
+pub const POSITION_STEPS: f32 = 2.0;
+/// The index of the first normal of the `VoxelShape::Cross` diagonals, the face directions come before it.
+pub const CROSS_NORMAL_INDEX: u8 = 6;
+/// The amount of texture layers that a vertex can address.
+pub const MAX_TEXTURE_LAYERS: u32 = 256;
+
+/// Packed chunk vertex type.
+///
+/// Everything is stored in two words:
+/// * The first word holds the chunk local position in half voxels with 8 bits per axis,
+///   followed by the normal index in 4 bits and the ambient occlusion level in 2 bits.
+/// * The second word holds the texture coordinates in half voxels with 8 bits per axis,
+///   followed by the block and the sky light level in 4 bits each and the texture layer in 8 bits.
 #[repr(C)]
-#[derive(Default, Clone, Copy, Debug, PartialEq, Pod, Zeroable, Serialize, Deserialize)]
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq, Pod, Zeroable, Serialize, Deserialize)]
 pub struct Vertex {
-    /// The position of the vertex in local space.
-    pub position: [f32; 3],
-    /// The texture coordinates of the vertex.
-    pub tex_coords: [f32; 2],
-    /// The normal of the vertex.
-    pub normal: [f32; 3],
-    /// The index of the texture used by the vertex.
-    pub texture_index: [u32; 3],
+    /// The packed vertex data.
+    pub data: [u32; 2],
 }
 
 impl Vertex {
+    /// Packs a vertex.
+    ///
+    /// ## Arguments
+    /// * `position` - The position of the vertex in chunk local space, from 0 to `CHUNK_LENGTH`.
+    /// * `tex_coords` - The texture coordinates of the vertex, from 0 to `CHUNK_LENGTH`.
+    /// * `normal_index` - The face direction of the vertex, or `CROSS_NORMAL_INDEX` plus the index of a cross diagonal normal.
+    /// * `texture_layer` - The layer of the voxel texture array, below `MAX_TEXTURE_LAYERS`.
+    /// * `ambient_occlusion` - The ambient occlusion level, from 0 to `chunk::MAX_AMBIENT_OCCLUSION`.
+    /// * `light` - The light levels of the vertex.
+    pub fn new(
+        position: Vector3<f32>,
+        tex_coords: [f32; 2],
+        normal_index: u8,
+        texture_layer: u32,
+        ambient_occlusion: u8,
+        light: LightLevels,
+    ) -> Self {
+        let pack = |value: f32| ((value * POSITION_STEPS).round() as u32).min(0xFF);
+        let position = pack(position.x) | pack(position.y) << 8 | pack(position.z) << 16;
+        let normal = (normal_index as u32 & 0xF) << 24;
+        let ambient_occlusion =
+            (ambient_occlusion.min(chunk::MAX_AMBIENT_OCCLUSION) as u32 & 0x3) << 28;
+        let tex_coords = pack(tex_coords[0]) | pack(tex_coords[1]) << 8;
+        let light = (light.block as u32 & 0xF) << 16 | (light.sky as u32 & 0xF) << 20;
+        let texture_layer = texture_layer.min(MAX_TEXTURE_LAYERS - 1) << 24;
+
+        Self {
+            data: [
+                position | normal | ambient_occlusion,
+                tex_coords | light | texture_layer,
+            ],
+        }
+    }
+
     /// Returns the vertex buffer layout.
     pub fn buffer_layout() -> VertexBufferLayout<'static> {
         VertexBufferLayout {
@@ -30,7 +74,7 @@ impl Vertex {
 }
 
 /// The number of vertex attributes.
-pub const VERTEX_ATTRIBUTE_COUNT: usize = 4;
+pub const VERTEX_ATTRIBUTE_COUNT: usize = 1;
 /// The vertex attributes.
 pub const VERTEX_ATTRIBUTES: [wgpu::VertexAttribute; VERTEX_ATTRIBUTE_COUNT] =
-    wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x2, 2 => Float32x3, 3 => Uint32x3];
+    wgpu::vertex_attr_array![0 => Uint32x2];
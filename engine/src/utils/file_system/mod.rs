@@ -12,6 +12,8 @@ pub fn get_asset_dir() -> PathBuf {
     PathBuf::from(ASSETS_DIR)
 }
 pub const CONFIG_PATH: &str = "./config/config.yml";
+/// The relative path to the directory the worlds are saved in.
+pub const SAVES_DIR: &str = "./saves";
 
 /// Reads the config and returns the result.
 pub fn read_config() -> io::Result<String> {